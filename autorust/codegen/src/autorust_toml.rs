@@ -94,6 +94,11 @@ pub struct Properties {
     /// Some properties need to be left as `serde_json::Value`
     #[serde(default)]
     pub invalid_type: Vec<Vec<String>>,
+
+    /// Some properties hold credentials and need to be wrapped in `crate::secret::Secret`,
+    /// which redacts them in `Debug` output
+    #[serde(default)]
+    pub secret: Vec<Vec<String>>,
 }
 
 impl<'a> PackageConfig {
@@ -344,4 +349,20 @@ mod tests {
         assert_eq!("innererror", config.properties.boxed[0][2]);
         Ok(())
     }
+
+    #[test]
+    fn secret() -> Result<()> {
+        let config: PackageConfig = toml::from_str(
+            r#"
+            [properties]
+            secret = [
+                ["../vsts-rest-api-specs.patched/specification/tokens/7.1/tokens.json", "PatToken", "token"]
+              ]
+            "#,
+        )?;
+        assert_eq!(1, config.properties.secret.len());
+        assert_eq!("PatToken", config.properties.secret[0][1]);
+        assert_eq!("token", config.properties.secret[0][2]);
+        Ok(())
+    }
 }
@@ -29,6 +29,7 @@ pub struct CodeGen<'a> {
     optional_properties: HashSet<PropertyName>,
     fix_case_properties: HashSet<&'a str>,
     invalid_types: HashSet<PropertyName>,
+    secret_properties: HashSet<PropertyName>,
 
     union_types: HashSet<String>,
 }
@@ -55,6 +56,7 @@ impl<'a> CodeGen<'a> {
         optional_properties: HashSet<PropertyName>,
         fix_case_properties: HashSet<&'a str>,
         invalid_types: HashSet<PropertyName>,
+        secret_properties: HashSet<PropertyName>,
     ) -> Result<Self> {
         let spec = Spec::read_files(&crate_config.input_files)?;
         Ok(Self {
@@ -64,6 +66,7 @@ impl<'a> CodeGen<'a> {
             optional_properties,
             fix_case_properties,
             invalid_types,
+            secret_properties,
             union_types: HashSet::new(),
         })
     }
@@ -96,6 +99,10 @@ impl<'a> CodeGen<'a> {
         self.box_properties.contains(prop_nm)
     }
 
+    pub fn should_wrap_secret(&self, prop_nm: &PropertyName) -> bool {
+        self.secret_properties.contains(prop_nm)
+    }
+
     pub fn has_xml(&self) -> bool {
         self.spec.has_xml()
             || self
@@ -147,6 +154,7 @@ pub struct TypeNameCode {
     impl_into: bool,
     allow_impl_into: bool,
     boxed: bool,
+    secret: bool,
     qualify_models: bool,
     allow_qualify_models: bool,
     type_name: Option<TypeName>,
@@ -273,6 +281,11 @@ impl TypeNameCode {
         self.boxed = boxed;
     }
 
+    /// Wraps the type in `crate::secret::Secret`
+    pub fn secret(&mut self, secret: bool) {
+        self.secret = secret;
+    }
+
     pub fn qualify_models(&mut self, qualify_models: bool) {
         self.qualify_models = qualify_models;
     }
@@ -306,6 +319,9 @@ impl TypeNameCode {
         if self.force_value {
             tp = Type::from(tp_json_value())
         }
+        if self.secret {
+            tp = generic_type(tp_secret(), tp);
+        }
         if self.is_reference() {
             let tr = TypeReference {
                 and_token: Default::default(),
@@ -392,6 +408,7 @@ impl From<TypePath> for TypeNameCode {
             impl_into: false,
             allow_impl_into: true,
             boxed: false,
+            secret: false,
             qualify_models: false,
             allow_qualify_models: false,
             type_name: None,
@@ -504,6 +521,10 @@ fn tp_box() -> TypePath {
     parse_type_path("Box").unwrap() // std::boxed::Box
 }
 
+fn tp_secret() -> TypePath {
+    parse_type_path("crate::secret::Secret").unwrap()
+}
+
 fn tp_str() -> TypePath {
     parse_type_path("str").unwrap() // std::str
 }
@@ -1187,6 +1187,11 @@ fn create_struct(
         }
         type_name.boxed(boxed);
 
+        // see if a field holds a secret that must not be exposed by `Debug`
+        if cg.should_wrap_secret(prop_nm) {
+            type_name.secret(true);
+        }
+
         doc_comments.push(DocCommentCode::from(
             &property.schema.schema.common.description,
         ));
@@ -130,6 +130,12 @@ pub fn run<'a>(
         .iter()
         .map(to_property_name)
         .collect();
+    let secret_properties: HashSet<PropertyName> = package_config
+        .properties
+        .secret
+        .iter()
+        .map(to_property_name)
+        .collect();
 
     let mut cg = CodeGen::new(
         crate_config,
@@ -137,6 +143,7 @@ pub fn run<'a>(
         optional_properties,
         fix_case_properties,
         invalid_types,
+        secret_properties,
     )?;
 
     // create models from schemas
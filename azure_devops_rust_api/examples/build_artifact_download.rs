@@ -0,0 +1,46 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+// build_artifact_download.rs
+// Download the contents of a build artifact to a local directory.
+use anyhow::Result;
+use azure_devops_rust_api::build;
+use std::env;
+
+mod utils;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Get authentication credential
+    let credential = utils::get_credential()?;
+
+    // Get ADO server configuration via environment variables
+    let organization = env::var("ADO_ORGANIZATION").expect("Must define ADO_ORGANIZATION");
+    let project = env::var("ADO_PROJECT").expect("Must define ADO_PROJECT");
+    let usage = "Usage: build_artifact_download <build-id> <artifact-name> <directory> [extension]";
+    let build_id: i32 = env::args()
+        .nth(1)
+        .expect(usage)
+        .parse()
+        .expect("build-id must be a number");
+    let artifact_name = env::args().nth(2).expect(usage);
+    let directory = env::args().nth(3).expect(usage);
+    let extension = env::args().nth(4);
+
+    // Create a build client
+    let build_client = build::ClientBuilder::new(credential).build();
+
+    // Download the artifact, optionally only the files with the given extension
+    let mut builder =
+        build_client.download_artifact(&organization, &project, build_id, artifact_name, directory);
+    if let Some(extension) = extension {
+        builder = builder.filter(move |path| path.ends_with(&extension));
+    }
+    let files = builder.await?;
+    for file in &files {
+        println!("{} ({} bytes)", file.local_path.display(), file.size);
+    }
+    println!("Downloaded {} files", files.len());
+
+    Ok(())
+}
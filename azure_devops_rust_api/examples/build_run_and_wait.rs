@@ -0,0 +1,68 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+// build_run_and_wait.rs
+// Queue a build and report its progress until it completes.
+use anyhow::Result;
+use azure_devops_rust_api::build;
+use azure_devops_rust_api::build_run::{BuildEvent, BuildOutcome};
+use futures::StreamExt;
+use std::env;
+
+mod utils;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Get authentication credential
+    let credential = utils::get_credential()?;
+
+    // Get ADO server configuration via environment variables
+    let organization = env::var("ADO_ORGANIZATION").expect("Must define ADO_ORGANIZATION");
+    let project = env::var("ADO_PROJECT").expect("Must define ADO_PROJECT");
+    let usage = "Usage: build_run_and_wait <definition-id> [source-branch]";
+    let definition_id: i32 = env::args()
+        .nth(1)
+        .expect(usage)
+        .parse()
+        .expect("definition-id must be a number");
+    let source_branch = env::args().nth(2);
+
+    // Create a build client
+    let build_client = build::ClientBuilder::new(credential).build();
+
+    // Queue the build
+    let mut builder = build_client.run_and_wait(&organization, &project, definition_id);
+    if let Some(source_branch) = source_branch {
+        builder = builder.source_branch(source_branch);
+    }
+    let run = builder.await?;
+    println!("Queued build {}", run.build().id);
+
+    // Report progress until the build completes
+    let mut events = run.events();
+    while let Some(event) = events.next().await {
+        match event? {
+            BuildEvent::Started(_) => println!("Build started"),
+            BuildEvent::RecordStarted(record) => println!(
+                "Started {} {}",
+                record.type_.unwrap_or_default(),
+                record.name.unwrap_or_default()
+            ),
+            BuildEvent::RecordCompleted(record) => println!(
+                "Completed {} {}: {:?}",
+                record.type_.unwrap_or_default(),
+                record.name.unwrap_or_default(),
+                record.result
+            ),
+            BuildEvent::Completed(outcome) => match outcome {
+                BuildOutcome::Succeeded(_) => println!("Build succeeded"),
+                BuildOutcome::PartiallySucceeded(_) => println!("Build partially succeeded"),
+                BuildOutcome::Failed(_) => println!("Build failed"),
+                BuildOutcome::Canceled(_) => println!("Build canceled"),
+            },
+            BuildEvent::Queued(_) => {}
+        }
+    }
+
+    Ok(())
+}
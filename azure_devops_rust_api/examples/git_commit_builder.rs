@@ -0,0 +1,41 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+// git_commit_builder.rs
+// Pushing a commit to a repo using the commit builder.
+use anyhow::Result;
+use azure_devops_rust_api::git;
+use std::env;
+
+mod utils;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Get authentication credential
+    let credential = utils::get_credential()?;
+
+    // Get ADO server configuration via environment variables
+    let organization = env::var("ADO_ORGANIZATION").expect("Must define ADO_ORGANIZATION");
+    let project = env::var("ADO_PROJECT").expect("Must define ADO_PROJECT");
+    let usage = "Usage: git_commit_builder <repository-name> <source-branch> <target-branch> <filename> <file-content>";
+    let repository_name = env::args().nth(1).expect(usage);
+    let source_branch = env::args().nth(2).expect(usage);
+    let target_branch = env::args().nth(3).expect(usage);
+    let filename = env::args().nth(4).expect(usage);
+    let file_content = env::args().nth(5).expect(usage);
+
+    // Create a git client
+    let git_client = git::ClientBuilder::new(credential).build();
+
+    // Push a commit to the target branch, creating it from the source branch if necessary.
+    // The current head of the target branch is looked up automatically.
+    let push = git_client
+        .commit(&organization, &project, &repository_name, &target_branch)
+        .create_branch_from(&source_branch)
+        .edit_text(&filename, file_content)
+        .message("This is a commit via the Azure DevOps API...")
+        .await?;
+    println!("Pushed commit.\npush: {:#?}", push);
+
+    Ok(())
+}
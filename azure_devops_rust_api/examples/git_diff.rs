@@ -0,0 +1,42 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+// git_diff.rs
+// Print a unified diff between two branches of a repo.
+use anyhow::Result;
+use azure_devops_rust_api::git;
+use azure_devops_rust_api::git_diff::GitVersion;
+use std::env;
+
+mod utils;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Get authentication credential
+    let credential = utils::get_credential()?;
+
+    // Get ADO server configuration via environment variables
+    let organization = env::var("ADO_ORGANIZATION").expect("Must define ADO_ORGANIZATION");
+    let project = env::var("ADO_PROJECT").expect("Must define ADO_PROJECT");
+    let usage = "Usage: git_diff <repository-name> <base-branch> <target-branch>";
+    let repository_name = env::args().nth(1).expect(usage);
+    let base_branch = env::args().nth(2).expect(usage);
+    let target_branch = env::args().nth(3).expect(usage);
+
+    // Create a git client
+    let git_client = git::ClientBuilder::new(credential).build();
+
+    // Diff the target branch against its common commit with the base branch
+    let diff = git_client
+        .diff(
+            &organization,
+            &project,
+            &repository_name,
+            GitVersion::branch(&base_branch),
+            GitVersion::branch(&target_branch),
+        )
+        .await?;
+    print!("{diff}");
+
+    Ok(())
+}
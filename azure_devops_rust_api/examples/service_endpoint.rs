@@ -33,7 +33,9 @@ async fn main() -> Result<()> {
     for endpoint in service_endpoints.iter() {
         println!(
             "{:38} {:40} {}",
-            endpoint.id, endpoint.name, endpoint.description
+            endpoint.id.as_deref().unwrap_or_default(),
+            endpoint.name,
+            endpoint.description
         );
     }
 
@@ -0,0 +1,84 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+// wit_work_item_tree.rs
+// Work item hierarchy traversal example.
+use anyhow::{Context, Result};
+use azure_devops_rust_api::wit;
+use azure_devops_rust_api::wit_graph::{self, WorkItemTreeNode};
+use std::env;
+
+mod utils;
+
+// Extract a string field from a work item.
+fn field<'a>(work_item: &'a wit::models::WorkItem, name: &str) -> &'a str {
+    work_item
+        .fields
+        .get(name)
+        .and_then(|value| value.as_str())
+        .unwrap_or("<unknown>")
+}
+
+fn print_tree(node: &WorkItemTreeNode, indent: usize) {
+    println!(
+        "{:indent$}[{}] {}: {}",
+        "",
+        node.work_item.id,
+        field(node.work_item, "System.WorkItemType"),
+        field(node.work_item, "System.Title"),
+    );
+    for child in &node.children {
+        print_tree(child, indent + 2);
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // Get authentication credential
+    let credential = utils::get_credential()?;
+
+    // Get ADO configuration via environment variables
+    let organization = env::var("ADO_ORGANIZATION").expect("Must define ADO_ORGANIZATION");
+    let project = env::var("ADO_PROJECT").expect("Must define ADO_PROJECT");
+    let work_item_id: i32 = env::args()
+        .nth(1)
+        .expect("Usage: wit_work_item_tree <work_item_id> [max_depth]")
+        .parse()
+        .expect("integer id");
+    let max_depth: usize = env::args()
+        .nth(2)
+        .map(|depth| depth.parse().expect("integer depth"))
+        .unwrap_or(5);
+
+    // Create a wit client
+    let wit_client = wit::ClientBuilder::new(credential).build();
+
+    // Walk the parent/child hierarchy below the specified work item
+    let graph = wit_client
+        .traverse_work_items(&organization, &project, [work_item_id])
+        .link_types([wit_graph::HIERARCHY_FORWARD])
+        .max_depth(max_depth)
+        .await?;
+
+    println!(
+        "Fetched {} work items with {} links",
+        graph.work_items().count(),
+        graph.links().len()
+    );
+    if !graph.missing().is_empty() {
+        println!("Missing work items: {:?}", graph.missing());
+    }
+    if !graph.truncated().is_empty() {
+        println!("Depth limit reached at: {:?}", graph.truncated());
+    }
+    if let Some(cycle) = graph.find_cycle() {
+        println!("Cycle detected: {cycle:?}");
+    }
+
+    let tree = graph
+        .tree(work_item_id)
+        .context("Root work item not found")?;
+    print_tree(&tree, 0);
+
+    Ok(())
+}
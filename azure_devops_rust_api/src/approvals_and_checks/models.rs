@@ -57,6 +57,9 @@ pub struct Approval {
     #[doc = "Current user permissions for approval object."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub permissions: Option<approval::Permissions>,
+    #[doc = "The pipeline whose run is waiting for an approval."]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<ApprovalPipelineReference>,
     #[doc = "Overall status of the approval."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub status: Option<approval::Status>,
@@ -248,6 +251,39 @@ impl ApprovalNotificationEventBase {
         Self::default()
     }
 }
+#[doc = "The pipeline run waiting for an approval."]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
+pub struct ApprovalPipelineOwner {
+    #[doc = "ID of the pipeline run."]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i32>,
+    #[doc = "Name of the pipeline run."]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+impl ApprovalPipelineOwner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+#[doc = "The pipeline whose run is waiting for an approval."]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
+pub struct ApprovalPipelineReference {
+    #[doc = "ID of the pipeline definition."]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[doc = "Name of the pipeline definition."]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[doc = "The pipeline run waiting for an approval."]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<ApprovalPipelineOwner>,
+}
+impl ApprovalPipelineReference {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
 #[doc = "Request to create a new approval."]
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Default)]
 pub struct ApprovalRequest {
@@ -421,6 +457,14 @@ pub struct ApprovalUpdateParameters {
     #[doc = "Gets or sets comment."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub comment: Option<String>,
+    #[doc = "Defer the approval until this time."]
+    #[serde(
+        rename = "deferredTo",
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::date_time::rfc3339::option"
+    )]
+    pub deferred_to: Option<time::OffsetDateTime>,
     #[serde(
         rename = "reassignTo",
         default,
@@ -0,0 +1,530 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Pending approvals of YAML pipeline runs.
+//!
+//! `approvals_and_checks::Client::pending_approvals()` lists the approvals that are waiting
+//! on a pipeline run or assigned to given approvers, together with the stage they guard and
+//! the check configuration (and so the protected resource, such as an environment) they
+//! belong to. `approve_approvals()` and `reject_approvals()` complete approvals in bulk.
+//!
+//! ```rust
+//!     let pending = approvals_client
+//!         .pending_approvals(&organization, &project)
+//!         .run(run_id)
+//!         .await?;
+//!     for approval in &pending {
+//!         println!(
+//!             "{} {:?} ({:?})",
+//!             approval.approval_id(),
+//!             approval.stage_name,
+//!             approval.resource().and_then(|resource| resource.name.as_deref())
+//!         );
+//!     }
+//!     approvals_client
+//!         .approve_approvals(&organization, &project, pending.iter().map(|a| a.approval_id()))
+//!         .comment("Approved by release tooling")
+//!         .await?;
+//! ```
+
+use crate::approvals_and_checks::models::{
+    approval, approval_update_parameters, Approval, ApprovalUpdateParameters, CheckConfiguration,
+    CheckSuite, Resource,
+};
+use azure_core::error::{Error, ErrorKind};
+use azure_core::{headers, Method, Request, Url};
+use serde::Deserialize;
+use std::collections::hash_map::{Entry, HashMap};
+use time::OffsetDateTime;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Timeline record type of a run's approvals.
+const APPROVAL_RECORD_TYPE: &str = "Checkpoint.Approval";
+
+/// An approval that is waiting to be completed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PendingApproval {
+    pub approval: Approval,
+    /// The ID of the pipeline run waiting for the approval.
+    pub run_id: Option<i32>,
+    /// The name of the stage guarded by the approval.
+    pub stage_name: Option<String>,
+    /// The check configuration the approval belongs to, if it could be determined.
+    pub check_configuration: Option<CheckConfiguration>,
+}
+
+impl PendingApproval {
+    /// The ID of the approval.
+    pub fn approval_id(&self) -> &str {
+        self.approval.id.as_deref().unwrap_or_default()
+    }
+
+    /// The resource (for example an environment) protected by the approval.
+    pub fn resource(&self) -> Option<&Resource> {
+        self.check_configuration
+            .as_ref()?
+            .check_configuration_ref
+            .resource
+            .as_ref()
+    }
+}
+
+impl crate::approvals_and_checks::Client {
+    /// Creates a builder that lists pending approvals.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    pub fn pending_approvals(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+    ) -> PendingApprovalsBuilder {
+        PendingApprovalsBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            run_id: None,
+            approvers: Vec::new(),
+            include_checks: true,
+        }
+    }
+
+    /// Creates a builder that approves approvals.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `approval_ids`: The IDs of the approvals.
+    pub fn approve_approvals<I, S>(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        approval_ids: I,
+    ) -> UpdateApprovalsBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        UpdateApprovalsBuilder::new(
+            self.clone(),
+            organization.into(),
+            project.into(),
+            approval_ids,
+            approval_update_parameters::Status::Approved,
+        )
+    }
+
+    /// Creates a builder that rejects approvals.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `approval_ids`: The IDs of the approvals.
+    pub fn reject_approvals<I, S>(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        approval_ids: I,
+    ) -> UpdateApprovalsBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        UpdateApprovalsBuilder::new(
+            self.clone(),
+            organization.into(),
+            project.into(),
+            approval_ids,
+            approval_update_parameters::Status::Rejected,
+        )
+    }
+}
+
+/// `PendingApprovalsBuilder` provides a mechanism for filtering pending approvals.
+///
+/// Invoke `.await` to list the approvals.
+#[derive(Clone)]
+pub struct PendingApprovalsBuilder {
+    client: crate::approvals_and_checks::Client,
+    organization: String,
+    project: String,
+    run_id: Option<i32>,
+    approvers: Vec<String>,
+    include_checks: bool,
+}
+
+impl PendingApprovalsBuilder {
+    /// Only list approvals waiting on this pipeline run.
+    #[must_use]
+    pub fn run(mut self, run_id: i32) -> Self {
+        self.run_id = Some(run_id);
+        self
+    }
+
+    /// Only list approvals assigned to this identity (or a group it belongs to).
+    /// May be called multiple times.
+    #[must_use]
+    pub fn approver(mut self, identity_id: impl Into<String>) -> Self {
+        self.approvers.push(identity_id.into());
+        self
+    }
+
+    /// Whether to look up the stage and check configuration of each approval (default `true`).
+    /// This requires reading the timeline and check suites of each run.
+    #[must_use]
+    pub fn include_checks(mut self, include_checks: bool) -> Self {
+        self.include_checks = include_checks;
+        self
+    }
+
+    async fn list(self) -> azure_core::Result<Vec<PendingApproval>> {
+        let mut query = self
+            .client
+            .approvals_client()
+            .query(&self.organization, &self.project)
+            .state("pending")
+            .expand("steps");
+        if !self.approvers.is_empty() {
+            query = query.user_ids(self.approvers.join(","));
+        }
+        let mut pending: Vec<PendingApproval> = query
+            .await?
+            .value
+            .into_iter()
+            .filter(|approval| approval.status == Some(approval::Status::Pending))
+            .map(|approval| PendingApproval {
+                run_id: approval
+                    .pipeline
+                    .as_ref()
+                    .and_then(|pipeline| pipeline.owner.as_ref())
+                    .and_then(|owner| owner.id),
+                approval,
+                stage_name: None,
+                check_configuration: None,
+            })
+            .filter(|approval| {
+                self.run_id
+                    .map_or(true, |run_id| approval.run_id == Some(run_id))
+            })
+            .collect();
+        if !self.include_checks {
+            return Ok(pending);
+        }
+
+        let mut run_ids: Vec<i32> = pending.iter().filter_map(|a| a.run_id).collect();
+        run_ids.sort_unstable();
+        run_ids.dedup();
+        let mut configurations: HashMap<i32, CheckConfiguration> = HashMap::new();
+        for run_id in run_ids {
+            let records = self.timeline(run_id).await?;
+            let mut suites: HashMap<String, CheckSuite> = HashMap::new();
+            for approval in pending.iter_mut().filter(|a| a.run_id == Some(run_id)) {
+                let location = match approval_location(&records, approval.approval_id()) {
+                    Some(location) => location,
+                    None => continue,
+                };
+                approval.stage_name = location.stage_name;
+                let check_suite_id = match location.check_suite_id {
+                    Some(check_suite_id) => check_suite_id,
+                    None => continue,
+                };
+                let suite = match suites.entry(check_suite_id) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => {
+                        let suite = self
+                            .client
+                            .check_evaluations_client()
+                            .get(&self.organization, &self.project, entry.key())
+                            .await?;
+                        entry.insert(suite)
+                    }
+                };
+                let configuration_id = suite
+                    .check_runs
+                    .iter()
+                    .find(|run| same_id(run.id.as_deref(), approval.approval_id()))
+                    .and_then(|run| run.check_configuration_ref.as_ref())
+                    .and_then(|configuration| configuration.id);
+                if let Some(configuration_id) = configuration_id {
+                    let configuration = match configurations.entry(configuration_id) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            let configuration = self
+                                .client
+                                .check_configurations_client()
+                                .get(&self.organization, &self.project, configuration_id)
+                                .await?;
+                            entry.insert(configuration)
+                        }
+                    };
+                    approval.check_configuration = Some(configuration.clone());
+                }
+            }
+        }
+        Ok(pending)
+    }
+
+    /// The records of a run's timeline.
+    async fn timeline(&self, run_id: i32) -> azure_core::Result<Vec<TimelineRecord>> {
+        let mut url = Url::parse(&format!(
+            "{}/{}/{}/_apis/build/builds/{}/timeline",
+            self.client.endpoint(),
+            &self.organization,
+            &self.project,
+            run_id
+        ))?;
+        url.query_pairs_mut()
+            .append_pair(azure_core::query_param::API_VERSION, "7.1-preview");
+        let mut request = Request::new(url, Method::Get);
+        if let Some(auth_header) = self
+            .client
+            .token_credential()
+            .http_authorization_header(&self.client.scopes())
+            .await?
+        {
+            request.insert_header(headers::AUTHORIZATION, auth_header);
+        }
+        request.set_body(azure_core::EMPTY_BODY);
+        let timeline: Timeline = self.client.send(&mut request).await?.json().await?;
+        Ok(timeline.records)
+    }
+}
+
+impl std::future::IntoFuture for PendingApprovalsBuilder {
+    type Output = azure_core::Result<Vec<PendingApproval>>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<Vec<PendingApproval>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.list())
+    }
+}
+
+/// `UpdateApprovalsBuilder` provides a mechanism for setting options when completing approvals.
+///
+/// Invoke `.await` to update the approvals in a single request, which returns the updated
+/// approvals.
+#[derive(Clone)]
+pub struct UpdateApprovalsBuilder {
+    client: crate::approvals_and_checks::Client,
+    organization: String,
+    project: String,
+    approval_ids: Vec<String>,
+    status: approval_update_parameters::Status,
+    comment: Option<String>,
+    deferred_to: Option<OffsetDateTime>,
+}
+
+impl UpdateApprovalsBuilder {
+    fn new<I, S>(
+        client: crate::approvals_and_checks::Client,
+        organization: String,
+        project: String,
+        approval_ids: I,
+        status: approval_update_parameters::Status,
+    ) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            client,
+            organization,
+            project,
+            approval_ids: approval_ids.into_iter().map(Into::into).collect(),
+            status,
+            comment: None,
+            deferred_to: None,
+        }
+    }
+
+    /// Comment recorded on each approval.
+    #[must_use]
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Defers the approvals: they take effect at the given time rather than immediately.
+    /// Only supported when approving.
+    #[must_use]
+    pub fn deferred_to(mut self, deferred_to: OffsetDateTime) -> Self {
+        self.deferred_to = Some(deferred_to);
+        self
+    }
+
+    fn parameters(&self) -> azure_core::Result<Vec<ApprovalUpdateParameters>> {
+        if self.deferred_to.is_some() && self.status != approval_update_parameters::Status::Approved
+        {
+            return Err(Error::message(
+                ErrorKind::Other,
+                "only approvals can be deferred",
+            ));
+        }
+        Ok(self
+            .approval_ids
+            .iter()
+            .map(|approval_id| ApprovalUpdateParameters {
+                approval_id: Some(approval_id.clone()),
+                comment: self.comment.clone(),
+                deferred_to: self.deferred_to,
+                status: Some(self.status.clone()),
+                ..Default::default()
+            })
+            .collect())
+    }
+
+    async fn update(self) -> azure_core::Result<Vec<Approval>> {
+        let parameters = self.parameters()?;
+        if parameters.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(self
+            .client
+            .approvals_client()
+            .update(&self.organization, parameters, &self.project)
+            .await?
+            .value)
+    }
+}
+
+impl std::future::IntoFuture for UpdateApprovalsBuilder {
+    type Output = azure_core::Result<Vec<Approval>>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<Vec<Approval>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.update())
+    }
+}
+
+/// The subset of a build timeline needed to locate approvals.
+#[derive(Clone, Debug, Deserialize)]
+struct Timeline {
+    #[serde(default)]
+    records: Vec<TimelineRecord>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct TimelineRecord {
+    id: String,
+    #[serde(rename = "parentId", default)]
+    parent_id: Option<String>,
+    #[serde(rename = "type", default)]
+    type_: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+}
+
+/// Where an approval sits in a run's timeline.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct ApprovalLocation {
+    check_suite_id: Option<String>,
+    stage_name: Option<String>,
+}
+
+fn same_id(a: Option<&str>, b: &str) -> bool {
+    a.map_or(false, |a| a.eq_ignore_ascii_case(b))
+}
+
+/// Finds the check suite (the approval's parent checkpoint) and stage of an approval.
+fn approval_location(records: &[TimelineRecord], approval_id: &str) -> Option<ApprovalLocation> {
+    let by_id: HashMap<String, &TimelineRecord> = records
+        .iter()
+        .map(|record| (record.id.to_ascii_lowercase(), record))
+        .collect();
+    let parent = |record: &TimelineRecord| {
+        record
+            .parent_id
+            .as_ref()
+            .and_then(|parent_id| by_id.get(&parent_id.to_ascii_lowercase()))
+            .copied()
+    };
+    let record = records.iter().find(|record| {
+        record.type_.as_deref() == Some(APPROVAL_RECORD_TYPE)
+            && same_id(Some(&record.id), approval_id)
+    })?;
+    let mut location = ApprovalLocation::default();
+    let mut ancestor = parent(record);
+    while let Some(record) = ancestor {
+        match record.type_.as_deref() {
+            Some("Checkpoint") if location.check_suite_id.is_none() => {
+                location.check_suite_id = Some(record.id.clone());
+            }
+            Some("Stage") => {
+                location.stage_name = record.name.clone();
+                break;
+            }
+            _ => {}
+        }
+        ancestor = parent(record);
+    }
+    Some(location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_approval_location() {
+        let timeline: Timeline = serde_json::from_value(json!({
+            "records": [
+                { "id": "stage", "type": "Stage", "name": "Deploy" },
+                { "id": "CHECKPOINT", "parentId": "stage", "type": "Checkpoint" },
+                { "id": "A1", "parentId": "checkpoint", "type": "Checkpoint.Approval" },
+                { "id": "job", "parentId": "stage", "type": "Job" }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(
+            approval_location(&timeline.records, "a1"),
+            Some(ApprovalLocation {
+                check_suite_id: Some("CHECKPOINT".to_string()),
+                stage_name: Some("Deploy".to_string()),
+            })
+        );
+        assert_eq!(approval_location(&timeline.records, "job"), None);
+    }
+
+    #[test]
+    fn test_update_parameters() {
+        let client =
+            crate::approvals_and_checks::ClientBuilder::new(crate::Credential::unauthenticated())
+                .build();
+        let deferred_to = OffsetDateTime::UNIX_EPOCH;
+        let approve = client
+            .approve_approvals("org", "project", ["a1", "a2"])
+            .comment("ok")
+            .deferred_to(deferred_to);
+        let parameters = approve.parameters().unwrap();
+        assert_eq!(parameters.len(), 2);
+        assert_eq!(parameters[1].approval_id.as_deref(), Some("a2"));
+        assert_eq!(parameters[1].deferred_to, Some(deferred_to));
+        assert_eq!(
+            parameters[1].status,
+            Some(approval_update_parameters::Status::Approved)
+        );
+        assert!(client
+            .reject_approvals("org", "project", ["a1"])
+            .deferred_to(deferred_to)
+            .parameters()
+            .is_err());
+    }
+
+    #[test]
+    fn test_approval_pipeline() {
+        let approval: Approval = serde_json::from_value(json!({
+            "id": "a1",
+            "status": "pending",
+            "pipeline": { "id": "12", "name": "deploy", "owner": { "id": 345, "name": "20240101.1" } }
+        }))
+        .unwrap();
+        let owner = approval.pipeline.and_then(|pipeline| pipeline.owner);
+        assert_eq!(owner.and_then(|owner| owner.id), Some(345));
+    }
+}
@@ -5,6 +5,7 @@
 //!
 //! For more background information on Azure DevOps authentication see: [Azure DevOps authentication](https://docs.microsoft.com/en-us/azure/devops/integrate/get-started/authentication/authentication-guidance)
 
+use crate::secret::Secret;
 use azure_core::auth::TokenCredential;
 use azure_core::error::{Result, ResultExt};
 use base64::{prelude::BASE64_STANDARD, Engine};
@@ -18,10 +19,23 @@ use std::sync::Arc;
 #[derive(Clone)]
 pub enum Credential {
     Unauthenticated,
-    Pat(String),
+    Pat(Secret),
     TokenCredential(Arc<dyn TokenCredential>),
 }
 
+impl std::fmt::Debug for Credential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Credential::Unauthenticated => f.write_str("Unauthenticated"),
+            Credential::Pat(pat) => f.debug_tuple("Pat").field(pat).finish(),
+            Credential::TokenCredential(token_credential) => f
+                .debug_tuple("TokenCredential")
+                .field(token_credential)
+                .finish(),
+        }
+    }
+}
+
 impl Credential {
     /// Creates a new `Credential` for unauthenticated operations.
     pub fn unauthenticated() -> Self {
@@ -31,7 +45,7 @@ impl Credential {
     /// Creates a new `Credential` using the supplied PAT token.
     pub fn from_pat(pat: impl Into<String>) -> Self {
         let pat = pat.into();
-        Credential::Pat(pat)
+        Credential::Pat(Secret::new(pat))
     }
 
     /// Creates a new `Credential` using the supplied object that implements [`TokenCredential`](https://docs.rs/azure_core/latest/azure_core/auth/trait.TokenCredential.html).
@@ -56,7 +70,7 @@ impl Credential {
             // PAT tokens are passed using Basic authentication.
             Credential::Pat(pat) => Ok(Some(format!(
                 "Basic {}",
-                BASE64_STANDARD.encode(format!(":{}", &pat))
+                BASE64_STANDARD.encode(format!(":{}", pat.secret()))
             ))),
             // OAuth tokens are passed using Bearer authentication.
             Credential::TokenCredential(token_credential) => {
@@ -1874,7 +1874,7 @@ pub struct BuildDefinitionVariable {
     pub is_secret: Option<bool>,
     #[doc = "The value of the variable."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
+    pub value: Option<crate::secret::Secret<String>>,
 }
 impl BuildDefinitionVariable {
     pub fn new() -> Self {
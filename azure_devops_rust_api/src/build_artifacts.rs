@@ -0,0 +1,628 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Download build and pipeline artifacts to a directory.
+//!
+//! Build artifacts are stored either in a file container (`Container`) or in the pipeline
+//! artifact service (`PipelineArtifact`). File container artifacts are listed and downloaded
+//! file by file, so filtered-out files are never transferred; other artifacts are downloaded
+//! as a zip archive, streamed to disk and unpacked.
+//!
+//! Downloaded files are verified: file container files against their recorded length and
+//! zip entries against their CRC-32 checksum.
+//!
+//! ```rust
+//!     let files = build_client
+//!         .download_artifact(&organization, &project, build_id, "drop", "./drop")
+//!         .filter(|path| path.ends_with(".nupkg"))
+//!         .await?;
+//!     for file in files {
+//!         println!("{} ({} bytes)", file.path, file.size);
+//!     }
+//! ```
+
+use azure_core::error::{Error, ErrorKind, ResultExt};
+use azure_core::{headers, Method, Request, Response, Url};
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde::Deserialize;
+use std::io::{Read, Seek};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+/// A file written by an artifact download.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DownloadedFile {
+    /// The path of the file within the artifact, using `/` separators.
+    pub path: String,
+    /// The location the file was written to.
+    pub local_path: PathBuf,
+    /// The size of the file in bytes.
+    pub size: u64,
+}
+
+type PathFilter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+#[cfg(feature = "build_artifacts")]
+impl crate::build::Client {
+    /// Creates a builder that downloads the contents of a build artifact to `directory`.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `build_id`: The ID of the build.
+    /// * `artifact_name`: The name of the artifact.
+    /// * `directory`: The directory to write the artifact's files to. It is created if needed.
+    pub fn download_artifact(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        build_id: i32,
+        artifact_name: impl Into<String>,
+        directory: impl Into<PathBuf>,
+    ) -> DownloadArtifactBuilder {
+        DownloadArtifactBuilder::new(
+            Backend::Build(self.clone(), build_id),
+            organization.into(),
+            project.into(),
+            artifact_name.into(),
+            directory.into(),
+        )
+    }
+}
+
+#[cfg(feature = "pipelines_artifacts")]
+impl crate::pipelines::Client {
+    /// Creates a builder that downloads the contents of a pipeline run artifact to `directory`.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `pipeline_id`: ID of the pipeline.
+    /// * `run_id`: ID of the run of that pipeline.
+    /// * `artifact_name`: Name of the artifact.
+    /// * `directory`: The directory to write the artifact's files to. It is created if needed.
+    pub fn download_artifact(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        pipeline_id: i32,
+        run_id: i32,
+        artifact_name: impl Into<String>,
+        directory: impl Into<PathBuf>,
+    ) -> DownloadArtifactBuilder {
+        DownloadArtifactBuilder::new(
+            Backend::Pipelines(self.clone(), pipeline_id, run_id),
+            organization.into(),
+            project.into(),
+            artifact_name.into(),
+            directory.into(),
+        )
+    }
+}
+
+/// The service client used to fetch the artifact, with the build or pipeline run it belongs to.
+#[derive(Clone)]
+enum Backend {
+    #[cfg(feature = "build_artifacts")]
+    Build(crate::build::Client, i32),
+    #[cfg(feature = "pipelines_artifacts")]
+    Pipelines(crate::pipelines::Client, i32, i32),
+}
+
+impl Backend {
+    fn endpoint(&self) -> &Url {
+        match self {
+            #[cfg(feature = "build_artifacts")]
+            Backend::Build(client, ..) => client.endpoint(),
+            #[cfg(feature = "pipelines_artifacts")]
+            Backend::Pipelines(client, ..) => client.endpoint(),
+        }
+    }
+
+    async fn get(&self, url: Url, accept: &str) -> azure_core::Result<Response> {
+        let mut request = Request::new(url, Method::Get);
+        let auth_header = match self {
+            #[cfg(feature = "build_artifacts")]
+            Backend::Build(client, ..) => {
+                client
+                    .token_credential()
+                    .http_authorization_header(&client.scopes())
+                    .await?
+            }
+            #[cfg(feature = "pipelines_artifacts")]
+            Backend::Pipelines(client, ..) => {
+                client
+                    .token_credential()
+                    .http_authorization_header(&client.scopes())
+                    .await?
+            }
+        };
+        if let Some(auth_header) = auth_header {
+            request.insert_header(headers::AUTHORIZATION, auth_header);
+        }
+        request.insert_header(headers::ACCEPT, accept.to_string());
+        request.set_body(azure_core::EMPTY_BODY);
+        match self {
+            #[cfg(feature = "build_artifacts")]
+            Backend::Build(client, ..) => client.send(&mut request).await,
+            #[cfg(feature = "pipelines_artifacts")]
+            Backend::Pipelines(client, ..) => client.send(&mut request).await,
+        }
+    }
+}
+
+/// Where the artifact's content is stored.
+#[cfg_attr(not(feature = "build"), allow(dead_code))]
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Source {
+    /// A file container, with the path of the artifact's root folder within it.
+    Container { container_id: String, root: String },
+    /// A zip archive of the artifact.
+    Zip { url: String },
+}
+
+/// `DownloadArtifactBuilder` provides a mechanism for setting artifact download options.
+///
+/// Await the builder to download the artifact. It resolves to the files that were written.
+/// The download uses Tokio for file I/O, so it must be awaited within a Tokio runtime.
+#[derive(Clone)]
+pub struct DownloadArtifactBuilder {
+    backend: Backend,
+    organization: String,
+    project: String,
+    artifact_name: String,
+    directory: PathBuf,
+    filters: Vec<PathFilter>,
+}
+
+impl DownloadArtifactBuilder {
+    fn new(
+        backend: Backend,
+        organization: String,
+        project: String,
+        artifact_name: String,
+        directory: PathBuf,
+    ) -> Self {
+        Self {
+            backend,
+            organization,
+            project,
+            artifact_name,
+            directory,
+            filters: Vec::new(),
+        }
+    }
+
+    /// Only download files whose path within the artifact (using `/` separators, without the
+    /// artifact name) is accepted by `filter`.
+    /// May be called multiple times; a file is downloaded if all filters accept it.
+    #[must_use]
+    pub fn filter(mut self, filter: impl Fn(&str) -> bool + Send + Sync + 'static) -> Self {
+        self.filters.push(Arc::new(filter));
+        self
+    }
+
+    fn accepts(&self, path: &str) -> bool {
+        self.filters.iter().all(|filter| filter(path))
+    }
+
+    async fn source(&self) -> azure_core::Result<Source> {
+        match &self.backend {
+            #[cfg(feature = "build_artifacts")]
+            Backend::Build(client, build_id) => {
+                let artifact = client
+                    .artifacts_client()
+                    .get_artifact(
+                        &self.organization,
+                        &self.project,
+                        *build_id,
+                        &self.artifact_name,
+                    )
+                    .await?;
+                let resource = artifact.resource.ok_or_else(|| {
+                    Error::with_message(ErrorKind::DataConversion, || {
+                        format!("artifact {} has no resource", self.artifact_name)
+                    })
+                })?;
+                artifact_source(
+                    resource.type_.as_deref(),
+                    resource.data.as_deref(),
+                    resource.download_url.as_deref(),
+                )
+            }
+            #[cfg(feature = "pipelines_artifacts")]
+            Backend::Pipelines(client, pipeline_id, run_id) => {
+                let artifact = client
+                    .artifacts_client()
+                    .get(
+                        &self.organization,
+                        &self.project,
+                        *pipeline_id,
+                        *run_id,
+                        &self.artifact_name,
+                    )
+                    .expand("signedContent")
+                    .await?;
+                let url = artifact
+                    .signed_content
+                    .and_then(|signed_content| signed_content.url)
+                    .ok_or_else(|| {
+                        Error::with_message(ErrorKind::DataConversion, || {
+                            format!("artifact {} has no download URL", self.artifact_name)
+                        })
+                    })?;
+                Ok(Source::Zip { url })
+            }
+        }
+    }
+
+    async fn download(self) -> azure_core::Result<Vec<DownloadedFile>> {
+        tokio::fs::create_dir_all(&self.directory)
+            .await
+            .with_context(ErrorKind::Io, || {
+                format!("Failed to create {}", self.directory.display())
+            })?;
+        match self.source().await? {
+            Source::Container { container_id, root } => {
+                self.download_container(&container_id, &root).await
+            }
+            Source::Zip { url } => self.download_zip(&url).await,
+        }
+    }
+
+    async fn download_container(
+        &self,
+        container_id: &str,
+        root: &str,
+    ) -> azure_core::Result<Vec<DownloadedFile>> {
+        let mut url = Url::parse(&format!(
+            "{}/{}/_apis/resources/Containers/{}",
+            self.backend.endpoint(),
+            &self.organization,
+            container_id
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("itemPath", root)
+            .append_pair(azure_core::query_param::API_VERSION, "7.1-preview.4");
+        let items: ContainerItems = self
+            .backend
+            .get(url.clone(), "application/json")
+            .await?
+            .json()
+            .await?;
+
+        let mut files = Vec::new();
+        for item in items.value {
+            if item.item_type != "file" {
+                continue;
+            }
+            let path = match relative_path(&item.path, root) {
+                Some(path) => path,
+                None => continue,
+            };
+            if !self.accepts(&path) {
+                continue;
+            }
+            let local_path = local_path(&self.directory, &path)?;
+            let mut file_url = url.clone();
+            file_url
+                .query_pairs_mut()
+                .clear()
+                .append_pair("itemPath", &item.path)
+                .append_pair("$format", "OctetStream")
+                .append_pair(azure_core::query_param::API_VERSION, "7.1-preview.4");
+            let response = self
+                .backend
+                .get(file_url, "application/octet-stream")
+                .await?;
+            let size = write_response(response, &local_path).await?;
+            if let Some(expected) = item.file_length {
+                if expected != size {
+                    let _ = tokio::fs::remove_file(&local_path).await;
+                    return Err(Error::with_message(ErrorKind::DataConversion, || {
+                        format!("{path}: expected {expected} bytes, downloaded {size}")
+                    }));
+                }
+            }
+            files.push(DownloadedFile {
+                path,
+                local_path,
+                size,
+            });
+        }
+        Ok(files)
+    }
+
+    async fn download_zip(&self, url: &str) -> azure_core::Result<Vec<DownloadedFile>> {
+        let url = Url::parse(url)?;
+        let archive_path = self.directory.join(archive_file_name(&self.artifact_name));
+        let response = self.backend.get(url, "application/zip").await?;
+        write_response(response, &archive_path).await?;
+
+        // Unpacking uses blocking file I/O, so it runs off the async executor.
+        let (path, root, directory, filters) = (
+            archive_path.clone(),
+            self.artifact_name.clone(),
+            self.directory.clone(),
+            self.filters.clone(),
+        );
+        let result = tokio::task::spawn_blocking(move || {
+            let archive = std::fs::File::open(&path).with_context(ErrorKind::Io, || {
+                format!("Failed to open {}", path.display())
+            })?;
+            extract_zip(archive, &root, &directory, |path| {
+                filters.iter().all(|filter| filter(path))
+            })
+        })
+        .await
+        .unwrap_or_else(|e| {
+            Err(Error::full(
+                ErrorKind::Other,
+                e,
+                "Failed to unpack artifact archive",
+            ))
+        });
+        let _ = tokio::fs::remove_file(&archive_path).await;
+        result
+    }
+}
+
+impl std::future::IntoFuture for DownloadArtifactBuilder {
+    type Output = azure_core::Result<Vec<DownloadedFile>>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<Vec<DownloadedFile>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.download())
+    }
+}
+
+#[derive(Deserialize)]
+struct ContainerItems {
+    #[serde(default)]
+    value: Vec<ContainerItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ContainerItem {
+    item_type: String,
+    path: String,
+    file_length: Option<u64>,
+}
+
+/// Determines where an artifact's content is stored from its resource description.
+#[cfg_attr(not(feature = "build"), allow(dead_code))]
+fn artifact_source(
+    resource_type: Option<&str>,
+    data: Option<&str>,
+    download_url: Option<&str>,
+) -> azure_core::Result<Source> {
+    if resource_type == Some("Container") {
+        // The data of a container artifact is "#/{containerId}/{path}".
+        if let Some((container_id, root)) = data
+            .and_then(|data| data.strip_prefix("#/"))
+            .and_then(|data| data.split_once('/'))
+        {
+            return Ok(Source::Container {
+                container_id: container_id.to_string(),
+                root: root.to_string(),
+            });
+        }
+    }
+    match download_url {
+        Some(url) => Ok(Source::Zip {
+            url: url.to_string(),
+        }),
+        None => Err(Error::with_message(ErrorKind::DataConversion, || {
+            format!(
+                "artifact of type {} cannot be downloaded",
+                resource_type.unwrap_or("unknown")
+            )
+        })),
+    }
+}
+
+/// The path of an item relative to the artifact root, or `None` if it is outside the root.
+fn relative_path(path: &str, root: &str) -> Option<String> {
+    let root = root.trim_end_matches('/');
+    let path = path.strip_prefix(root)?;
+    // The root must match a whole path segment: `drop2/a.dll` is not below `drop`.
+    if !root.is_empty() && !path.is_empty() && !path.starts_with('/') {
+        return None;
+    }
+    let path = path.trim_start_matches('/');
+    if path.is_empty() {
+        None
+    } else {
+        Some(path.to_string())
+    }
+}
+
+/// The name of the temporary file an artifact's zip archive is downloaded to, with any
+/// characters of the artifact name that could form a path replaced.
+fn archive_file_name(artifact_name: &str) -> String {
+    let name: String = artifact_name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!(".{name}.zip.download")
+}
+
+/// The local path for a file, rejecting artifact paths that would escape `directory`.
+fn local_path(directory: &Path, path: &str) -> azure_core::Result<PathBuf> {
+    let relative = Path::new(path);
+    if relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        Ok(directory.join(relative))
+    } else {
+        Err(Error::with_message(ErrorKind::DataConversion, || {
+            format!("invalid artifact file path: {path}")
+        }))
+    }
+}
+
+/// Streams a response body to a file, creating parent directories as needed, and returns the
+/// number of bytes written.
+async fn write_response(response: Response, path: &Path) -> azure_core::Result<u64> {
+    use tokio::io::AsyncWriteExt;
+
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(ErrorKind::Io, || {
+                format!("Failed to create {}", parent.display())
+            })?;
+    }
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .with_context(ErrorKind::Io, || {
+            format!("Failed to create {}", path.display())
+        })?;
+    let (_, _, mut body) = response.deconstruct();
+    let mut written = 0;
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk)
+            .await
+            .with_context(ErrorKind::Io, || {
+                format!("Failed to write {}", path.display())
+            })?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await.with_context(ErrorKind::Io, || {
+        format!("Failed to write {}", path.display())
+    })?;
+    Ok(written)
+}
+
+/// Unpacks the files of a zip archive whose paths (below `root`) are accepted by `accepts`.
+///
+/// Reading each entry to the end verifies its CRC-32 checksum.
+fn extract_zip<R: Read + Seek>(
+    archive: R,
+    root: &str,
+    directory: &Path,
+    accepts: impl Fn(&str) -> bool,
+) -> azure_core::Result<Vec<DownloadedFile>> {
+    let mut archive = zip::ZipArchive::new(archive)
+        .map_err(|e| Error::full(ErrorKind::DataConversion, e, "Invalid artifact archive"))?;
+    let mut files = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| Error::full(ErrorKind::DataConversion, e, "Invalid artifact archive"))?;
+        if entry.is_dir() {
+            continue;
+        }
+        let name = entry.name().replace('\\', "/");
+        let path = relative_path(&name, root).unwrap_or(name);
+        if !accepts(&path) {
+            continue;
+        }
+        let local_path = local_path(directory, &path)?;
+        if let Some(parent) = local_path.parent() {
+            std::fs::create_dir_all(parent).with_context(ErrorKind::Io, || {
+                format!("Failed to create {}", parent.display())
+            })?;
+        }
+        let mut file = std::fs::File::create(&local_path).with_context(ErrorKind::Io, || {
+            format!("Failed to create {}", local_path.display())
+        })?;
+        let size = match std::io::copy(&mut entry, &mut file) {
+            Ok(size) => size,
+            Err(e) => {
+                drop(file);
+                let _ = std::fs::remove_file(&local_path);
+                return Err(Error::full(
+                    ErrorKind::Io,
+                    e,
+                    format!("Failed to extract {path}"),
+                ));
+            }
+        };
+        files.push(DownloadedFile {
+            path,
+            local_path,
+            size,
+        });
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    #[test]
+    fn test_artifact_source() {
+        assert_eq!(
+            artifact_source(Some("Container"), Some("#/1234/drop"), Some("https://x")).unwrap(),
+            Source::Container {
+                container_id: "1234".to_string(),
+                root: "drop".to_string()
+            }
+        );
+        assert_eq!(
+            artifact_source(Some("PipelineArtifact"), Some("ABCD"), Some("https://x")).unwrap(),
+            Source::Zip {
+                url: "https://x".to_string()
+            }
+        );
+        assert!(artifact_source(Some("FilePath"), Some("\\\\share\\drop"), None).is_err());
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(
+            relative_path("drop/bin/a.dll", "drop").unwrap(),
+            "bin/a.dll"
+        );
+        assert_eq!(relative_path("drop", "drop"), None);
+        assert_eq!(relative_path("other/a.dll", "drop"), None);
+        assert_eq!(relative_path("drop2/a.dll", "drop"), None);
+        assert_eq!(relative_path("drop/a.dll", "drop/").unwrap(), "a.dll");
+        let directory = Path::new("out");
+        assert_eq!(
+            local_path(directory, "bin/a.dll").unwrap(),
+            directory.join("bin").join("a.dll")
+        );
+        assert!(local_path(directory, "../a.dll").is_err());
+        assert!(local_path(directory, "/etc/passwd").is_err());
+        assert_eq!(archive_file_name("drop"), ".drop.zip.download");
+        assert_eq!(archive_file_name("../x/y z"), ".___x_y_z.zip.download");
+    }
+
+    #[test]
+    fn test_extract_zip() {
+        let mut archive = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::FileOptions::default();
+        archive.add_directory("drop/bin/", options).unwrap();
+        archive.start_file("drop/bin/a.dll", options).unwrap();
+        archive.write_all(b"dll").unwrap();
+        archive.start_file("drop/readme.md", options).unwrap();
+        archive.write_all(b"readme").unwrap();
+        let archive = archive.finish().unwrap();
+
+        let directory =
+            std::env::temp_dir().join(format!("build_artifacts_test_{}", std::process::id()));
+        let files = extract_zip(archive, "drop", &directory, |path| path.ends_with(".dll"));
+        let contents = std::fs::read(directory.join("bin").join("a.dll"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let files = files.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, "bin/a.dll");
+        assert_eq!(files[0].size, 3);
+        assert_eq!(contents.unwrap(), b"dll");
+    }
+}
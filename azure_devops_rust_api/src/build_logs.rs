@@ -0,0 +1,276 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Follow the logs of a running build.
+//!
+//! `build::Client::follow_build_logs()` discovers the logs of a build's timeline records,
+//! fetches new lines of each log as they become available and yields them in timeline
+//! order until the build completes.
+//!
+//! ```rust
+//!     let mut lines = build_client
+//!         .follow_build_logs(&organization, &project, build_id)
+//!         .record_type("Task")
+//!         .stream();
+//!     while let Some(line) = lines.next().await {
+//!         let line = line?;
+//!         println!("[{}] {}", line.record.name.as_deref().unwrap_or_default(), line.text);
+//!     }
+//! ```
+
+use crate::build::models::{build::Status as BuildStatus, TimelineRecord};
+use crate::build_run::{get_timeline, type_rank};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream::BoxStream;
+#[cfg(target_arch = "wasm32")]
+use futures::stream::LocalBoxStream as BoxStream;
+
+/// A line of a build log.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogLine {
+    /// The timeline record (task, job, ...) that produced the log.
+    pub record: Arc<TimelineRecord>,
+    /// The ID of the log.
+    pub log_id: i32,
+    /// The line number within the log, starting at 1.
+    pub line_number: i64,
+    /// The text of the line, without its line terminator.
+    pub text: String,
+}
+
+impl crate::build::Client {
+    /// Creates a builder that follows the logs of a build until it completes.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `build_id`: The ID of the build.
+    pub fn follow_build_logs(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        build_id: i32,
+    ) -> FollowLogsBuilder {
+        FollowLogsBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            build_id,
+            poll_interval: Duration::from_secs(5),
+            record_types: Vec::new(),
+        }
+    }
+}
+
+/// `FollowLogsBuilder` provides a mechanism for setting log follower options.
+///
+/// Call [`stream()`](FollowLogsBuilder::stream) to start following the logs.
+#[derive(Clone)]
+pub struct FollowLogsBuilder {
+    client: crate::build::Client,
+    organization: String,
+    project: String,
+    build_id: i32,
+    poll_interval: Duration,
+    record_types: Vec<String>,
+}
+
+impl FollowLogsBuilder {
+    /// Interval between checks for new log lines (default 5 seconds).
+    #[must_use]
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Only follow the logs of records of this type (e.g. `Task` or `Job`).
+    /// May be called multiple times; by default the logs of all records are followed.
+    #[must_use]
+    pub fn record_type(mut self, record_type: impl Into<String>) -> Self {
+        self.record_types.push(record_type.into());
+        self
+    }
+
+    /// A stream of log lines, ordered by timeline record and then line number,
+    /// which ends once the build has completed and all of its logs have been read.
+    pub fn stream(self) -> BoxStream<'static, azure_core::Result<LogLine>> {
+        let state = FollowState {
+            builder: self,
+            fetched: HashMap::new(),
+            pending: VecDeque::new(),
+            first_poll: true,
+            done: false,
+        };
+        Box::pin(futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(line) = state.pending.pop_front() {
+                    return Some((Ok(line), state));
+                }
+                if state.done {
+                    return None;
+                }
+                if let Err(e) = state.poll().await {
+                    state.done = true;
+                    return Some((Err(e), state));
+                }
+            }
+        }))
+    }
+}
+
+struct FollowState {
+    builder: FollowLogsBuilder,
+    /// Number of lines already read from each log, by log id.
+    fetched: HashMap<i32, i64>,
+    pending: VecDeque<LogLine>,
+    first_poll: bool,
+    done: bool,
+}
+
+impl FollowState {
+    async fn poll(&mut self) -> azure_core::Result<()> {
+        let b = &self.builder;
+        if !std::mem::replace(&mut self.first_poll, false) {
+            azure_core::sleep::sleep(b.poll_interval).await;
+        }
+        // Read the build status first, so that lines written before completion are
+        // guaranteed to be fetched by this (final) pass.
+        let build = b
+            .client
+            .builds_client()
+            .get(&b.organization, &b.project, b.build_id)
+            .await?;
+        let completed = build.status == Some(BuildStatus::Completed);
+        let records = match get_timeline(&b.client, &b.organization, &b.project, &build).await? {
+            Some(timeline) => timeline.records,
+            None => Vec::new(),
+        };
+        let line_counts: HashMap<i32, i64> = b
+            .client
+            .builds_client()
+            .get_build_logs(&b.organization, &b.project, b.build_id)
+            .await?
+            .value
+            .into_iter()
+            .filter_map(|log| {
+                log.line_count
+                    .map(|line_count| (log.build_log_reference.id, line_count))
+            })
+            .collect();
+
+        for (record, log_id) in logged_records(records, &b.record_types) {
+            let fetched = self.fetched.get(&log_id).copied().unwrap_or(0);
+            let available = line_counts.get(&log_id).copied().unwrap_or(0);
+            if available <= fetched {
+                continue;
+            }
+            let text = b
+                .client
+                .builds_client()
+                .get_build_log(&b.organization, &b.project, b.build_id, log_id)
+                .start_line(fetched + 1)
+                .end_line(available)
+                .send()
+                .await?
+                .into_raw_response()
+                .into_body()
+                .collect_string()
+                .await?;
+            let lines = split_lines(&text);
+            let mut line_number = fetched;
+            for text in lines.into_iter().take((available - fetched) as usize) {
+                line_number += 1;
+                self.pending.push_back(LogLine {
+                    record: record.clone(),
+                    log_id,
+                    line_number,
+                    text: text.to_string(),
+                });
+            }
+            self.fetched.insert(log_id, line_number);
+        }
+        self.done = completed;
+        Ok(())
+    }
+}
+
+/// The records that have logs (optionally only those of the given types), in the order
+/// they ran, parents before children.
+fn logged_records(
+    records: Vec<TimelineRecord>,
+    record_types: &[String],
+) -> Vec<(Arc<TimelineRecord>, i32)> {
+    let mut records: Vec<(Arc<TimelineRecord>, i32)> = records
+        .into_iter()
+        .filter(|record| {
+            record_types.is_empty()
+                || record
+                    .type_
+                    .as_ref()
+                    .map_or(false, |type_| record_types.contains(type_))
+        })
+        .filter_map(|record| {
+            let log_id = record.log.as_ref()?.id;
+            Some((Arc::new(record), log_id))
+        })
+        .collect();
+    records.sort_by_key(|(record, _)| {
+        (
+            record.start_time.is_none(),
+            record.start_time,
+            type_rank(record),
+            record.order,
+        )
+    });
+    records
+}
+
+/// Splits log text into lines, dropping line terminators and the empty string after a final newline.
+fn split_lines(text: &str) -> Vec<&str> {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    if text.is_empty() {
+        return Vec::new();
+    }
+    text.split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_split_lines() {
+        assert_eq!(split_lines(""), Vec::<&str>::new());
+        assert_eq!(split_lines("a\r\nb\n"), vec!["a", "b"]);
+        assert_eq!(split_lines("a\n\nb"), vec!["a", "", "b"]);
+    }
+
+    #[test]
+    fn test_logged_records() {
+        let records: Vec<TimelineRecord> = serde_json::from_value(json!([
+            { "id": "2", "type": "Task", "order": 2, "startTime": "2024-01-01T00:00:05Z",
+              "log": { "id": 7, "type": "Container", "url": "" } },
+            { "id": "1", "type": "Task", "order": 1, "startTime": "2024-01-01T00:00:01Z",
+              "log": { "id": 6, "type": "Container", "url": "" } },
+            { "id": "3", "type": "Task", "order": 3 },
+            { "id": "job", "type": "Job", "startTime": "2024-01-01T00:00:00Z",
+              "log": { "id": 5, "type": "Container", "url": "" } }
+        ]))
+        .unwrap();
+        let ids = |records: Vec<(Arc<TimelineRecord>, i32)>| {
+            records.into_iter().map(|(_, id)| id).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(logged_records(records.clone(), &[])), vec![5, 6, 7]);
+        assert_eq!(
+            ids(logged_records(records, &["Task".to_string()])),
+            vec![6, 7]
+        );
+    }
+}
@@ -0,0 +1,680 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Queue a build and follow it to completion.
+//!
+//! `build::Client::run_and_wait()` queues a build and returns a [`BuildRun`], whose
+//! [`events()`](BuildRun::events) stream reports the build being queued and started, each
+//! stage/job/task starting and completing (derived by diffing successive timelines),
+//! and finally the build's [`BuildOutcome`].
+//!
+//! ```rust
+//!     let run = build_client
+//!         .run_and_wait(&organization, &project, definition_id)
+//!         .source_branch("refs/heads/main")
+//!         .variable("configuration", "release")
+//!         .await?;
+//!     let canceller = run.canceller();
+//!     let mut events = run.events();
+//!     while let Some(event) = events.next().await {
+//!         match event? {
+//!             BuildEvent::RecordCompleted(record) => println!("{:?} {:?}", record.name, record.result),
+//!             BuildEvent::Completed(outcome) => println!("Build finished: {outcome:?}"),
+//!             _ => {}
+//!         }
+//!     }
+//! ```
+
+use crate::build::models::{
+    build::{Result as BuildResult, Status as BuildStatus},
+    timeline_record::State,
+    Build, Timeline, TimelineRecord,
+};
+use azure_core::error::{Error, ErrorKind};
+use azure_core::{headers, Method, Request, StatusCode, Url};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream::BoxStream;
+#[cfg(target_arch = "wasm32")]
+use futures::stream::LocalBoxStream as BoxStream;
+
+/// A change in the progress of a build.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildEvent {
+    /// The build as it was when the run was queued (or followed). Always the first event.
+    Queued(Build),
+    /// The build has started running.
+    Started(Build),
+    /// A timeline record (stage, phase, job or task) started running.
+    RecordStarted(TimelineRecord),
+    /// A timeline record completed (or was skipped).
+    RecordCompleted(TimelineRecord),
+    /// The build completed. This is always the last event.
+    Completed(BuildOutcome),
+}
+
+/// The final result of a build.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BuildOutcome {
+    Succeeded(Build),
+    PartiallySucceeded(Build),
+    Failed(Build),
+    Canceled(Build),
+}
+
+impl BuildOutcome {
+    fn from_build(build: Build) -> Self {
+        match build.result {
+            Some(BuildResult::Succeeded) => BuildOutcome::Succeeded(build),
+            Some(BuildResult::PartiallySucceeded) => BuildOutcome::PartiallySucceeded(build),
+            Some(BuildResult::Canceled) => BuildOutcome::Canceled(build),
+            _ => BuildOutcome::Failed(build),
+        }
+    }
+
+    /// The completed build.
+    pub fn build(&self) -> &Build {
+        match self {
+            BuildOutcome::Succeeded(build)
+            | BuildOutcome::PartiallySucceeded(build)
+            | BuildOutcome::Failed(build)
+            | BuildOutcome::Canceled(build) => build,
+        }
+    }
+
+    /// Returns `true` if the build succeeded (optionally including partial success).
+    pub fn is_success(&self, allow_partial: bool) -> bool {
+        match self {
+            BuildOutcome::Succeeded(_) => true,
+            BuildOutcome::PartiallySucceeded(_) => allow_partial,
+            _ => false,
+        }
+    }
+}
+
+/// Polling schedule used while waiting for a build.
+///
+/// The interval starts at `initial`, is multiplied by `multiplier` after each poll that
+/// observed no change (up to `max`), and is reset to `initial` whenever something changes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PollBackoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+}
+
+impl Default for PollBackoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_secs(2),
+            max: Duration::from_secs(30),
+            multiplier: 1.5,
+        }
+    }
+}
+
+impl PollBackoff {
+    fn next(&self, current: Duration, changed: bool) -> Duration {
+        if changed {
+            self.initial
+        } else {
+            current.mul_f64(self.multiplier.max(1.0)).min(self.max)
+        }
+    }
+}
+
+impl crate::build::Client {
+    /// Creates a builder that queues a build of a definition and follows it to completion.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `definition_id`: The ID of the build definition to queue.
+    pub fn run_and_wait(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        definition_id: i32,
+    ) -> RunAndWaitBuilder {
+        RunAndWaitBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            definition_id,
+            source_branch: None,
+            source_version: None,
+            queue_id: None,
+            variables: Map::new(),
+            template_parameters: Map::new(),
+            backoff: PollBackoff::default(),
+        }
+    }
+
+    /// Follows an existing build to completion.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `build_id`: The ID of the build.
+    pub async fn follow_build(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        build_id: i32,
+    ) -> azure_core::Result<BuildRun> {
+        let organization = organization.into();
+        let project = project.into();
+        let build = self
+            .builds_client()
+            .get(&organization, &project, build_id)
+            .await?;
+        Ok(BuildRun {
+            client: self.clone(),
+            organization,
+            project,
+            build,
+            backoff: PollBackoff::default(),
+        })
+    }
+}
+
+/// `RunAndWaitBuilder` provides a mechanism for setting the parameters of a queued build.
+///
+/// Invoke `.await` to queue the build, which returns a [`BuildRun`].
+#[derive(Clone)]
+pub struct RunAndWaitBuilder {
+    client: crate::build::Client,
+    organization: String,
+    project: String,
+    definition_id: i32,
+    source_branch: Option<String>,
+    source_version: Option<String>,
+    queue_id: Option<i32>,
+    variables: Map<String, Value>,
+    template_parameters: Map<String, Value>,
+    backoff: PollBackoff,
+}
+
+impl RunAndWaitBuilder {
+    /// The branch to build, e.g. `refs/heads/main` (default: the definition's default branch).
+    #[must_use]
+    pub fn source_branch(mut self, source_branch: impl Into<String>) -> Self {
+        self.source_branch = Some(source_branch.into());
+        self
+    }
+
+    /// The commit to build (default: the head of the source branch).
+    #[must_use]
+    pub fn source_version(mut self, source_version: impl Into<String>) -> Self {
+        self.source_version = Some(source_version.into());
+        self
+    }
+
+    /// The ID of the agent queue to run the build on (default: the definition's queue).
+    #[must_use]
+    pub fn queue_id(mut self, queue_id: i32) -> Self {
+        self.queue_id = Some(queue_id);
+        self
+    }
+
+    /// Sets a variable that is settable at queue time.
+    #[must_use]
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables
+            .insert(name.into(), Value::String(value.into()));
+        self
+    }
+
+    /// Sets a YAML pipeline runtime parameter.
+    #[must_use]
+    pub fn template_parameter(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.template_parameters
+            .insert(name.into(), Value::String(value.into()));
+        self
+    }
+
+    /// The polling schedule used while waiting for the build.
+    #[must_use]
+    pub fn backoff(mut self, backoff: PollBackoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    fn body(&self) -> Value {
+        let mut body = json!({ "definition": { "id": self.definition_id } });
+        if let Some(source_branch) = &self.source_branch {
+            body["sourceBranch"] = json!(source_branch);
+        }
+        if let Some(source_version) = &self.source_version {
+            body["sourceVersion"] = json!(source_version);
+        }
+        if let Some(queue_id) = self.queue_id {
+            body["queue"] = json!({ "id": queue_id });
+        }
+        if !self.variables.is_empty() {
+            // Queue-time variables are passed as a JSON-encoded string.
+            body["parameters"] = json!(Value::Object(self.variables.clone()).to_string());
+        }
+        if !self.template_parameters.is_empty() {
+            body["templateParameters"] = Value::Object(self.template_parameters.clone());
+        }
+        body
+    }
+
+    async fn queue(self) -> azure_core::Result<BuildRun> {
+        // The generated `queue` operation requires a fully populated `Build`, so the minimal
+        // request body is sent directly.
+        let url = build_url(&self.client, &self.organization, &self.project, None)?;
+        let build: Build = send_json(&self.client, Method::Post, url, &self.body())
+            .await?
+            .json()
+            .await?;
+        Ok(BuildRun {
+            client: self.client,
+            organization: self.organization,
+            project: self.project,
+            build,
+            backoff: self.backoff,
+        })
+    }
+}
+
+impl std::future::IntoFuture for RunAndWaitBuilder {
+    type Output = azure_core::Result<BuildRun>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<BuildRun>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.queue())
+    }
+}
+
+/// A queued build being followed to completion.
+#[derive(Clone)]
+pub struct BuildRun {
+    client: crate::build::Client,
+    organization: String,
+    project: String,
+    build: Build,
+    backoff: PollBackoff,
+}
+
+impl BuildRun {
+    /// The build, as returned when it was queued.
+    pub fn build(&self) -> &Build {
+        &self.build
+    }
+
+    /// Sets the polling schedule used while waiting for the build.
+    #[must_use]
+    pub fn with_backoff(mut self, backoff: PollBackoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Returns a handle that can cancel the build while its events are being consumed.
+    pub fn canceller(&self) -> BuildCanceller {
+        BuildCanceller {
+            client: self.client.clone(),
+            organization: self.organization.clone(),
+            project: self.project.clone(),
+            build_id: self.build.id,
+        }
+    }
+
+    /// A stream of progress events, ending with [`BuildEvent::Completed`].
+    pub fn events(self) -> BoxStream<'static, azure_core::Result<BuildEvent>> {
+        let state = PollState {
+            interval: self.backoff.initial,
+            run: self,
+            started: false,
+            records: HashMap::new(),
+            pending: VecDeque::new(),
+            first_poll: true,
+            done: false,
+        };
+        Box::pin(futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(event) = state.pending.pop_front() {
+                    return Some((Ok(event), state));
+                }
+                if state.done {
+                    return None;
+                }
+                if let Err(e) = state.poll().await {
+                    state.done = true;
+                    return Some((Err(e), state));
+                }
+            }
+        }))
+    }
+
+    /// Waits for the build to complete, ignoring intermediate events.
+    pub async fn wait(self) -> azure_core::Result<BuildOutcome> {
+        use futures::StreamExt;
+        let mut events = self.events();
+        while let Some(event) = events.next().await {
+            if let BuildEvent::Completed(outcome) = event? {
+                return Ok(outcome);
+            }
+        }
+        Err(Error::message(
+            ErrorKind::Other,
+            "build events ended before the build completed",
+        ))
+    }
+}
+
+struct PollState {
+    run: BuildRun,
+    interval: Duration,
+    started: bool,
+    /// Last observed state of each timeline record, by record id.
+    records: HashMap<String, Option<State>>,
+    pending: VecDeque<BuildEvent>,
+    first_poll: bool,
+    done: bool,
+}
+
+impl PollState {
+    async fn poll(&mut self) -> azure_core::Result<()> {
+        let run = &self.run;
+        if !self.first_poll {
+            azure_core::sleep::sleep(self.interval).await;
+        }
+        let build = if self.first_poll {
+            run.build.clone()
+        } else {
+            run.client
+                .builds_client()
+                .get(&run.organization, &run.project, run.build.id)
+                .await?
+        };
+        let first_poll = std::mem::replace(&mut self.first_poll, false);
+        let status = build.status.clone();
+
+        let mut events = Vec::new();
+        if first_poll {
+            events.push(BuildEvent::Queued(build.clone()));
+        }
+        if !self.started && !matches!(status, Some(BuildStatus::NotStarted) | None) {
+            self.started = true;
+            events.push(BuildEvent::Started(build.clone()));
+        }
+        if self.started {
+            if let Some(timeline) =
+                get_timeline(&run.client, &run.organization, &run.project, &build).await?
+            {
+                events.extend(timeline_events(&mut self.records, &timeline.records));
+            }
+        }
+        if status == Some(BuildStatus::Completed) {
+            events.push(BuildEvent::Completed(BuildOutcome::from_build(build)));
+            self.done = true;
+        }
+
+        self.interval = self.run.backoff.next(self.interval, !events.is_empty());
+        self.pending.extend(events);
+        Ok(())
+    }
+}
+
+/// Cancels a running build, created by [`BuildRun::canceller`].
+#[derive(Clone)]
+pub struct BuildCanceller {
+    client: crate::build::Client,
+    organization: String,
+    project: String,
+    build_id: i32,
+}
+
+impl BuildCanceller {
+    /// Requests cancellation of the build.
+    ///
+    /// The build's event stream completes with [`BuildOutcome::Canceled`] once the
+    /// service has stopped the build.
+    pub async fn cancel(&self) -> azure_core::Result<()> {
+        let url = build_url(
+            &self.client,
+            &self.organization,
+            &self.project,
+            Some(self.build_id),
+        )?;
+        send_json(
+            &self.client,
+            Method::Patch,
+            url,
+            &json!({ "status": "cancelling" }),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// Returns the build's timeline, or `None` if it doesn't have one yet.
+pub(crate) async fn get_timeline(
+    client: &crate::build::Client,
+    organization: &str,
+    project: &str,
+    build: &Build,
+) -> azure_core::Result<Option<Timeline>> {
+    let timeline_id = build
+        .orchestration_plan
+        .as_ref()
+        .map(|plan| plan.plan_id.clone())
+        .unwrap_or_default();
+    match client
+        .timeline_client()
+        .get(organization, project, build.id, timeline_id)
+        .await
+    {
+        Ok(timeline) => Ok(Some(timeline)),
+        Err(e)
+            if matches!(
+                e.kind(),
+                ErrorKind::HttpResponse {
+                    status: StatusCode::NotFound,
+                    ..
+                }
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+fn build_url(
+    client: &crate::build::Client,
+    organization: &str,
+    project: &str,
+    build_id: Option<i32>,
+) -> azure_core::Result<Url> {
+    let mut url = Url::parse(&format!(
+        "{}/{}/{}/_apis/build/builds",
+        client.endpoint(),
+        organization,
+        project
+    ))?;
+    if let Some(build_id) = build_id {
+        url.path_segments_mut()
+            .map_err(|_| Error::message(ErrorKind::Other, "invalid build URL"))?
+            .push(&build_id.to_string());
+    }
+    url.query_pairs_mut()
+        .append_pair(azure_core::query_param::API_VERSION, "7.1-preview");
+    Ok(url)
+}
+
+async fn send_json(
+    client: &crate::build::Client,
+    method: Method,
+    url: Url,
+    body: &Value,
+) -> azure_core::Result<azure_core::Response> {
+    let mut request = Request::new(url, method);
+    if let Some(auth_header) = client
+        .token_credential()
+        .http_authorization_header(&client.scopes())
+        .await?
+    {
+        request.insert_header(headers::AUTHORIZATION, auth_header);
+    }
+    request.insert_header(headers::CONTENT_TYPE, "application/json");
+    request.set_body(serde_json::to_vec(body)?);
+    client.send(&mut request).await
+}
+
+/// Compares `records` with the previously observed record states, returning the
+/// start/completion events and updating `seen`.
+fn timeline_events(
+    seen: &mut HashMap<String, Option<State>>,
+    records: &[TimelineRecord],
+) -> Vec<BuildEvent> {
+    let mut records: Vec<&TimelineRecord> = records.iter().collect();
+    // Report records in the order they ran, parents before children.
+    records.sort_by_key(|record| {
+        (
+            record.start_time.is_none(),
+            record.start_time,
+            type_rank(record),
+        )
+    });
+    let mut events = Vec::new();
+    for record in records {
+        let id = match &record.id {
+            Some(id) => id.clone(),
+            None => continue,
+        };
+        let previous = seen.insert(id, record.state.clone()).flatten();
+        if previous == record.state {
+            continue;
+        }
+        match record.state {
+            Some(State::InProgress) => events.push(BuildEvent::RecordStarted(record.clone())),
+            Some(State::Completed) => {
+                if previous != Some(State::InProgress) && record.start_time.is_some() {
+                    // Started and completed between polls.
+                    events.push(BuildEvent::RecordStarted(record.clone()));
+                }
+                events.push(BuildEvent::RecordCompleted(record.clone()))
+            }
+            _ => {}
+        }
+    }
+    events
+}
+
+/// Sort rank of a timeline record type: stages before phases before jobs before tasks.
+pub(crate) fn type_rank(record: &TimelineRecord) -> u8 {
+    match record.type_.as_deref() {
+        Some("Stage") => 0,
+        Some("Phase") => 1,
+        Some("Job") => 2,
+        Some("Task") => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, type_: &str, state: &str, start: Option<&str>) -> TimelineRecord {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": id,
+            "type": type_,
+            "state": state,
+            "startTime": start,
+        }))
+        .unwrap()
+    }
+
+    fn names(events: &[BuildEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                BuildEvent::RecordStarted(r) => format!("start {}", r.name.as_deref().unwrap()),
+                BuildEvent::RecordCompleted(r) => format!("end {}", r.name.as_deref().unwrap()),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_timeline_events() {
+        let mut seen = HashMap::new();
+        let events = timeline_events(
+            &mut seen,
+            &[
+                record("build", "Task", "pending", None),
+                record("job", "Job", "inProgress", Some("2024-01-01T00:00:00Z")),
+                record(
+                    "checkout",
+                    "Task",
+                    "completed",
+                    Some("2024-01-01T00:00:01Z"),
+                ),
+            ],
+        );
+        assert_eq!(
+            names(&events),
+            ["start job", "start checkout", "end checkout"]
+        );
+
+        let events = timeline_events(
+            &mut seen,
+            &[
+                record("build", "Task", "completed", Some("2024-01-01T00:00:02Z")),
+                record("job", "Job", "inProgress", Some("2024-01-01T00:00:00Z")),
+                record(
+                    "checkout",
+                    "Task",
+                    "completed",
+                    Some("2024-01-01T00:00:01Z"),
+                ),
+            ],
+        );
+        assert_eq!(names(&events), ["start build", "end build"]);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let backoff = PollBackoff {
+            initial: Duration::from_secs(2),
+            max: Duration::from_secs(5),
+            multiplier: 2.0,
+        };
+        let interval = backoff.next(backoff.initial, false);
+        assert_eq!(interval, Duration::from_secs(4));
+        assert_eq!(backoff.next(interval, false), Duration::from_secs(5));
+        assert_eq!(backoff.next(interval, true), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn test_queue_body() {
+        let client = crate::build::ClientBuilder::new(crate::Credential::from_pat("")).build();
+        let body = client
+            .run_and_wait("org", "project", 12)
+            .source_branch("refs/heads/main")
+            .variable("configuration", "release")
+            .template_parameter("environment", "test")
+            .body();
+        assert_eq!(
+            body,
+            json!({
+                "definition": { "id": 12 },
+                "sourceBranch": "refs/heads/main",
+                "parameters": "{\"configuration\":\"release\"}",
+                "templateParameters": { "environment": "test" }
+            })
+        );
+    }
+}
@@ -0,0 +1,486 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Build timeline tree model.
+//!
+//! A build timeline is returned as a flat list of records linked by `parent_id`.
+//! [`TimelineTree`] rebuilds the Stage > Phase > Job > Task hierarchy and provides
+//! navigation, durations, the critical path and the failed steps of a build.
+//!
+//! ```rust
+//!     let tree = build_client.get_timeline_tree(&organization, &project, build_id).await?;
+//!     for stage in tree.roots() {
+//!         println!("{} ({:?})", stage.name(), stage.duration());
+//!     }
+//!     if let Some(failure) = tree.first_failure() {
+//!         println!("{} failed: {:?}", failure.path().join(" > "), failure.error_messages());
+//!     }
+//! ```
+
+use crate::build::models::{
+    issue, timeline_record::Result as RecordResult, BuildLogReference, Issue, Timeline,
+    TimelineAttempt, TimelineRecord,
+};
+use std::collections::HashMap;
+use time::Duration;
+
+/// The type of a timeline record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RecordType {
+    Stage,
+    Phase,
+    Job,
+    Task,
+    Checkpoint,
+    Other(String),
+}
+
+impl RecordType {
+    fn from_record(record: &TimelineRecord) -> Self {
+        match record.type_.as_deref() {
+            Some("Stage") => RecordType::Stage,
+            Some("Phase") => RecordType::Phase,
+            Some("Job") => RecordType::Job,
+            Some("Task") => RecordType::Task,
+            Some("Checkpoint") => RecordType::Checkpoint,
+            other => RecordType::Other(other.unwrap_or_default().to_string()),
+        }
+    }
+}
+
+/// A build timeline arranged as a tree of records.
+///
+/// When a record has been retried, only its latest attempt is part of the tree; the
+/// attempts it replaced are available from [`TimelineNode::superseded_attempts`] and,
+/// for attempts recorded in other timelines, [`TimelineNode::previous_attempts`].
+#[derive(Clone, Debug)]
+pub struct TimelineTree {
+    records: Vec<TimelineRecord>,
+    parents: Vec<Option<usize>>,
+    children: Vec<Vec<usize>>,
+    superseded: Vec<Vec<usize>>,
+    roots: Vec<usize>,
+    by_id: HashMap<String, usize>,
+}
+
+impl TimelineTree {
+    /// Builds the tree from a timeline's records.
+    pub fn new(records: Vec<TimelineRecord>) -> Self {
+        let n = records.len();
+        let by_id: HashMap<String, usize> = records
+            .iter()
+            .enumerate()
+            .filter_map(|(i, record)| Some((record.id.clone()?, i)))
+            .collect();
+
+        // Group retries of the same record (same parent and identifier) and keep the latest attempt.
+        let mut latest: HashMap<(Option<&str>, &str), usize> = HashMap::new();
+        let mut superseded = vec![Vec::new(); n];
+        let mut replaced = vec![false; n];
+        for (i, record) in records.iter().enumerate() {
+            let identifier = match record.identifier.as_deref() {
+                Some(identifier) => identifier,
+                None => continue,
+            };
+            let key = (record.parent_id.as_deref(), identifier);
+            match latest.get(&key).copied() {
+                // Not a retry; keep both.
+                Some(j) if records[j].attempt == record.attempt => {}
+                Some(j) if records[j].attempt > record.attempt => {
+                    superseded[j].push(i);
+                    replaced[i] = true;
+                }
+                Some(j) => {
+                    let mut older = std::mem::take(&mut superseded[j]);
+                    older.push(j);
+                    superseded[i].extend(older);
+                    replaced[j] = true;
+                    latest.insert(key, i);
+                }
+                None => {
+                    latest.insert(key, i);
+                }
+            }
+        }
+
+        let mut parents = vec![None; n];
+        let mut children = vec![Vec::new(); n];
+        let mut roots = Vec::new();
+        for (i, record) in records.iter().enumerate() {
+            if replaced[i] {
+                continue;
+            }
+            let parent = record
+                .parent_id
+                .as_ref()
+                .and_then(|parent_id| by_id.get(parent_id))
+                .copied()
+                .filter(|&parent| parent != i);
+            parents[i] = parent;
+            match parent {
+                Some(parent) => children[parent].push(i),
+                None => roots.push(i),
+            }
+        }
+        let sort_key = |&i: &usize| (records[i].order, records[i].start_time);
+        roots.sort_by_key(sort_key);
+        for siblings in &mut children {
+            siblings.sort_by_key(sort_key);
+        }
+        for attempts in &mut superseded {
+            attempts.sort_by_key(|&i| records[i].attempt);
+        }
+
+        Self {
+            records,
+            parents,
+            children,
+            superseded,
+            roots,
+            by_id,
+        }
+    }
+
+    /// Builds the tree from a timeline.
+    pub fn from_timeline(timeline: Timeline) -> Self {
+        Self::new(timeline.records)
+    }
+
+    fn node(&self, index: usize) -> TimelineNode<'_> {
+        TimelineNode { tree: self, index }
+    }
+
+    /// The top-level records (usually stages), in order.
+    pub fn roots(&self) -> impl Iterator<Item = TimelineNode<'_>> + '_ {
+        self.roots.iter().map(|&i| self.node(i))
+    }
+
+    /// Looks up a record by id.
+    pub fn get(&self, id: &str) -> Option<TimelineNode<'_>> {
+        self.by_id.get(id).map(|&i| self.node(i))
+    }
+
+    /// All records in the tree in depth-first order.
+    pub fn iter(&self) -> impl Iterator<Item = TimelineNode<'_>> + '_ {
+        let mut stack: Vec<usize> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            let i = stack.pop()?;
+            stack.extend(self.children[i].iter().rev());
+            Some(self.node(i))
+        })
+    }
+
+    /// All records of the given type in depth-first order.
+    pub fn of_type(&self, record_type: RecordType) -> impl Iterator<Item = TimelineNode<'_>> + '_ {
+        self.iter()
+            .filter(move |node| node.record_type() == record_type)
+    }
+
+    /// The time from the first record starting to the last record finishing.
+    pub fn duration(&self) -> Option<Duration> {
+        let start = self
+            .iter()
+            .filter_map(|node| node.record().start_time)
+            .min()?;
+        let finish = self
+            .iter()
+            .filter_map(|node| node.record().finish_time)
+            .max()?;
+        Some(finish - start)
+    }
+
+    /// The chain of records that determined when the build finished: starting from the
+    /// root that finished last, repeatedly the child that finished last.
+    pub fn critical_path(&self) -> Vec<TimelineNode<'_>> {
+        let last_finished = |nodes: &[usize]| {
+            nodes
+                .iter()
+                .copied()
+                .filter(|&i| self.records[i].finish_time.is_some())
+                .max_by_key(|&i| self.records[i].finish_time)
+        };
+        let mut path = Vec::new();
+        let mut next = last_finished(&self.roots);
+        while let Some(i) = next {
+            path.push(self.node(i));
+            next = last_finished(&self.children[i]);
+        }
+        path
+    }
+
+    /// The records that failed without a failed child, i.e. the steps that caused the
+    /// failure, in the order they finished.
+    pub fn failed_steps(&self) -> Vec<FailedStep<'_>> {
+        let mut failed: Vec<FailedStep<'_>> = self
+            .iter()
+            .filter(|node| node.is_failed() && !node.children().any(|child| child.is_failed()))
+            .map(|node| FailedStep { node })
+            .collect();
+        failed.sort_by_key(|step| step.node.record().finish_time);
+        failed
+    }
+
+    /// The first step to fail.
+    pub fn first_failure(&self) -> Option<FailedStep<'_>> {
+        self.failed_steps().into_iter().next()
+    }
+}
+
+/// A record in a [`TimelineTree`].
+#[derive(Clone, Copy)]
+pub struct TimelineNode<'a> {
+    tree: &'a TimelineTree,
+    index: usize,
+}
+
+impl<'a> std::fmt::Debug for TimelineNode<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimelineNode")
+            .field("type", &self.record_type())
+            .field("name", &self.name())
+            .field("state", &self.record().state)
+            .field("result", &self.record().result)
+            .finish()
+    }
+}
+
+impl<'a> TimelineNode<'a> {
+    /// The underlying timeline record.
+    pub fn record(&self) -> &'a TimelineRecord {
+        &self.tree.records[self.index]
+    }
+
+    /// The record's name, or an empty string if it has none.
+    pub fn name(&self) -> &'a str {
+        self.record().name.as_deref().unwrap_or_default()
+    }
+
+    /// The record's type.
+    pub fn record_type(&self) -> RecordType {
+        RecordType::from_record(self.record())
+    }
+
+    /// The parent record.
+    pub fn parent(&self) -> Option<TimelineNode<'a>> {
+        self.tree.parents[self.index].map(|i| self.tree.node(i))
+    }
+
+    /// The child records, in order.
+    pub fn children(&self) -> impl Iterator<Item = TimelineNode<'a>> + 'a {
+        let tree = self.tree;
+        tree.children[self.index].iter().map(move |&i| tree.node(i))
+    }
+
+    /// The ancestors of the record, from the root down to (excluding) the record itself.
+    pub fn ancestors(&self) -> Vec<TimelineNode<'a>> {
+        let mut ancestors = Vec::new();
+        let mut next = self.parent();
+        while let Some(node) = next {
+            next = node.parent();
+            ancestors.push(node);
+        }
+        ancestors.reverse();
+        ancestors
+    }
+
+    /// The names of the record's ancestors and the record itself, e.g. `["Build", "Linux", "Linux", "Run tests"]`.
+    pub fn path(&self) -> Vec<&'a str> {
+        let mut path: Vec<&str> = self.ancestors().iter().map(|node| node.name()).collect();
+        path.push(self.name());
+        path
+    }
+
+    /// How long the record ran, if it has started and finished.
+    pub fn duration(&self) -> Option<Duration> {
+        let record = self.record();
+        Some(record.finish_time? - record.start_time?)
+    }
+
+    /// The record's attempt number (starting at 1).
+    pub fn attempt(&self) -> i32 {
+        self.record().attempt.unwrap_or(1)
+    }
+
+    /// Earlier attempts of this record present in the same timeline, oldest first.
+    pub fn superseded_attempts(&self) -> impl Iterator<Item = &'a TimelineRecord> + 'a {
+        let tree = self.tree;
+        tree.superseded[self.index]
+            .iter()
+            .map(move |&i| &tree.records[i])
+    }
+
+    /// References to earlier attempts of this record stored in other timelines.
+    ///
+    /// See [`crate::build::Client::get_previous_attempts`] to fetch them.
+    pub fn previous_attempts(&self) -> &'a [TimelineAttempt] {
+        &self.record().previous_attempts
+    }
+
+    /// Returns `true` if the record failed.
+    pub fn is_failed(&self) -> bool {
+        self.record().result == Some(RecordResult::Failed)
+    }
+}
+
+/// A step that caused a build to fail, from [`TimelineTree::failed_steps`].
+#[derive(Clone, Copy, Debug)]
+pub struct FailedStep<'a> {
+    pub node: TimelineNode<'a>,
+}
+
+impl<'a> FailedStep<'a> {
+    /// The names of the step's ancestors and the step itself.
+    pub fn path(&self) -> Vec<&'a str> {
+        self.node.path()
+    }
+
+    /// The step's error issues.
+    pub fn errors(&self) -> impl Iterator<Item = &'a Issue> + 'a {
+        self.node
+            .record()
+            .issues
+            .iter()
+            .filter(|issue| issue.type_ == Some(issue::Type::Error))
+    }
+
+    /// The messages of the step's error issues.
+    pub fn error_messages(&self) -> Vec<&'a str> {
+        self.errors()
+            .filter_map(|issue| issue.message.as_deref())
+            .collect()
+    }
+
+    /// The step's log, which can be fetched with `builds_client().get_build_log()`.
+    pub fn log(&self) -> Option<&'a BuildLogReference> {
+        self.node.record().log.as_ref()
+    }
+}
+
+impl crate::build::Client {
+    /// Fetches a build's timeline and arranges it as a [`TimelineTree`].
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `build_id`: The ID of the build.
+    pub async fn get_timeline_tree(
+        &self,
+        organization: &str,
+        project: &str,
+        build_id: i32,
+    ) -> azure_core::Result<TimelineTree> {
+        let build = self
+            .builds_client()
+            .get(organization, project, build_id)
+            .await?;
+        let records = crate::build_run::get_timeline(self, organization, project, &build)
+            .await?
+            .map(|timeline| timeline.records)
+            .unwrap_or_default();
+        Ok(TimelineTree::new(records))
+    }
+
+    /// Fetches the earlier attempts of a timeline record that are stored in other timelines,
+    /// oldest first.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `build_id`: The ID of the build.
+    /// * `record`: The (latest attempt of the) record.
+    pub async fn get_previous_attempts(
+        &self,
+        organization: &str,
+        project: &str,
+        build_id: i32,
+        record: &TimelineRecord,
+    ) -> azure_core::Result<Vec<TimelineRecord>> {
+        let mut attempts = Vec::new();
+        for attempt in &record.previous_attempts {
+            let (timeline_id, record_id) = match (&attempt.timeline_id, &attempt.record_id) {
+                (Some(timeline_id), Some(record_id)) => (timeline_id, record_id),
+                _ => continue,
+            };
+            let timeline = self
+                .timeline_client()
+                .get(organization, project, build_id, timeline_id)
+                .await?;
+            attempts.extend(
+                timeline
+                    .records
+                    .into_iter()
+                    .find(|r| r.id.as_ref() == Some(record_id)),
+            );
+        }
+        attempts.sort_by_key(|r| r.attempt);
+        Ok(attempts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tree() -> TimelineTree {
+        let records: Vec<TimelineRecord> = serde_json::from_value(json!([
+            { "id": "t2", "parentId": "j1", "type": "Task", "name": "Test", "order": 2, "identifier": "test",
+              "attempt": 1, "result": "failed", "startTime": "2024-01-01T00:01:00Z", "finishTime": "2024-01-01T00:03:00Z",
+              "issues": [{ "type": "warning", "message": "slow" }, { "type": "error", "message": "3 tests failed" }],
+              "log": { "id": 9, "type": "Container", "url": "" } },
+            { "id": "s1", "type": "Stage", "name": "Build", "order": 1, "identifier": "build",
+              "result": "failed", "startTime": "2024-01-01T00:00:00Z", "finishTime": "2024-01-01T00:05:00Z" },
+            { "id": "p1", "parentId": "s1", "type": "Phase", "name": "Linux", "order": 1, "identifier": "build.linux",
+              "result": "failed", "startTime": "2024-01-01T00:00:00Z", "finishTime": "2024-01-01T00:05:00Z" },
+            { "id": "j0", "parentId": "p1", "type": "Job", "name": "Linux", "order": 1, "identifier": "build.linux.job",
+              "attempt": 1, "result": "failed", "startTime": "2024-01-01T00:00:00Z", "finishTime": "2024-01-01T00:01:00Z" },
+            { "id": "j1", "parentId": "p1", "type": "Job", "name": "Linux", "order": 1, "identifier": "build.linux.job",
+              "attempt": 2, "result": "failed", "startTime": "2024-01-01T00:00:30Z", "finishTime": "2024-01-01T00:05:00Z" },
+            { "id": "t1", "parentId": "j1", "type": "Task", "name": "Compile", "order": 1, "identifier": "compile",
+              "result": "succeeded", "startTime": "2024-01-01T00:00:30Z", "finishTime": "2024-01-01T00:01:00Z" },
+            { "id": "t3", "parentId": "j1", "type": "Task", "name": "Cleanup", "order": 3, "identifier": "cleanup",
+              "result": "succeeded", "startTime": "2024-01-01T00:03:00Z", "finishTime": "2024-01-01T00:05:00Z" }
+        ]))
+        .unwrap();
+        TimelineTree::new(records)
+    }
+
+    #[test]
+    fn test_tree_structure() {
+        let tree = tree();
+        let names: Vec<&str> = tree.iter().map(|node| node.name()).collect();
+        assert_eq!(
+            names,
+            ["Build", "Linux", "Linux", "Compile", "Test", "Cleanup"]
+        );
+        let job = tree.get("j1").unwrap();
+        assert_eq!(job.attempt(), 2);
+        assert_eq!(
+            job.superseded_attempts()
+                .map(|r| r.id.as_deref().unwrap())
+                .collect::<Vec<_>>(),
+            ["j0"]
+        );
+        assert_eq!(tree.of_type(RecordType::Task).count(), 3);
+        assert_eq!(tree.duration(), Some(Duration::minutes(5)));
+        assert_eq!(job.duration(), Some(Duration::seconds(270)));
+    }
+
+    #[test]
+    fn test_critical_path_and_failures() {
+        let tree = tree();
+        let path: Vec<&str> = tree
+            .critical_path()
+            .iter()
+            .map(|node| node.name())
+            .collect();
+        assert_eq!(path, ["Build", "Linux", "Linux", "Cleanup"]);
+
+        let failed = tree.failed_steps();
+        assert_eq!(failed.len(), 1);
+        let failure = tree.first_failure().unwrap();
+        assert_eq!(failure.path(), ["Build", "Linux", "Linux", "Test"]);
+        assert_eq!(failure.error_messages(), ["3 tests failed"]);
+        assert_eq!(failure.log().map(|log| log.id), Some(9));
+    }
+}
@@ -1733,7 +1733,7 @@ pub mod elastic_pool_settings {
 pub struct EndpointAuthorization {
     #[doc = "Gets or sets the parameters for the selected authorization scheme."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub parameters: Option<serde_json::Value>,
+    pub parameters: Option<crate::secret::Secret<serde_json::Value>>,
     #[doc = "Gets or sets the scheme used for service endpoint authentication."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scheme: Option<String>,
@@ -7534,7 +7534,7 @@ pub struct VariableGroup {
         default,
         skip_serializing_if = "Option::is_none"
     )]
-    pub provider_data: Option<VariableGroupProviderData>,
+    pub provider_data: Option<serde_json::Value>,
     #[doc = "Gets or sets type of the variable group."]
     #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
     pub type_: Option<String>,
@@ -7585,7 +7585,7 @@ pub struct VariableGroupParameters {
         default,
         skip_serializing_if = "Option::is_none"
     )]
-    pub provider_data: Option<VariableGroupProviderData>,
+    pub provider_data: Option<serde_json::Value>,
     #[doc = "Sets type of the variable group."]
     #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
     pub type_: Option<String>,
@@ -7649,7 +7649,7 @@ pub struct VariableValue {
     pub is_secret: Option<bool>,
     #[doc = "The value of the variable."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
+    pub value: Option<crate::secret::Secret<String>>,
 }
 impl VariableValue {
     pub fn new() -> Self {
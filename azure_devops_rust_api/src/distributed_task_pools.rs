@@ -0,0 +1,651 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Monitor agent pools.
+//!
+//! `distributed_task::Client::monitor_pool()` takes periodic snapshots of an agent pool (its
+//! agents, job requests and, for elastic pools, the scale set status and recent logs) and
+//! reports what changed between them, e.g. to drive autoscaling decisions.
+//!
+//! ```rust
+//!     let mut updates = distributed_task_client
+//!         .monitor_pool(&organization, pool_id)
+//!         .poll_interval(Duration::from_secs(60))
+//!         .stream();
+//!     while let Some(update) = updates.next().await {
+//!         let update = update?;
+//!         println!(
+//!             "{} idle agents, {} queued jobs",
+//!             update.snapshot.idle_agents().count(),
+//!             update.snapshot.queue_length()
+//!         );
+//!         for event in &update.events {
+//!             println!("{event:?}");
+//!         }
+//!     }
+//! ```
+
+use crate::distributed_task::models::{
+    elastic_pool, task_agent_reference, ElasticPool, ElasticPoolLog, TaskAgent,
+    TaskAgentJobRequest, TaskAgentReference,
+};
+use azure_core::error::{ErrorKind, ResultExt};
+use azure_core::{headers, Method, Request, StatusCode, Url};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream::BoxStream;
+#[cfg(target_arch = "wasm32")]
+use futures::stream::LocalBoxStream as BoxStream;
+
+/// Number of finished job requests fetched with each snapshot, so that jobs which finish
+/// between two snapshots are still seen with their result.
+const COMPLETED_REQUEST_COUNT: &str = "50";
+
+/// The state of an agent pool at a point in time.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolSnapshot {
+    /// The ID of the agent pool.
+    pub pool_id: i32,
+    /// When the snapshot was taken, by the server's clock (the `Date` of the job requests
+    /// response), so that it can be compared with the job request times.
+    pub taken_at: OffsetDateTime,
+    /// The agents of the pool, with their assigned job requests.
+    pub agents: Vec<TaskAgent>,
+    /// The job requests of the pool: queued, running and recently finished.
+    pub job_requests: Vec<TaskAgentJobRequest>,
+    /// The scale set settings and status, if the pool is an elastic pool.
+    pub elastic_pool: Option<ElasticPool>,
+    /// The most recent elastic pool logs, newest first.
+    pub elastic_pool_logs: Vec<ElasticPoolLog>,
+}
+
+impl PoolSnapshot {
+    /// The agents that are online.
+    pub fn online_agents(&self) -> impl Iterator<Item = &TaskAgent> {
+        self.agents.iter().filter(|agent| is_online(agent))
+    }
+
+    /// The agents that are offline.
+    pub fn offline_agents(&self) -> impl Iterator<Item = &TaskAgent> {
+        self.agents.iter().filter(|agent| !is_online(agent))
+    }
+
+    /// The enabled, online agents that are not running a job.
+    pub fn idle_agents(&self) -> impl Iterator<Item = &TaskAgent> {
+        self.online_agents().filter(|agent| {
+            agent.task_agent_reference.enabled != Some(false) && agent.assigned_request.is_none()
+        })
+    }
+
+    /// The agents that are running a job.
+    pub fn busy_agents(&self) -> impl Iterator<Item = &TaskAgent> {
+        self.agents
+            .iter()
+            .filter(|agent| agent.assigned_request.is_some())
+    }
+
+    /// The job requests that are waiting for an agent, longest waiting first.
+    pub fn queued_jobs(&self) -> Vec<&TaskAgentJobRequest> {
+        let mut queued: Vec<&TaskAgentJobRequest> = self
+            .job_requests
+            .iter()
+            .filter(|request| job_state(request) == JobState::Queued)
+            .collect();
+        queued.sort_by_key(|request| (request.queue_time.is_none(), request.queue_time));
+        queued
+    }
+
+    /// The job requests that are running on an agent.
+    pub fn running_jobs(&self) -> impl Iterator<Item = &TaskAgentJobRequest> {
+        self.job_requests
+            .iter()
+            .filter(|request| job_state(request) == JobState::Assigned)
+    }
+
+    /// The number of job requests waiting for an agent.
+    pub fn queue_length(&self) -> usize {
+        self.job_requests
+            .iter()
+            .filter(|request| job_state(request) == JobState::Queued)
+            .count()
+    }
+
+    /// How long the longest waiting job request has been queued, if any are queued.
+    pub fn longest_wait(&self) -> Option<Duration> {
+        let queue_time = self.queued_jobs().first()?.queue_time?;
+        Some(
+            (self.taken_at - queue_time)
+                .max(time::Duration::ZERO)
+                .unsigned_abs(),
+        )
+    }
+}
+
+/// A change between two snapshots of an agent pool.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PoolEvent {
+    /// An agent was added to the pool.
+    AgentAdded(Box<TaskAgentReference>),
+    /// An agent was removed from the pool.
+    AgentRemoved(Box<TaskAgentReference>),
+    /// An agent came online.
+    AgentOnline(Box<TaskAgentReference>),
+    /// An agent went offline.
+    AgentOffline(Box<TaskAgentReference>),
+    /// A job request was queued.
+    JobQueued(Box<TaskAgentJobRequest>),
+    /// A job request was assigned to an agent.
+    JobAssigned(Box<TaskAgentJobRequest>),
+    /// A job request finished. The request has no result if it was no longer listed by the pool.
+    JobFinished(Box<TaskAgentJobRequest>),
+    /// The number of job requests waiting for an agent changed.
+    QueueLengthChanged { previous: usize, current: usize },
+    /// The state of the elastic pool changed.
+    ElasticPoolStateChanged {
+        previous: Option<elastic_pool::State>,
+        current: Option<elastic_pool::State>,
+    },
+    /// The elastic pool logged a new entry.
+    ElasticPoolLog(Box<ElasticPoolLog>),
+}
+
+/// A snapshot of an agent pool and the changes since the previous one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolUpdate {
+    pub snapshot: PoolSnapshot,
+    /// Changes since the previous snapshot; empty for the first snapshot.
+    pub events: Vec<PoolEvent>,
+}
+
+/// Compares two snapshots of the same pool and returns the changes between them:
+/// agent changes first, then job request changes in request order, then queue length
+/// and elastic pool changes.
+pub fn diff_snapshots(previous: &PoolSnapshot, current: &PoolSnapshot) -> Vec<PoolEvent> {
+    let mut events = Vec::new();
+
+    let previous_agents: HashMap<i32, &TaskAgent> = previous
+        .agents
+        .iter()
+        .filter_map(|agent| Some((agent.task_agent_reference.id?, agent)))
+        .collect();
+    for agent in &current.agents {
+        let reference = || Box::new(agent.task_agent_reference.clone());
+        match agent
+            .task_agent_reference
+            .id
+            .and_then(|id| previous_agents.get(&id))
+        {
+            None => events.push(PoolEvent::AgentAdded(reference())),
+            Some(previous) if is_online(previous) && !is_online(agent) => {
+                events.push(PoolEvent::AgentOffline(reference()))
+            }
+            Some(previous) if !is_online(previous) && is_online(agent) => {
+                events.push(PoolEvent::AgentOnline(reference()))
+            }
+            Some(_) => {}
+        }
+    }
+    for agent in &previous.agents {
+        let removed = !current
+            .agents
+            .iter()
+            .any(|current| current.task_agent_reference.id == agent.task_agent_reference.id);
+        if removed {
+            events.push(PoolEvent::AgentRemoved(Box::new(
+                agent.task_agent_reference.clone(),
+            )));
+        }
+    }
+
+    let previous_jobs: HashMap<i64, &TaskAgentJobRequest> = previous
+        .job_requests
+        .iter()
+        .filter_map(|request| Some((request.request_id?, request)))
+        .collect();
+    let mut current_jobs: Vec<&TaskAgentJobRequest> = current.job_requests.iter().collect();
+    current_jobs.sort_by_key(|request| request.request_id);
+    for request in current_jobs {
+        let from = request
+            .request_id
+            .and_then(|id| previous_jobs.get(&id))
+            .map_or(JobState::Unknown, |previous| job_state(previous));
+        let to = job_state(request);
+        if from < JobState::Queued && to >= JobState::Queued {
+            events.push(PoolEvent::JobQueued(Box::new(request.clone())));
+        }
+        if from < JobState::Assigned && to >= JobState::Assigned && request.assign_time.is_some() {
+            events.push(PoolEvent::JobAssigned(Box::new(request.clone())));
+        }
+        if from < JobState::Finished && to == JobState::Finished {
+            events.push(PoolEvent::JobFinished(Box::new(request.clone())));
+        }
+    }
+    // Finished requests eventually drop out of the listing; active ones that disappear
+    // have finished (or been deleted) since the previous snapshot.
+    let mut vanished: Vec<&TaskAgentJobRequest> = previous
+        .job_requests
+        .iter()
+        .filter(|request| {
+            job_state(request) != JobState::Finished
+                && !current
+                    .job_requests
+                    .iter()
+                    .any(|current| current.request_id == request.request_id)
+        })
+        .collect();
+    vanished.sort_by_key(|request| request.request_id);
+    for request in vanished {
+        events.push(PoolEvent::JobFinished(Box::new(request.clone())));
+    }
+
+    let (previous_length, current_length) = (previous.queue_length(), current.queue_length());
+    if previous_length != current_length {
+        events.push(PoolEvent::QueueLengthChanged {
+            previous: previous_length,
+            current: current_length,
+        });
+    }
+
+    let previous_state = previous
+        .elastic_pool
+        .as_ref()
+        .and_then(|pool| pool.state.clone());
+    let current_state = current
+        .elastic_pool
+        .as_ref()
+        .and_then(|pool| pool.state.clone());
+    if previous_state != current_state {
+        events.push(PoolEvent::ElasticPoolStateChanged {
+            previous: previous_state,
+            current: current_state,
+        });
+    }
+    let last_log_id = previous
+        .elastic_pool_logs
+        .iter()
+        .filter_map(|log| log.id)
+        .max();
+    let mut new_logs: Vec<&ElasticPoolLog> = current
+        .elastic_pool_logs
+        .iter()
+        .filter(|log| log.id > last_log_id)
+        .collect();
+    new_logs.sort_by_key(|log| log.id);
+    for log in new_logs {
+        events.push(PoolEvent::ElasticPoolLog(Box::new(log.clone())));
+    }
+
+    events
+}
+
+/// The progress of a job request, in the order it is made.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum JobState {
+    Unknown,
+    Queued,
+    Assigned,
+    Finished,
+}
+
+fn job_state(request: &TaskAgentJobRequest) -> JobState {
+    if request.finish_time.is_some() || request.result.is_some() {
+        JobState::Finished
+    } else if request.assign_time.is_some() {
+        JobState::Assigned
+    } else {
+        JobState::Queued
+    }
+}
+
+fn is_online(agent: &TaskAgent) -> bool {
+    agent.task_agent_reference.status == Some(task_agent_reference::Status::Online)
+}
+
+impl crate::distributed_task::Client {
+    /// Creates a builder that takes snapshots of an agent pool.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `pool_id`: The ID of the agent pool.
+    pub fn monitor_pool(
+        &self,
+        organization: impl Into<String>,
+        pool_id: i32,
+    ) -> PoolMonitorBuilder {
+        PoolMonitorBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            pool_id,
+            poll_interval: Duration::from_secs(30),
+            elastic_pool_log_count: 10,
+        }
+    }
+}
+
+/// `PoolMonitorBuilder` provides a mechanism for setting pool monitor options.
+///
+/// Call [`snapshot()`](PoolMonitorBuilder::snapshot) for a single snapshot of the pool, or
+/// [`stream()`](PoolMonitorBuilder::stream) to monitor it.
+#[derive(Clone)]
+pub struct PoolMonitorBuilder {
+    client: crate::distributed_task::Client,
+    organization: String,
+    pool_id: i32,
+    poll_interval: Duration,
+    elastic_pool_log_count: i32,
+}
+
+impl PoolMonitorBuilder {
+    /// Interval between snapshots (default 30 seconds).
+    #[must_use]
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Number of most recent elastic pool logs included in each snapshot (default 10).
+    /// Set to 0 to not fetch elastic pool logs.
+    #[must_use]
+    pub fn elastic_pool_log_count(mut self, elastic_pool_log_count: i32) -> Self {
+        self.elastic_pool_log_count = elastic_pool_log_count;
+        self
+    }
+
+    /// Takes a single snapshot of the pool.
+    pub fn snapshot(self) -> BoxFuture<'static, azure_core::Result<PoolSnapshot>> {
+        Box::pin(async move { self.take_snapshot(&mut None).await })
+    }
+
+    /// An endless stream of pool updates, one every poll interval, starting immediately.
+    /// The stream ends after the first error.
+    pub fn stream(self) -> BoxStream<'static, azure_core::Result<PoolUpdate>> {
+        let state = MonitorState {
+            builder: self,
+            is_elastic: None,
+            previous: None,
+            done: false,
+        };
+        Box::pin(futures::stream::unfold(state, |mut state| async move {
+            if state.done {
+                return None;
+            }
+            match state.poll().await {
+                Ok(update) => Some((Ok(update), state)),
+                Err(e) => {
+                    state.done = true;
+                    Some((Err(e), state))
+                }
+            }
+        }))
+    }
+
+    /// Takes a snapshot of the pool. `is_elastic` caches whether the pool is an elastic pool,
+    /// so that non-elastic pools are only probed once.
+    async fn take_snapshot(
+        &self,
+        is_elastic: &mut Option<bool>,
+    ) -> azure_core::Result<PoolSnapshot> {
+        let agents = self
+            .client
+            .agents_client()
+            .list(&self.organization, self.pool_id)
+            .include_assigned_request(true)
+            .await?
+            .value;
+        let (taken_at, job_requests) = self.job_requests().await?;
+
+        let mut elastic_pool = None;
+        let mut elastic_pool_logs = Vec::new();
+        if *is_elastic != Some(false) {
+            match self
+                .client
+                .elasticpools_client()
+                .get(&self.organization, self.pool_id)
+                .await
+            {
+                Ok(pool) => elastic_pool = Some(pool),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::HttpResponse {
+                            status: StatusCode::NotFound,
+                            ..
+                        }
+                    ) => {}
+                Err(e) => return Err(e),
+            }
+            *is_elastic = Some(elastic_pool.is_some());
+        }
+        if elastic_pool.is_some() && self.elastic_pool_log_count > 0 {
+            elastic_pool_logs = self
+                .client
+                .elasticpoollogs_client()
+                .list(&self.organization, self.pool_id)
+                .top(self.elastic_pool_log_count)
+                .await?
+                .value;
+        }
+
+        Ok(PoolSnapshot {
+            pool_id: self.pool_id,
+            taken_at,
+            agents,
+            job_requests,
+            elastic_pool,
+            elastic_pool_logs,
+        })
+    }
+
+    /// Lists the active and most recently finished job requests of the pool, with the time
+    /// of the response. The generated `requests` operations only cover agent cloud requests.
+    async fn job_requests(&self) -> azure_core::Result<(OffsetDateTime, Vec<TaskAgentJobRequest>)> {
+        #[derive(Deserialize)]
+        struct JobRequestList {
+            #[serde(default)]
+            value: Vec<TaskAgentJobRequest>,
+        }
+
+        let mut url = Url::parse(&format!(
+            "{}/{}/_apis/distributedtask/pools/{}/jobrequests",
+            self.client.endpoint(),
+            &self.organization,
+            self.pool_id
+        ))?;
+        url.query_pairs_mut()
+            .append_pair("completedRequestCount", COMPLETED_REQUEST_COUNT)
+            .append_pair(azure_core::query_param::API_VERSION, "7.1-preview");
+        let mut request = Request::new(url, Method::Get);
+        if let Some(auth_header) = self
+            .client
+            .token_credential()
+            .http_authorization_header(&self.client.scopes())
+            .await?
+        {
+            request.insert_header(headers::AUTHORIZATION, auth_header);
+        }
+        request.set_body(azure_core::EMPTY_BODY);
+        let response = self.client.send(&mut request).await?;
+        let date = headers::date_from_headers(response.headers()).context(
+            ErrorKind::DataConversion,
+            "job requests response has no valid Date header",
+        )?;
+        let list: JobRequestList = response.json().await?;
+        Ok((date, list.value))
+    }
+}
+
+struct MonitorState {
+    builder: PoolMonitorBuilder,
+    is_elastic: Option<bool>,
+    previous: Option<PoolSnapshot>,
+    done: bool,
+}
+
+impl MonitorState {
+    async fn poll(&mut self) -> azure_core::Result<PoolUpdate> {
+        if self.previous.is_some() {
+            azure_core::sleep::sleep(self.builder.poll_interval).await;
+        }
+        let snapshot = self.builder.take_snapshot(&mut self.is_elastic).await?;
+        let events = match &self.previous {
+            Some(previous) => diff_snapshots(previous, &snapshot),
+            None => Vec::new(),
+        };
+        self.previous = Some(snapshot.clone());
+        Ok(PoolUpdate { snapshot, events })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn snapshot(agents: serde_json::Value, job_requests: serde_json::Value) -> PoolSnapshot {
+        PoolSnapshot {
+            pool_id: 1,
+            taken_at: OffsetDateTime::parse(
+                "2024-01-01T00:10:00Z",
+                &time::format_description::well_known::Rfc3339,
+            )
+            .unwrap(),
+            agents: serde_json::from_value(agents).unwrap(),
+            job_requests: serde_json::from_value(job_requests).unwrap(),
+            elastic_pool: None,
+            elastic_pool_logs: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_counts() {
+        let snapshot = snapshot(
+            json!([
+                { "id": 1, "status": "online", "enabled": true },
+                { "id": 2, "status": "online", "enabled": true,
+                  "assignedRequest": { "requestId": 11, "assignTime": "2024-01-01T00:05:00Z" } },
+                { "id": 3, "status": "offline", "enabled": true }
+            ]),
+            json!([
+                { "requestId": 10, "queueTime": "2024-01-01T00:01:00Z",
+                  "finishTime": "2024-01-01T00:02:00Z", "result": "succeeded" },
+                { "requestId": 11, "queueTime": "2024-01-01T00:04:00Z",
+                  "assignTime": "2024-01-01T00:05:00Z" },
+                { "requestId": 12, "queueTime": "2024-01-01T00:08:00Z" },
+                { "requestId": 13, "queueTime": "2024-01-01T00:07:00Z" }
+            ]),
+        );
+        assert_eq!(snapshot.online_agents().count(), 2);
+        assert_eq!(snapshot.offline_agents().count(), 1);
+        assert_eq!(
+            snapshot
+                .idle_agents()
+                .map(|agent| agent.task_agent_reference.id)
+                .collect::<Vec<_>>(),
+            vec![Some(1)]
+        );
+        assert_eq!(snapshot.busy_agents().count(), 1);
+        assert_eq!(snapshot.running_jobs().count(), 1);
+        assert_eq!(snapshot.queue_length(), 2);
+        assert_eq!(snapshot.longest_wait(), Some(Duration::from_secs(180)));
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let previous = snapshot(
+            json!([
+                { "id": 1, "status": "online" },
+                { "id": 2, "status": "offline" },
+                { "id": 3, "status": "online" }
+            ]),
+            json!([
+                { "requestId": 10, "queueTime": "2024-01-01T00:01:00Z" },
+                { "requestId": 11, "queueTime": "2024-01-01T00:02:00Z",
+                  "assignTime": "2024-01-01T00:03:00Z" }
+            ]),
+        );
+        let current = snapshot(
+            json!([
+                { "id": 1, "status": "offline" },
+                { "id": 2, "status": "online" },
+                { "id": 4, "status": "online" }
+            ]),
+            json!([
+                { "requestId": 10, "queueTime": "2024-01-01T00:01:00Z",
+                  "assignTime": "2024-01-01T00:06:00Z" },
+                { "requestId": 12, "queueTime": "2024-01-01T00:07:00Z" },
+                { "requestId": 13, "queueTime": "2024-01-01T00:07:00Z" }
+            ]),
+        );
+        let events: Vec<String> = diff_snapshots(&previous, &current)
+            .into_iter()
+            .map(|event| match event {
+                PoolEvent::AgentAdded(agent) => format!("added {:?}", agent.id),
+                PoolEvent::AgentRemoved(agent) => format!("removed {:?}", agent.id),
+                PoolEvent::AgentOnline(agent) => format!("online {:?}", agent.id),
+                PoolEvent::AgentOffline(agent) => format!("offline {:?}", agent.id),
+                PoolEvent::JobQueued(request) => format!("queued {:?}", request.request_id),
+                PoolEvent::JobAssigned(request) => format!("assigned {:?}", request.request_id),
+                PoolEvent::JobFinished(request) => format!("finished {:?}", request.request_id),
+                PoolEvent::QueueLengthChanged { previous, current } => {
+                    format!("queue {previous} -> {current}")
+                }
+                event => format!("{event:?}"),
+            })
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                "offline Some(1)",
+                "online Some(2)",
+                "added Some(4)",
+                "removed Some(3)",
+                "assigned Some(10)",
+                "queued Some(12)",
+                "queued Some(13)",
+                "finished Some(11)",
+                "queue 1 -> 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_elastic_pool() {
+        let mut previous = snapshot(json!([]), json!([]));
+        previous.elastic_pool = serde_json::from_value(json!({ "state": "online" })).unwrap();
+        previous.elastic_pool_logs =
+            serde_json::from_value(json!([{ "id": 5, "message": "old" }])).unwrap();
+        let mut current = previous.clone();
+        current.elastic_pool = serde_json::from_value(json!({ "state": "unhealthy" })).unwrap();
+        current.elastic_pool_logs = serde_json::from_value(json!([
+            { "id": 7, "message": "newest" },
+            { "id": 6, "message": "new" },
+            { "id": 5, "message": "old" }
+        ]))
+        .unwrap();
+        let events = diff_snapshots(&previous, &current);
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0],
+            PoolEvent::ElasticPoolStateChanged {
+                previous: Some(elastic_pool::State::Online),
+                current: Some(elastic_pool::State::Unhealthy),
+            }
+        );
+        let messages: Vec<_> = events[1..]
+            .iter()
+            .map(|event| match event {
+                PoolEvent::ElasticPoolLog(log) => log.message.clone(),
+                event => panic!("unexpected event {event:?}"),
+            })
+            .collect();
+        assert_eq!(messages, vec![Some("new".into()), Some("newest".into())]);
+    }
+}
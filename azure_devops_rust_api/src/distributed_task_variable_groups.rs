@@ -0,0 +1,506 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Edit variable groups without losing secrets.
+//!
+//! Updating a variable group replaces all of its variables, but the service never returns
+//! the values of secret variables. `distributed_task::Client::edit_variable_group()` loads a
+//! group into a [`VariableGroupEditor`], which records which variables are added, changed or
+//! removed and, when saved, sends untouched secrets back without a value so that the service
+//! keeps their stored values. The group's provider data (e.g. the Key Vault a group is linked
+//! to) is preserved as returned by the service. Secret values are masked in `Debug` output.
+//!
+//! ```rust
+//!     let mut editor = distributed_task_client
+//!         .edit_variable_group(&organization, &project, group_id)
+//!         .await?;
+//!     editor
+//!         .set("environment", "staging")
+//!         .set_secret("api-key", new_api_key)
+//!         .remove("obsolete");
+//!     println!("Changes: {:?}", editor.changes());
+//!     editor.save().await?;
+//! ```
+
+use crate::distributed_task::models::{
+    AzureKeyVaultVariableGroupProviderData, AzureKeyVaultVariableValue, VariableGroup,
+    VariableGroupParameters,
+};
+use crate::secret::Secret;
+use azure_core::error::{Error, ErrorKind};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The `type` of variable groups linked to an Azure Key Vault.
+const KEY_VAULT_GROUP_TYPE: &str = "AzureKeyVault";
+
+/// A variable of a variable group.
+///
+/// Variables are held as [`AzureKeyVaultVariableValue`]s, so that the extra properties of Key
+/// Vault secrets (e.g. `enabled` and `contentType`) are sent back unchanged.
+#[derive(Clone, Default, PartialEq)]
+pub struct Variable(AzureKeyVaultVariableValue);
+
+impl Variable {
+    /// The value of the variable. Always `None` for secrets loaded from the service and
+    /// for Key Vault linked variables.
+    pub fn value(&self) -> Option<&str> {
+        self.0
+            .variable_value
+            .value
+            .as_ref()
+            .map(|value| value.secret().as_str())
+    }
+
+    pub fn is_secret(&self) -> bool {
+        self.0.variable_value.is_secret == Some(true)
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.0.variable_value.is_read_only == Some(true)
+    }
+}
+
+impl From<Variable> for AzureKeyVaultVariableValue {
+    fn from(variable: Variable) -> Self {
+        variable.0
+    }
+}
+
+impl std::fmt::Debug for Variable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value = match self.value() {
+            Some(_) if self.is_secret() => Some("***"),
+            value => value,
+        };
+        f.debug_struct("Variable")
+            .field("value", &value)
+            .field("is_secret", &self.is_secret())
+            .field("is_read_only", &self.is_read_only())
+            .finish()
+    }
+}
+
+/// A pending change to a variable of a variable group.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VariableChange {
+    Added(String),
+    Updated(String),
+    Removed(String),
+}
+
+impl crate::distributed_task::Client {
+    /// Loads a variable group for editing.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `group_id`: Id of the variable group.
+    pub async fn edit_variable_group(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        group_id: i32,
+    ) -> azure_core::Result<VariableGroupEditor> {
+        let organization = organization.into();
+        let group = self
+            .variablegroups_client()
+            .get(&organization, project, group_id)
+            .await?;
+        let mut editor = VariableGroupEditor {
+            client: self.clone(),
+            organization,
+            group_id,
+            group: VariableGroup::new(),
+            variables: BTreeMap::new(),
+            original: BTreeSet::new(),
+            secrets: BTreeSet::new(),
+            changed: BTreeSet::new(),
+            removed: BTreeSet::new(),
+        };
+        editor.load(group)?;
+        Ok(editor)
+    }
+}
+
+/// A variable group being edited. Changes are only sent to the service by
+/// [`save()`](VariableGroupEditor::save).
+#[derive(Clone)]
+pub struct VariableGroupEditor {
+    client: crate::distributed_task::Client,
+    organization: String,
+    group_id: i32,
+    /// The group as last returned by the service, without its variables.
+    group: VariableGroup,
+    variables: BTreeMap<String, Variable>,
+    /// Names of the variables returned by the service.
+    original: BTreeSet<String>,
+    /// Names of the variables that were secrets when loaded, whose values the service keeps.
+    secrets: BTreeSet<String>,
+    /// Names of the variables added or updated since the group was loaded.
+    changed: BTreeSet<String>,
+    /// Names of the loaded variables removed since the group was loaded.
+    removed: BTreeSet<String>,
+}
+
+impl VariableGroupEditor {
+    pub fn id(&self) -> i32 {
+        self.group_id
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.group.name.as_deref()
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.group.description.as_deref()
+    }
+
+    /// Whether the group's variables are secrets of an Azure Key Vault.
+    pub fn is_key_vault(&self) -> bool {
+        self.group.type_.as_deref() == Some(KEY_VAULT_GROUP_TYPE)
+    }
+
+    /// The Key Vault the group is linked to, if it is a Key Vault linked group.
+    pub fn key_vault(&self) -> Option<AzureKeyVaultVariableGroupProviderData> {
+        if !self.is_key_vault() {
+            return None;
+        }
+        serde_json::from_value(self.group.provider_data.clone()?).ok()
+    }
+
+    /// Looks up a variable. Variable names are case-insensitive.
+    pub fn variable(&self, name: &str) -> Option<&Variable> {
+        self.variables.get(&self.key(name))
+    }
+
+    /// The variables of the group, including pending changes, ordered by name.
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &Variable)> {
+        self.variables
+            .iter()
+            .map(|(name, variable)| (name.as_str(), variable))
+    }
+
+    /// Sets the value of a variable, adding it if it doesn't exist.
+    /// An existing secret stays secret.
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        let name = self.key(&name.into());
+        let is_secret = self.variables.get(&name).map_or(false, Variable::is_secret);
+        self.update(name, Some(value.into()), is_secret)
+    }
+
+    /// Sets the value of a secret variable, adding it if it doesn't exist.
+    /// An existing plain variable becomes a secret.
+    pub fn set_secret(&mut self, name: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        let name = self.key(&name.into());
+        self.update(name, Some(value.into()), true)
+    }
+
+    /// Adds a Key Vault secret to a Key Vault linked group. The secret's value is read from
+    /// the vault when a pipeline runs.
+    pub fn link_secret(&mut self, name: impl Into<String>) -> &mut Self {
+        let name = self.key(&name.into());
+        self.update(name, None, true)
+    }
+
+    /// Marks a variable as read-only (or not). Does nothing if the variable doesn't exist.
+    pub fn set_read_only(&mut self, name: &str, read_only: bool) -> &mut Self {
+        let name = self.key(name);
+        if let Some(variable) = self.variables.get_mut(&name) {
+            if variable.is_read_only() != read_only {
+                let value = &mut variable.0.variable_value;
+                value.is_read_only = flag(read_only, value.is_read_only);
+                self.changed.insert(name);
+            }
+        }
+        self
+    }
+
+    /// Removes a variable. Does nothing if the variable doesn't exist.
+    pub fn remove(&mut self, name: &str) -> &mut Self {
+        let name = self.key(name);
+        if self.variables.remove(&name).is_some() {
+            self.changed.remove(&name);
+            if self.original.contains(&name) {
+                self.removed.insert(name);
+            }
+        }
+        self
+    }
+
+    pub fn set_description(&mut self, description: impl Into<String>) -> &mut Self {
+        self.group.description = Some(description.into());
+        self
+    }
+
+    /// The pending variable changes, ordered by variable name.
+    pub fn changes(&self) -> Vec<VariableChange> {
+        let mut changes: Vec<(&String, VariableChange)> = self
+            .changed
+            .iter()
+            .map(|name| {
+                let change = if self.original.contains(name) {
+                    VariableChange::Updated(name.clone())
+                } else {
+                    VariableChange::Added(name.clone())
+                };
+                (name, change)
+            })
+            .chain(
+                self.removed
+                    .iter()
+                    .map(|name| (name, VariableChange::Removed(name.clone()))),
+            )
+            .collect();
+        changes.sort_by_key(|(name, _)| *name);
+        changes.into_iter().map(|(_, change)| change).collect()
+    }
+
+    /// Whether there are pending variable changes.
+    pub fn is_modified(&self) -> bool {
+        !self.changed.is_empty() || !self.removed.is_empty()
+    }
+
+    /// Sends the group, with its pending changes, to the service and returns the updated group.
+    ///
+    /// Fails without sending anything if a value was set for a variable of a Key Vault linked
+    /// group, or if a new secret has no value.
+    pub async fn save(&mut self) -> azure_core::Result<VariableGroup> {
+        let body = self.update_body()?;
+        let group = self
+            .client
+            .variablegroups_client()
+            .update(&self.organization, body, self.group_id)
+            .await?;
+        self.load(group.clone())?;
+        Ok(group)
+    }
+
+    /// The body of the update request: the group as returned by the service, with the
+    /// current variables.
+    fn update_body(&self) -> azure_core::Result<VariableGroupParameters> {
+        let is_key_vault = self.is_key_vault();
+        let mut variables = BTreeMap::new();
+        for (name, variable) in &self.variables {
+            if self.changed.contains(name) {
+                if is_key_vault && variable.value().is_some() {
+                    return Err(Error::with_message(ErrorKind::Other, || {
+                        format!(
+                            "variable group {} is linked to a Key Vault: the value of {name} must be set in the vault",
+                            self.group_id
+                        )
+                    }));
+                }
+                let missing_secret = !is_key_vault
+                    && variable.is_secret()
+                    && variable.value().is_none()
+                    && !self.secrets.contains(name);
+                if missing_secret {
+                    return Err(Error::with_message(ErrorKind::Other, || {
+                        format!("secret variable {name} has no value")
+                    }));
+                }
+            }
+            // Secrets without a value keep the value stored by the service.
+            variables.insert(name, &variable.0);
+        }
+
+        Ok(VariableGroupParameters {
+            description: self.group.description.clone(),
+            name: self.group.name.clone(),
+            provider_data: self.group.provider_data.clone(),
+            type_: self.group.type_.clone(),
+            variable_group_project_references: self.group.variable_group_project_references.clone(),
+            variables: Some(serde_json::to_value(variables)?),
+        })
+    }
+
+    /// Replaces the editor's state with a group returned by the service.
+    fn load(&mut self, mut group: VariableGroup) -> azure_core::Result<()> {
+        let variables: BTreeMap<String, AzureKeyVaultVariableValue> = match group.variables.take() {
+            Some(variables) => serde_json::from_value(variables)?,
+            None => BTreeMap::new(),
+        };
+        let variables: BTreeMap<String, Variable> = variables
+            .into_iter()
+            .map(|(name, mut value)| {
+                // The service doesn't return the values of secrets.
+                if value.variable_value.is_secret == Some(true) {
+                    value.variable_value.value = None;
+                }
+                (name, Variable(value))
+            })
+            .collect();
+        self.group = group;
+        self.original = variables.keys().cloned().collect();
+        self.secrets = variables
+            .iter()
+            .filter(|(_, variable)| variable.is_secret())
+            .map(|(name, _)| name.clone())
+            .collect();
+        self.variables = variables;
+        self.changed.clear();
+        self.removed.clear();
+        Ok(())
+    }
+
+    fn update(&mut self, name: String, value: Option<String>, is_secret: bool) -> &mut Self {
+        let variable = &mut self
+            .variables
+            .entry(name.clone())
+            .or_default()
+            .0
+            .variable_value;
+        variable.value = value.map(Secret::new);
+        variable.is_secret = flag(is_secret, variable.is_secret);
+        self.removed.remove(&name);
+        self.changed.insert(name);
+        self
+    }
+
+    /// The name under which a variable is stored: the existing variable's name if one matches
+    /// case-insensitively, otherwise `name` itself.
+    fn key(&self, name: &str) -> String {
+        self.variables
+            .keys()
+            .chain(self.removed.iter())
+            .find(|key| key.eq_ignore_ascii_case(name))
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+}
+
+impl std::fmt::Debug for VariableGroupEditor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VariableGroupEditor")
+            .field("id", &self.group_id)
+            .field("name", &self.name())
+            .field("variables", &self.variables)
+            .field("changes", &self.changes())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The new value of a `Some(true)`/`None` flag: `Some(false)` is only kept when clearing a
+/// flag the service returned.
+fn flag(value: bool, current: Option<bool>) -> Option<bool> {
+    if value {
+        Some(true)
+    } else {
+        current.and(Some(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn editor(group: serde_json::Value) -> VariableGroupEditor {
+        let mut editor = VariableGroupEditor {
+            client: crate::distributed_task::ClientBuilder::new(
+                crate::Credential::unauthenticated(),
+            )
+            .build(),
+            organization: "org".to_string(),
+            group_id: 1,
+            group: VariableGroup::new(),
+            variables: BTreeMap::new(),
+            original: BTreeSet::new(),
+            secrets: BTreeSet::new(),
+            changed: BTreeSet::new(),
+            removed: BTreeSet::new(),
+        };
+        editor.load(serde_json::from_value(group).unwrap()).unwrap();
+        editor
+    }
+
+    #[test]
+    fn test_update_body_preserves_secrets() {
+        let mut editor = editor(json!({
+            "id": 1,
+            "name": "settings",
+            "type": "Vsts",
+            "variableGroupProjectReferences": [{ "name": "settings" }],
+            "variables": {
+                "Environment": { "value": "dev" },
+                "password": { "isSecret": true, "value": null },
+                "token": { "isSecret": true, "value": null },
+                "obsolete": { "value": "x" }
+            }
+        }));
+        editor
+            .set("environment", "staging")
+            .set_secret("password", "hunter2")
+            .set("region", "westeurope")
+            .remove("OBSOLETE");
+        assert_eq!(
+            editor.changes(),
+            vec![
+                VariableChange::Updated("Environment".to_string()),
+                VariableChange::Removed("obsolete".to_string()),
+                VariableChange::Updated("password".to_string()),
+                VariableChange::Added("region".to_string()),
+            ]
+        );
+        assert_eq!(
+            serde_json::to_value(editor.update_body().unwrap()).unwrap(),
+            json!({
+                "name": "settings",
+                "type": "Vsts",
+                "variableGroupProjectReferences": [{ "name": "settings" }],
+                "variables": {
+                    "Environment": { "value": "staging" },
+                    "password": { "isSecret": true, "value": "hunter2" },
+                    "region": { "value": "westeurope" },
+                    "token": { "isSecret": true }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_key_vault_group() {
+        let mut editor = editor(json!({
+            "id": 1,
+            "name": "vault",
+            "type": "AzureKeyVault",
+            "providerData": { "serviceEndpointId": "e", "vault": "my-vault" },
+            "variables": {
+                "db-password": { "isSecret": true, "enabled": true, "contentType": "" }
+            }
+        }));
+        assert_eq!(
+            editor.key_vault().and_then(|vault| vault.vault),
+            Some("my-vault".to_string())
+        );
+        editor.link_secret("api-key");
+        let body = serde_json::to_value(editor.update_body().unwrap()).unwrap();
+        assert_eq!(
+            body["providerData"],
+            json!({ "serviceEndpointId": "e", "vault": "my-vault" })
+        );
+        assert_eq!(
+            body["variables"],
+            json!({
+                "api-key": { "isSecret": true },
+                "db-password": { "isSecret": true, "enabled": true, "contentType": "" }
+            })
+        );
+        editor.set("api-key", "value");
+        assert!(editor.update_body().is_err());
+    }
+
+    #[test]
+    fn test_debug_masks_secrets() {
+        let mut editor = editor(json!({ "id": 1, "variables": {} }));
+        editor
+            .set_secret("password", "hunter2")
+            .set("user", "admin");
+        let debug = format!("{editor:?}");
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("***"));
+        assert!(debug.contains("admin"));
+        editor.link_secret("token");
+        assert!(editor.update_body().is_err());
+    }
+}
@@ -0,0 +1,561 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Git commit builder.
+//!
+//! Stages file changes and pushes them as a single commit, without having to assemble
+//! [`GitPush`], [`GitCommitRef`] and [`GitChange`] values by hand.
+//!
+//! ```rust
+//!     let push = git_client
+//!         .commit(&organization, &project, &repository_name, "feature/docs")
+//!         .create_branch_from("main")
+//!         .add_text("/docs/README.md", "# Docs\n")
+//!         .delete("/docs/old.md")
+//!         .message("Update docs")
+//!         .await?;
+//! ```
+
+use crate::git::models::{
+    change::ChangeType, item_content::ContentType, Change, GitChange, GitCommitRef, GitPush,
+    GitRefUpdate, GitUserDate, ItemContent,
+};
+use azure_core::error::{Error, ErrorKind};
+use azure_core::StatusCode;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Object id used as the old object id when creating a ref, or the new object id when deleting one.
+pub const ZERO_OBJECT_ID: &str = "0000000000000000000000000000000000000000";
+
+/// Content of a staged file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileContent {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl FileContent {
+    fn to_item_content(&self) -> ItemContent {
+        match self {
+            FileContent::Text(text) => ItemContent::new(text.clone(), ContentType::RawText),
+            FileContent::Binary(bytes) => {
+                ItemContent::new(BASE64_STANDARD.encode(bytes), ContentType::Base64Encoded)
+            }
+        }
+    }
+}
+
+/// A file change staged by a [`CommitBuilder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StagedChange {
+    Add { path: String, content: FileContent },
+    Edit { path: String, content: FileContent },
+    Delete { path: String },
+    Rename { from: String, to: String },
+}
+
+impl StagedChange {
+    /// The paths this change reads or writes: the target path, plus the source of a rename.
+    fn paths(&self) -> impl Iterator<Item = &str> {
+        let (path, source) = match self {
+            StagedChange::Add { path, .. }
+            | StagedChange::Edit { path, .. }
+            | StagedChange::Delete { path } => (path, None),
+            StagedChange::Rename { from, to } => (to, Some(from)),
+        };
+        std::iter::once(path.as_str()).chain(source.map(String::as_str))
+    }
+
+    fn to_git_change(&self) -> GitChange {
+        let (change_type, path, content, source) = match self {
+            StagedChange::Add { path, content } => (ChangeType::Add, path, Some(content), None),
+            StagedChange::Edit { path, content } => (ChangeType::Edit, path, Some(content), None),
+            StagedChange::Delete { path } => (ChangeType::Delete, path, None, None),
+            StagedChange::Rename { from, to } => (ChangeType::Rename, to, None, Some(from)),
+        };
+        GitChange::new(Change {
+            new_content: content.map(FileContent::to_item_content),
+            source_server_item: source.cloned(),
+            ..Change::new(change_type, json!({ "path": path }))
+        })
+    }
+}
+
+impl crate::git::Client {
+    /// Returns the object id of the commit at the head of `branch`, or `None` if the branch doesn't exist.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `repository_id`: The name or ID of the repository.
+    /// * `branch`: Branch name, with or without the `refs/heads/` prefix.
+    pub async fn get_branch_head(
+        &self,
+        organization: &str,
+        project: &str,
+        repository_id: &str,
+        branch: &str,
+    ) -> azure_core::Result<Option<String>> {
+        let name = branch_ref_name(branch);
+        let refs = self
+            .refs_client()
+            .list(organization, repository_id, project)
+            .filter(name.trim_start_matches("refs/"))
+            .await?
+            .value;
+        Ok(refs
+            .into_iter()
+            .find(|git_ref| git_ref.name == name)
+            .map(|git_ref| git_ref.object_id))
+    }
+
+    /// Creates a commit builder that pushes staged changes to `branch`.
+    ///
+    /// The current head of the branch is resolved automatically when the commit is pushed.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `repository_id`: The name or ID of the repository.
+    /// * `branch`: Target branch name, with or without the `refs/heads/` prefix.
+    pub fn commit(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        repository_id: impl Into<String>,
+        branch: impl Into<String>,
+    ) -> CommitBuilder {
+        CommitBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            repository_id: repository_id.into(),
+            branch: branch_ref_name(&branch.into()),
+            base: None,
+            message: None,
+            author: None,
+            changes: Vec::new(),
+            max_retries: 3,
+        }
+    }
+}
+
+/// `CommitBuilder` stages file changes for a single commit.
+///
+/// To push the commit, invoke `.await`, which returns the created [`GitPush`].
+///
+/// If the branch moves between resolving its head and pushing (so the push is rejected
+/// because `oldObjectId` is stale), the commits pushed in the meantime are checked against
+/// the staged paths. If none of them touched a staged path (or the source of a staged rename),
+/// the staged changes are re-applied on top of the new head and the push is retried, up to
+/// [`CommitBuilder::max_retries`] times. Otherwise the push fails with a [`CommitConflictError`].
+#[derive(Clone)]
+pub struct CommitBuilder {
+    client: crate::git::Client,
+    organization: String,
+    project: String,
+    repository_id: String,
+    branch: String,
+    base: Option<String>,
+    message: Option<String>,
+    author: Option<GitUserDate>,
+    changes: Vec<StagedChange>,
+    max_retries: u32,
+}
+
+impl CommitBuilder {
+    /// Commit message.
+    #[must_use]
+    pub fn message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Commit author. Defaults to the authenticated user.
+    #[must_use]
+    pub fn author(mut self, name: impl Into<String>, email: impl Into<String>) -> Self {
+        self.author = Some(GitUserDate {
+            name: Some(name.into()),
+            email: Some(email.into()),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Creates the target branch from `base` (a branch name or commit id) if it doesn't exist.
+    ///
+    /// Without this, pushing to a branch that doesn't exist is an error.
+    #[must_use]
+    pub fn create_branch_from(mut self, base: impl Into<String>) -> Self {
+        self.base = Some(base.into());
+        self
+    }
+
+    /// Maximum number of times to retry a push rejected because the branch moved.
+    #[must_use]
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Stages a new text file.
+    #[must_use]
+    pub fn add_text(self, path: impl Into<String>, content: impl Into<String>) -> Self {
+        self.stage(StagedChange::Add {
+            path: path.into(),
+            content: FileContent::Text(content.into()),
+        })
+    }
+
+    /// Stages a new binary file.
+    #[must_use]
+    pub fn add_binary(self, path: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        self.stage(StagedChange::Add {
+            path: path.into(),
+            content: FileContent::Binary(content.into()),
+        })
+    }
+
+    /// Stages new content for an existing text file.
+    #[must_use]
+    pub fn edit_text(self, path: impl Into<String>, content: impl Into<String>) -> Self {
+        self.stage(StagedChange::Edit {
+            path: path.into(),
+            content: FileContent::Text(content.into()),
+        })
+    }
+
+    /// Stages new content for an existing binary file.
+    #[must_use]
+    pub fn edit_binary(self, path: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        self.stage(StagedChange::Edit {
+            path: path.into(),
+            content: FileContent::Binary(content.into()),
+        })
+    }
+
+    /// Stages deletion of a file.
+    #[must_use]
+    pub fn delete(self, path: impl Into<String>) -> Self {
+        self.stage(StagedChange::Delete { path: path.into() })
+    }
+
+    /// Stages a rename of a file.
+    #[must_use]
+    pub fn rename(self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.stage(StagedChange::Rename {
+            from: from.into(),
+            to: to.into(),
+        })
+    }
+
+    /// Stages a change.
+    #[must_use]
+    pub fn stage(mut self, change: StagedChange) -> Self {
+        self.changes.push(change);
+        self
+    }
+
+    /// The changes staged so far.
+    pub fn staged_changes(&self) -> &[StagedChange] {
+        &self.changes
+    }
+
+    /// Resolves `(old_object_id, parent)` for the push.
+    async fn resolve_parent(&self) -> azure_core::Result<(String, String)> {
+        let head = self
+            .client
+            .get_branch_head(
+                &self.organization,
+                &self.project,
+                &self.repository_id,
+                &self.branch,
+            )
+            .await?;
+        if let Some(head) = head {
+            return Ok((head.clone(), head));
+        }
+
+        let base = self.base.as_deref().ok_or_else(|| {
+            Error::message(
+                ErrorKind::Other,
+                format!("Branch {} does not exist", self.branch),
+            )
+        })?;
+        let base_commit = if is_object_id(base) {
+            base.to_string()
+        } else {
+            self.client
+                .get_branch_head(&self.organization, &self.project, &self.repository_id, base)
+                .await?
+                .ok_or_else(|| {
+                    Error::message(
+                        ErrorKind::Other,
+                        format!("Base branch {base} does not exist"),
+                    )
+                })?
+        };
+        Ok((ZERO_OBJECT_ID.to_string(), base_commit))
+    }
+
+    fn push_request(&self, old_object_id: String, parent: String) -> GitPush {
+        let commit = GitCommitRef {
+            comment: self.message.clone(),
+            author: self.author.clone(),
+            changes: self
+                .changes
+                .iter()
+                .map(StagedChange::to_git_change)
+                .collect(),
+            parents: vec![parent],
+            ..Default::default()
+        };
+        GitPush {
+            commits: vec![commit],
+            ref_updates: vec![GitRefUpdate {
+                name: Some(self.branch.clone()),
+                old_object_id: Some(old_object_id),
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    /// Returns the paths changed between commits `base` and `target`.
+    async fn changed_paths(&self, base: &str, target: &str) -> azure_core::Result<Vec<String>> {
+        let mut paths = Vec::new();
+        let mut skip = 0;
+        loop {
+            let diffs = self
+                .client
+                .diffs_client()
+                .get(&self.organization, &self.repository_id, &self.project)
+                .base_version(base)
+                .base_version_type("commit")
+                .target_version(target)
+                .target_version_type("commit")
+                .skip(skip)
+                .await?;
+            let count = diffs.changes.len();
+            skip += count as i32;
+            for change in diffs.changes {
+                if let Some(path) = change.change.item.get("path").and_then(|p| p.as_str()) {
+                    paths.push(path.to_string());
+                }
+                if let Some(source) = change.change.source_server_item {
+                    paths.push(source);
+                }
+            }
+            if count == 0 || diffs.all_changes_included != Some(false) {
+                return Ok(paths);
+            }
+        }
+    }
+
+    async fn push(self) -> azure_core::Result<GitPush> {
+        if self.changes.is_empty() {
+            return Err(Error::message(ErrorKind::Other, "No changes staged"));
+        }
+
+        let (mut old_object_id, base) = self.resolve_parent().await?;
+        let mut parent = base.clone();
+        let mut attempt = 0;
+        let mut delay = Duration::from_millis(200);
+        loop {
+            let result = self
+                .client
+                .pushes_client()
+                .create(
+                    &self.organization,
+                    self.push_request(old_object_id, parent),
+                    &self.repository_id,
+                    &self.project,
+                )
+                .await;
+            match result {
+                Err(err) if attempt < self.max_retries && is_stale_ref_error(&err) => {
+                    attempt += 1;
+                    azure_core::sleep::sleep(delay).await;
+                    delay *= 2;
+                }
+                result => return result,
+            }
+
+            (old_object_id, parent) = self.resolve_parent().await?;
+            if parent != base {
+                let changed = self.changed_paths(&base, &parent).await?;
+                let conflicts = conflicting_paths(&self.changes, &changed);
+                if !conflicts.is_empty() {
+                    return Err(Error::new(
+                        ErrorKind::Other,
+                        CommitConflictError {
+                            branch: self.branch.clone(),
+                            head: parent,
+                            paths: conflicts,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+}
+
+impl std::future::IntoFuture for CommitBuilder {
+    type Output = azure_core::Result<GitPush>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<GitPush>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.push())
+    }
+}
+
+/// Returns the full ref name for a branch (e.g. `main` -> `refs/heads/main`).
+pub fn branch_ref_name(branch: &str) -> String {
+    if branch.starts_with("refs/") {
+        branch.to_string()
+    } else {
+        format!("refs/heads/{branch}")
+    }
+}
+
+/// Returns `true` if `value` looks like a full (40 hex digit) Git object id.
+pub fn is_object_id(value: &str) -> bool {
+    value.len() == 40 && value.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// The branch was updated, between resolving its head and pushing, by commits that touched
+/// paths staged by the [`CommitBuilder`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommitConflictError {
+    /// The full name of the target branch.
+    pub branch: String,
+    /// The new head of the branch.
+    pub head: String,
+    /// The staged paths that were changed on the branch.
+    pub paths: Vec<String>,
+}
+
+impl fmt::Display for CommitConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} was updated to {} with conflicting changes to {}",
+            self.branch,
+            self.head,
+            self.paths.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for CommitConflictError {}
+
+/// Returns the staged paths (including rename sources) that appear in `changed`.
+fn conflicting_paths(staged: &[StagedChange], changed: &[String]) -> Vec<String> {
+    let changed: BTreeSet<&str> = changed.iter().map(String::as_str).collect();
+    staged
+        .iter()
+        .flat_map(StagedChange::paths)
+        .filter(|path| changed.contains(path))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Azure DevOps rejects a ref update whose `oldObjectId` is no longer current with `409 Conflict`
+/// and a `TF401028` message. Other conflicts (e.g. a policy rejection) are not retried.
+fn is_stale_ref_error(err: &Error) -> bool {
+    err.as_http_error().map_or(false, |http_error| {
+        is_stale_ref_rejection(http_error.status(), http_error.error_message())
+    })
+}
+
+fn is_stale_ref_rejection(status: StatusCode, message: Option<&str>) -> bool {
+    status == StatusCode::Conflict
+        && message.map_or(false, |message| {
+            message.starts_with("TF401028") || message.contains("has already been updated")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_branch_ref_name() {
+        assert_eq!(branch_ref_name("main"), "refs/heads/main");
+        assert_eq!(branch_ref_name("refs/heads/main"), "refs/heads/main");
+        assert_eq!(branch_ref_name("users/me/fix"), "refs/heads/users/me/fix");
+    }
+
+    #[test]
+    fn test_conflicting_paths() {
+        let staged = vec![
+            StagedChange::Edit {
+                path: "/README.md".to_string(),
+                content: FileContent::Text("# Readme\n".to_string()),
+            },
+            StagedChange::Rename {
+                from: "/old.txt".to_string(),
+                to: "/new.txt".to_string(),
+            },
+        ];
+        let changed = |paths: &[&str]| paths.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        assert!(conflicting_paths(&staged, &changed(&["/", "/docs/index.md"])).is_empty());
+        assert_eq!(
+            conflicting_paths(&staged, &changed(&["/old.txt", "/README.md", "/other.md"])),
+            vec!["/README.md", "/old.txt"]
+        );
+    }
+
+    #[test]
+    fn test_is_stale_ref_rejection() {
+        let stale = "TF401028: The reference 'refs/heads/main' has already been updated by another client, so you cannot update it. Please try again.";
+        assert!(is_stale_ref_rejection(StatusCode::Conflict, Some(stale)));
+        assert!(!is_stale_ref_rejection(
+            StatusCode::Conflict,
+            Some("TF402455: Pushes to this branch are not permitted; you must use a pull request to update this branch.")
+        ));
+        assert!(!is_stale_ref_rejection(StatusCode::Conflict, None));
+        assert!(!is_stale_ref_rejection(StatusCode::BadRequest, Some(stale)));
+    }
+
+    #[test]
+    fn test_staged_change_serialization() {
+        let change = StagedChange::Add {
+            path: "/image.png".to_string(),
+            content: FileContent::Binary(vec![0x89, b'P', b'N', b'G']),
+        };
+        assert_eq!(
+            serde_json::to_value(change.to_git_change()).unwrap(),
+            json!({
+                "changeType": "add",
+                "item": { "path": "/image.png" },
+                "newContent": { "content": "iVBORw==", "contentType": "base64Encoded" }
+            })
+        );
+
+        let change = StagedChange::Rename {
+            from: "/old.txt".to_string(),
+            to: "/new.txt".to_string(),
+        };
+        assert_eq!(
+            serde_json::to_value(change.to_git_change()).unwrap(),
+            json!({
+                "changeType": "rename",
+                "item": { "path": "/new.txt" },
+                "sourceServerItem": "/old.txt"
+            })
+        );
+    }
+}
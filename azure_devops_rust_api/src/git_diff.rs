@@ -0,0 +1,866 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Unified diffs between commits, branches, tags and pull request iterations.
+//!
+//! The diffs API only lists the paths that changed. `git::Client::diff()` and
+//! `git::Client::pull_request_iteration_diff()` additionally fetch both versions of each
+//! changed file (concurrently) and produce a standard unified diff, with renames
+//! and binary files marked the same way `git diff` does.
+//!
+//! ```rust
+//!     let diff = git_client
+//!         .diff(&organization, &project, &repository_name, GitVersion::branch("main"), GitVersion::branch("feature"))
+//!         .context_lines(5)
+//!         .await?;
+//!     print!("{diff}");
+//! ```
+
+use crate::git::models::{change::ChangeType, GitChange};
+use azure_core::error::{Error, ErrorKind};
+use azure_core::{headers, Method, Request, Url};
+use futures::{stream, StreamExt, TryStreamExt};
+use similar::{Algorithm, ChangeTag, TextDiff};
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Default number of unchanged lines shown around each change.
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+/// Default maximum size of each version of a file that will be diffed.
+pub const DEFAULT_MAX_FILE_SIZE: usize = 1024 * 1024;
+/// Default number of file versions fetched concurrently.
+pub const DEFAULT_CONCURRENCY: usize = 8;
+/// Number of leading bytes checked for NUL bytes when detecting binary files (as git does).
+const BINARY_DETECTION_LENGTH: usize = 8000;
+/// Number of changes requested per page from the diffs API.
+const CHANGES_PAGE_SIZE: i32 = 1000;
+
+/// A version of a repository to diff.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GitVersion {
+    Branch(String),
+    Commit(String),
+    Tag(String),
+}
+
+impl GitVersion {
+    /// A branch, with or without the `refs/heads/` prefix.
+    pub fn branch(name: impl AsRef<str>) -> Self {
+        let name = name.as_ref();
+        GitVersion::Branch(name.strip_prefix("refs/heads/").unwrap_or(name).to_string())
+    }
+
+    /// A commit id.
+    pub fn commit(commit_id: impl Into<String>) -> Self {
+        GitVersion::Commit(commit_id.into())
+    }
+
+    /// A tag, with or without the `refs/tags/` prefix.
+    pub fn tag(name: impl AsRef<str>) -> Self {
+        let name = name.as_ref();
+        GitVersion::Tag(name.strip_prefix("refs/tags/").unwrap_or(name).to_string())
+    }
+
+    fn version(&self) -> &str {
+        match self {
+            GitVersion::Branch(version)
+            | GitVersion::Commit(version)
+            | GitVersion::Tag(version) => version,
+        }
+    }
+
+    fn version_type(&self) -> &'static str {
+        match self {
+            GitVersion::Branch(_) => "branch",
+            GitVersion::Commit(_) => "commit",
+            GitVersion::Tag(_) => "tag",
+        }
+    }
+}
+
+/// How a file changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    Added,
+    Deleted,
+    Modified,
+    Renamed,
+}
+
+/// The content differences of a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffContent {
+    /// Text differences (empty for pure renames).
+    Text(Vec<Hunk>),
+    /// At least one version of the file is binary.
+    Binary,
+    /// At least one version of the file exceeds the size limit.
+    TooLarge { limit: usize },
+}
+
+/// A line in a [`Hunk`], including its line terminator (if any).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffLine {
+    Context(String),
+    Removed(String),
+    Added(String),
+}
+
+/// A group of changed lines and their surrounding context.
+///
+/// Line numbers start at 1. As in `diff -u`, the start line of an empty range is the
+/// line *before* the (empty) range.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+/// The differences of a single file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileDiff {
+    pub status: FileStatus,
+    /// Path before the change (`None` for added files).
+    pub old_path: Option<String>,
+    /// Path after the change (`None` for deleted files).
+    pub new_path: Option<String>,
+    pub old_object_id: Option<String>,
+    pub new_object_id: Option<String>,
+    pub content: DiffContent,
+}
+
+/// A diff between two versions of a repository.
+///
+/// `Display` formats the diff as unified diff text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnifiedDiff {
+    pub base_commit: Option<String>,
+    pub target_commit: Option<String>,
+    pub files: Vec<FileDiff>,
+}
+
+impl crate::git::Client {
+    /// Creates a builder for a unified diff between two versions of a repository.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `repository_id`: The name or ID of the repository.
+    /// * `base`: The version to diff from.
+    /// * `target`: The version to diff to.
+    pub fn diff(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        repository_id: impl Into<String>,
+        base: GitVersion,
+        target: GitVersion,
+    ) -> DiffBuilder {
+        self.diff_builder(
+            organization,
+            project,
+            repository_id,
+            DiffSource::Versions { base, target },
+        )
+    }
+
+    /// Creates a builder for a unified diff between two iterations of a pull request.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `repository_id`: The name or ID of the repository.
+    /// * `pull_request_id`: ID of the pull request.
+    /// * `base_iteration`: The iteration to diff from, or 0 to diff from the
+    ///   common commit of the target iteration and the target branch.
+    /// * `target_iteration`: The iteration to diff to.
+    pub fn pull_request_iteration_diff(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        repository_id: impl Into<String>,
+        pull_request_id: i32,
+        base_iteration: i32,
+        target_iteration: i32,
+    ) -> DiffBuilder {
+        self.diff_builder(
+            organization,
+            project,
+            repository_id,
+            DiffSource::PullRequestIterations {
+                pull_request_id,
+                base_iteration,
+                target_iteration,
+            },
+        )
+    }
+
+    fn diff_builder(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        repository_id: impl Into<String>,
+        source: DiffSource,
+    ) -> DiffBuilder {
+        DiffBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            repository_id: repository_id.into(),
+            source,
+            diff_common_commit: None,
+            context_lines: DEFAULT_CONTEXT_LINES,
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum DiffSource {
+    Versions {
+        base: GitVersion,
+        target: GitVersion,
+    },
+    PullRequestIterations {
+        pull_request_id: i32,
+        base_iteration: i32,
+        target_iteration: i32,
+    },
+}
+
+/// `DiffBuilder` provides a mechanism for setting optional diff parameters.
+///
+/// Invoke `.await` to fetch the changes and file contents, which returns a [`UnifiedDiff`].
+#[derive(Clone)]
+pub struct DiffBuilder {
+    client: crate::git::Client,
+    organization: String,
+    project: String,
+    repository_id: String,
+    source: DiffSource,
+    diff_common_commit: Option<bool>,
+    context_lines: usize,
+    max_file_size: usize,
+    concurrency: usize,
+}
+
+impl DiffBuilder {
+    /// If true, diff from the common commit of the base and target versions rather than from
+    /// the base version itself. The service defaults to true.
+    ///
+    /// Ignored for pull request iteration diffs.
+    #[must_use]
+    pub fn diff_common_commit(mut self, diff_common_commit: bool) -> Self {
+        self.diff_common_commit = Some(diff_common_commit);
+        self
+    }
+
+    /// Number of unchanged lines shown around each change (default 3).
+    #[must_use]
+    pub fn context_lines(mut self, context_lines: usize) -> Self {
+        self.context_lines = context_lines;
+        self
+    }
+
+    /// Maximum size, in bytes, of each version of a file. Larger files are reported as
+    /// [`DiffContent::TooLarge`] without being fully downloaded (default 1 MiB).
+    #[must_use]
+    pub fn max_file_size(mut self, max_file_size: usize) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Number of file versions fetched concurrently (default 8).
+    #[must_use]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Resolves the diff source to the versions passed to the diffs API.
+    async fn versions(&self) -> azure_core::Result<(GitVersion, GitVersion, Option<bool>)> {
+        match &self.source {
+            DiffSource::Versions { base, target } => {
+                Ok((base.clone(), target.clone(), self.diff_common_commit))
+            }
+            DiffSource::PullRequestIterations {
+                pull_request_id,
+                base_iteration,
+                target_iteration,
+            } => {
+                let iterations = self.client.pull_request_iterations_client();
+                let target = iterations
+                    .get(
+                        &self.organization,
+                        &self.repository_id,
+                        *pull_request_id,
+                        *target_iteration,
+                        &self.project,
+                    )
+                    .await?;
+                let base = if *base_iteration == 0 {
+                    target.common_ref_commit.as_ref()
+                } else {
+                    None
+                };
+                let base_commit = match base {
+                    Some(commit) => commit.commit_id.clone(),
+                    None => {
+                        let base = iterations
+                            .get(
+                                &self.organization,
+                                &self.repository_id,
+                                *pull_request_id,
+                                *base_iteration,
+                                &self.project,
+                            )
+                            .await?;
+                        base.source_ref_commit.and_then(|commit| commit.commit_id)
+                    }
+                };
+                let target_commit = target.source_ref_commit.and_then(|commit| commit.commit_id);
+                match (base_commit, target_commit) {
+                    (Some(base), Some(target)) => Ok((
+                        GitVersion::Commit(base),
+                        GitVersion::Commit(target),
+                        Some(false),
+                    )),
+                    _ => Err(Error::with_message(ErrorKind::Other, || {
+                        format!(
+                            "pull request {pull_request_id} iterations {base_iteration} and {target_iteration} have no commits to diff"
+                        )
+                    })),
+                }
+            }
+        }
+    }
+
+    async fn changes(
+        &self,
+        base: &GitVersion,
+        target: &GitVersion,
+        diff_common_commit: Option<bool>,
+    ) -> azure_core::Result<(Option<String>, Option<String>, Vec<GitChange>)> {
+        let mut changes = Vec::new();
+        let mut skip = 0;
+        loop {
+            let mut request = self
+                .client
+                .diffs_client()
+                .get(&self.organization, &self.repository_id, &self.project)
+                .base_version(base.version())
+                .base_version_type(base.version_type())
+                .target_version(target.version())
+                .target_version_type(target.version_type())
+                .top(CHANGES_PAGE_SIZE)
+                .skip(skip);
+            if let Some(diff_common_commit) = diff_common_commit {
+                request = request.diff_common_commit(diff_common_commit);
+            }
+            let diffs = request.await?;
+            let page_len = diffs.changes.len() as i32;
+            changes.extend(diffs.changes);
+            if diffs.all_changes_included.unwrap_or(true) || page_len == 0 {
+                let base_commit = if diff_common_commit.unwrap_or(true) {
+                    diffs.common_commit.or(diffs.base_commit)
+                } else {
+                    diffs.base_commit
+                };
+                return Ok((base_commit, diffs.target_commit, changes));
+            }
+            skip += page_len;
+        }
+    }
+
+    /// Fetches a file version, returning `None` if it exceeds the size limit.
+    async fn fetch(&self, version: &FileVersion) -> azure_core::Result<Option<Vec<u8>>> {
+        let mut url = match version {
+            FileVersion::Object(object_id) => Url::parse(&format!(
+                "{}/{}/{}/_apis/git/repositories/{}/blobs/{}",
+                self.client.endpoint(),
+                &self.organization,
+                &self.project,
+                &self.repository_id,
+                object_id
+            ))?,
+            FileVersion::Path { path, commit_id } => {
+                let mut url = Url::parse(&format!(
+                    "{}/{}/{}/_apis/git/repositories/{}/items",
+                    self.client.endpoint(),
+                    &self.organization,
+                    &self.project,
+                    &self.repository_id
+                ))?;
+                url.query_pairs_mut()
+                    .append_pair("path", path)
+                    .append_pair("versionDescriptor.version", commit_id)
+                    .append_pair("versionDescriptor.versionType", "commit");
+                url
+            }
+        };
+        url.query_pairs_mut()
+            .append_pair("$format", "octetstream")
+            .append_pair(azure_core::query_param::API_VERSION, "7.1-preview");
+        let mut request = Request::new(url, Method::Get);
+        if let Some(auth_header) = self
+            .client
+            .token_credential()
+            .http_authorization_header(&self.client.scopes())
+            .await?
+        {
+            request.insert_header(headers::AUTHORIZATION, auth_header);
+        }
+        request.insert_header(headers::ACCEPT, "application/octet-stream");
+        request.set_body(azure_core::EMPTY_BODY);
+        let (_, _, mut body) = self.client.send(&mut request).await?.deconstruct();
+        let mut content = Vec::new();
+        while let Some(bytes) = body.next().await {
+            content.extend_from_slice(&bytes?);
+            if content.len() > self.max_file_size {
+                return Ok(None);
+            }
+        }
+        Ok(Some(content))
+    }
+
+    async fn file_diff(&self, change: PendingFile) -> azure_core::Result<FileDiff> {
+        let old = match &change.old_version {
+            Some(version) => self.fetch(version).await?,
+            None => Some(Vec::new()),
+        };
+        let new = match &change.new_version {
+            Some(version) if Some(version) == change.old_version.as_ref() => old.clone(),
+            Some(version) => self.fetch(version).await?,
+            None => Some(Vec::new()),
+        };
+        let content = match (old, new) {
+            (Some(old), Some(new)) => text_diff(&old, &new, self.context_lines),
+            _ => DiffContent::TooLarge {
+                limit: self.max_file_size,
+            },
+        };
+        Ok(FileDiff {
+            status: change.status,
+            old_path: change.old_path,
+            new_path: change.new_path,
+            old_object_id: change.old_object_id,
+            new_object_id: change.new_object_id,
+            content,
+        })
+    }
+
+    async fn diff(self) -> azure_core::Result<UnifiedDiff> {
+        let (base, target, diff_common_commit) = self.versions().await?;
+        let (base_commit, target_commit, changes) =
+            self.changes(&base, &target, diff_common_commit).await?;
+        let pending = pending_files(&changes, base_commit.as_deref(), target_commit.as_deref());
+        let files = stream::iter(pending.into_iter().map(|file| self.file_diff(file)))
+            .buffered(self.concurrency)
+            .try_collect()
+            .await?;
+        Ok(UnifiedDiff {
+            base_commit,
+            target_commit,
+            files,
+        })
+    }
+}
+
+impl std::future::IntoFuture for DiffBuilder {
+    type Output = azure_core::Result<UnifiedDiff>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<UnifiedDiff>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.diff())
+    }
+}
+
+/// Where to fetch a version of a file from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum FileVersion {
+    Object(String),
+    Path { path: String, commit_id: String },
+}
+
+/// A changed file whose contents haven't been fetched yet.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct PendingFile {
+    status: FileStatus,
+    old_path: Option<String>,
+    new_path: Option<String>,
+    old_object_id: Option<String>,
+    new_object_id: Option<String>,
+    old_version: Option<FileVersion>,
+    new_version: Option<FileVersion>,
+}
+
+fn item_str<'a>(change: &'a GitChange, name: &str) -> Option<&'a str> {
+    change
+        .change
+        .item
+        .get(name)
+        .and_then(|value| value.as_str())
+}
+
+fn is_file(change: &GitChange) -> bool {
+    let item = &change.change.item;
+    !item
+        .get("isFolder")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+        && item
+            .get("gitObjectType")
+            .and_then(|v| v.as_str())
+            .map_or(true, |object_type| object_type == "blob")
+}
+
+/// Converts the diffs API's changes into files to diff, pairing up deletes and adds of
+/// identical content as renames.
+fn pending_files(
+    changes: &[GitChange],
+    base_commit: Option<&str>,
+    target_commit: Option<&str>,
+) -> Vec<PendingFile> {
+    let version = |object_id: Option<&str>, path: Option<&str>, commit_id: Option<&str>| {
+        object_id
+            .map(|object_id| FileVersion::Object(object_id.to_string()))
+            .or_else(|| match (path, commit_id) {
+                (Some(path), Some(commit_id)) => Some(FileVersion::Path {
+                    path: path.to_string(),
+                    commit_id: commit_id.to_string(),
+                }),
+                _ => None,
+            })
+    };
+
+    let mut files: Vec<PendingFile> = Vec::new();
+    for change in changes.iter().filter(|change| is_file(change)) {
+        let path = item_str(change, "path");
+        let object_id = item_str(change, "objectId");
+        let original_object_id = item_str(change, "originalObjectId");
+        let file = match change.change.change_type {
+            ChangeType::Add => PendingFile {
+                status: FileStatus::Added,
+                old_path: None,
+                new_path: path.map(str::to_string),
+                old_object_id: None,
+                new_object_id: object_id.map(str::to_string),
+                old_version: None,
+                new_version: version(object_id, path, target_commit),
+            },
+            ChangeType::Delete | ChangeType::DeleteSourceRename => {
+                let old_object_id = original_object_id.or(object_id);
+                PendingFile {
+                    status: FileStatus::Deleted,
+                    old_path: path.map(str::to_string),
+                    new_path: None,
+                    old_object_id: old_object_id.map(str::to_string),
+                    new_object_id: None,
+                    old_version: version(old_object_id, path, base_commit),
+                    new_version: None,
+                }
+            }
+            ChangeType::Rename | ChangeType::EditRename => {
+                let old_path = change
+                    .original_path
+                    .as_deref()
+                    .or(change.change.source_server_item.as_deref())
+                    .or(path);
+                let old_object_id = original_object_id.or(object_id);
+                PendingFile {
+                    status: FileStatus::Renamed,
+                    old_path: old_path.map(str::to_string),
+                    new_path: path.map(str::to_string),
+                    old_object_id: old_object_id.map(str::to_string),
+                    new_object_id: object_id.map(str::to_string),
+                    old_version: version(old_object_id, old_path, base_commit),
+                    new_version: version(object_id, path, target_commit),
+                }
+            }
+            _ => PendingFile {
+                status: FileStatus::Modified,
+                old_path: path.map(str::to_string),
+                new_path: path.map(str::to_string),
+                old_object_id: original_object_id.map(str::to_string),
+                new_object_id: object_id.map(str::to_string),
+                old_version: version(original_object_id, path, base_commit),
+                new_version: version(object_id, path, target_commit),
+            },
+        };
+        files.push(file);
+    }
+
+    // Detect renames the service reported as a delete plus an add of the same blob.
+    let mut i = 0;
+    while i < files.len() {
+        if files[i].status == FileStatus::Deleted && files[i].old_object_id.is_some() {
+            let added = files.iter().position(|file| {
+                file.status == FileStatus::Added && file.new_object_id == files[i].old_object_id
+            });
+            if let Some(index) = added {
+                let added = files.remove(index);
+                if index < i {
+                    i -= 1;
+                }
+                let deleted = &mut files[i];
+                deleted.status = FileStatus::Renamed;
+                deleted.new_path = added.new_path;
+                deleted.new_object_id = added.new_object_id;
+                deleted.new_version = deleted.old_version.clone();
+                continue;
+            }
+        }
+        i += 1;
+    }
+
+    files.sort_by(|a, b| {
+        let path = |file: &PendingFile| file.new_path.clone().or_else(|| file.old_path.clone());
+        path(a).cmp(&path(b))
+    });
+    files
+}
+
+fn is_binary(content: &[u8]) -> bool {
+    content[..content.len().min(BINARY_DETECTION_LENGTH)].contains(&0)
+        || std::str::from_utf8(content).is_err()
+}
+
+fn text_diff(old: &[u8], new: &[u8], context_lines: usize) -> DiffContent {
+    if is_binary(old) || is_binary(new) {
+        return if old == new {
+            DiffContent::Text(Vec::new())
+        } else {
+            DiffContent::Binary
+        };
+    }
+    // Both are valid UTF-8, checked by `is_binary`.
+    let old = String::from_utf8_lossy(old);
+    let new = String::from_utf8_lossy(new);
+    DiffContent::Text(diff_hunks(&old, &new, context_lines))
+}
+
+/// Computes the unified diff hunks between two texts, line by line.
+pub fn diff_hunks(old: &str, new: &str, context_lines: usize) -> Vec<Hunk> {
+    let diff = TextDiff::configure()
+        .algorithm(Algorithm::Myers)
+        .diff_lines(old, new);
+    diff.grouped_ops(context_lines)
+        .iter()
+        .map(|group| {
+            let mut hunk = Hunk {
+                old_start: group.first().map_or(0, |op| op.old_range().start),
+                old_lines: 0,
+                new_start: group.first().map_or(0, |op| op.new_range().start),
+                new_lines: 0,
+                lines: Vec::new(),
+            };
+            for change in group.iter().flat_map(|op| diff.iter_changes(op)) {
+                let line = change.value().to_string();
+                let line = match change.tag() {
+                    ChangeTag::Equal => {
+                        hunk.old_lines += 1;
+                        hunk.new_lines += 1;
+                        DiffLine::Context(line)
+                    }
+                    ChangeTag::Delete => {
+                        hunk.old_lines += 1;
+                        DiffLine::Removed(line)
+                    }
+                    ChangeTag::Insert => {
+                        hunk.new_lines += 1;
+                        DiffLine::Added(line)
+                    }
+                };
+                hunk.lines.push(line);
+            }
+            if hunk.old_lines > 0 {
+                hunk.old_start += 1;
+            }
+            if hunk.new_lines > 0 {
+                hunk.new_start += 1;
+            }
+            hunk
+        })
+        .collect()
+}
+
+impl fmt::Display for DiffLine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (prefix, line) = match self {
+            DiffLine::Context(line) => (' ', line),
+            DiffLine::Removed(line) => ('-', line),
+            DiffLine::Added(line) => ('+', line),
+        };
+        match line.strip_suffix('\n') {
+            Some(line) => writeln!(f, "{prefix}{line}"),
+            None => write!(f, "{prefix}{line}\n\\ No newline at end of file\n"),
+        }
+    }
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = |start: usize, lines: usize| {
+            if lines == 1 {
+                start.to_string()
+            } else {
+                format!("{start},{lines}")
+            }
+        };
+        writeln!(
+            f,
+            "@@ -{} +{} @@",
+            range(self.old_start, self.old_lines),
+            range(self.new_start, self.new_lines)
+        )?;
+        self.lines.iter().try_for_each(|line| line.fmt(f))
+    }
+}
+
+impl fmt::Display for FileDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let strip = |path: &str| path.trim_start_matches('/').to_string();
+        let old_path = self.old_path.as_deref().map(strip);
+        let new_path = self.new_path.as_deref().map(strip);
+        let a = old_path
+            .as_deref()
+            .or(new_path.as_deref())
+            .unwrap_or_default();
+        let b = new_path
+            .as_deref()
+            .or(old_path.as_deref())
+            .unwrap_or_default();
+        writeln!(f, "diff --git a/{a} b/{b}")?;
+        match self.status {
+            // The diffs API doesn't report file modes, so assume regular files.
+            FileStatus::Added => writeln!(f, "new file mode 100644")?,
+            FileStatus::Deleted => writeln!(f, "deleted file mode 100644")?,
+            FileStatus::Renamed => {
+                if self.old_object_id.is_some() && self.old_object_id == self.new_object_id {
+                    writeln!(f, "similarity index 100%")?;
+                }
+                writeln!(f, "rename from {a}")?;
+                writeln!(f, "rename to {b}")?;
+            }
+            FileStatus::Modified => {}
+        }
+        if let (Some(old), Some(new)) = (&self.old_object_id, &self.new_object_id) {
+            if old != new {
+                writeln!(f, "index {old}..{new}")?;
+            }
+        }
+        let from = old_path.map_or("/dev/null".to_string(), |path| format!("a/{path}"));
+        let to = new_path.map_or("/dev/null".to_string(), |path| format!("b/{path}"));
+        match &self.content {
+            DiffContent::Text(hunks) => {
+                if !hunks.is_empty() {
+                    writeln!(f, "--- {from}")?;
+                    writeln!(f, "+++ {to}")?;
+                }
+                hunks.iter().try_for_each(|hunk| hunk.fmt(f))
+            }
+            DiffContent::Binary => writeln!(f, "Binary files {from} and {to} differ"),
+            DiffContent::TooLarge { limit } => writeln!(
+                f,
+                "Files {from} and {to} differ (larger than {limit} bytes, not diffed)"
+            ),
+        }
+    }
+}
+
+impl fmt::Display for UnifiedDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.files.iter().try_for_each(|file| file.fmt(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(old: &str, new: &str) -> String {
+        diff_hunks(old, new, 1)
+            .iter()
+            .map(|hunk| hunk.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_hunks() {
+        assert_eq!(render("a\nb\n", "a\nb\n"), "");
+        assert_eq!(render("", "a\n"), "@@ -0,0 +1 @@\n+a\n");
+        assert_eq!(
+            render("a\nb\nc\nd\ne\nf\ng\n", "a\nB\nc\nd\ne\nf\nG\n"),
+            "@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n@@ -6,2 +6,2 @@\n f\n-g\n+G\n"
+        );
+        assert_eq!(
+            render("a\nb\nc\nd\n", "a\nc\nx\nd"),
+            "@@ -1,4 +1,4 @@\n a\n-b\n c\n-d\n+x\n+d\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn test_diff_is_minimal() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n";
+        let new = "0\n1\n3\n4\n9\n5\n7\n8\n";
+        let edits = diff_hunks(old, new, 0)
+            .iter()
+            .flat_map(|hunk| hunk.lines.clone())
+            .filter(|line| !matches!(line, DiffLine::Context(_)))
+            .count();
+        assert_eq!(edits, 4);
+    }
+
+    #[test]
+    fn test_pending_files_detects_renames() {
+        let changes: Vec<GitChange> = serde_json::from_value(json!([
+            { "changeType": "delete", "item": { "path": "/old.txt", "objectId": "1111", "gitObjectType": "blob" } },
+            { "changeType": "add", "item": { "path": "/src", "isFolder": true, "gitObjectType": "tree" } },
+            { "changeType": "add", "item": { "path": "/src/new.txt", "objectId": "1111", "gitObjectType": "blob" } },
+            { "changeType": "edit", "item": { "path": "/a.txt", "objectId": "3333", "originalObjectId": "2222", "gitObjectType": "blob" } }
+        ]))
+        .unwrap();
+        let files = pending_files(&changes, Some("base"), Some("target"));
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].status, FileStatus::Modified);
+        assert_eq!(
+            files[0].old_version,
+            Some(FileVersion::Object("2222".to_string()))
+        );
+        assert_eq!(files[1].status, FileStatus::Renamed);
+        assert_eq!(files[1].old_path.as_deref(), Some("/old.txt"));
+        assert_eq!(files[1].new_path.as_deref(), Some("/src/new.txt"));
+
+        let diff = FileDiff {
+            status: files[1].status.clone(),
+            old_path: files[1].old_path.clone(),
+            new_path: files[1].new_path.clone(),
+            old_object_id: files[1].old_object_id.clone(),
+            new_object_id: files[1].new_object_id.clone(),
+            content: text_diff(b"x\n", b"x\n", 3),
+        };
+        assert_eq!(
+            diff.to_string(),
+            "diff --git a/old.txt b/src/new.txt\nsimilarity index 100%\nrename from old.txt\nrename to src/new.txt\n"
+        );
+    }
+
+    #[test]
+    fn test_binary_content() {
+        assert_eq!(text_diff(b"\x00\x01", b"\x00\x02", 3), DiffContent::Binary);
+        assert_eq!(text_diff(b"\xff", b"\xfe", 3), DiffContent::Binary);
+        assert_eq!(
+            text_diff(b"\x00", b"\x00", 3),
+            DiffContent::Text(Vec::new())
+        );
+    }
+}
@@ -0,0 +1,533 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Pull request comment threads anchored to file positions.
+//!
+//! Creating a review comment on a line of a file requires filling in the thread's
+//! `threadContext` (file path and left/right line positions) and `pullRequestThreadContext`
+//! (iteration ids and change tracking id). `git::Client::create_pull_request_thread()`
+//! takes a file path, line range and diff side and fills these in, using the latest
+//! pull request iteration unless one is specified.
+//!
+//! ```rust
+//!     let thread = git_client
+//!         .create_pull_request_thread(&organization, &project, &repository_name, pull_request_id, "Typo here")
+//!         .file("/src/main.rs")
+//!         .lines(10, 12)
+//!         .await?;
+//!     git_client
+//!         .reply_to_pull_request_thread(&organization, &project, &repository_name, pull_request_id, thread_id, "Fixed")
+//!         .await?;
+//!     git_client
+//!         .resolve_pull_request_thread(&organization, &project, &repository_name, pull_request_id, thread_id)
+//!         .await?;
+//! ```
+
+use crate::git::models::{
+    comment, comment_thread, Comment, CommentIterationContext, CommentPosition, CommentThread,
+    CommentThreadContext, GitPullRequestCommentThread, GitPullRequestCommentThreadContext,
+};
+use azure_core::error::{Error, ErrorKind};
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Maximum number of changes requested per page when looking up a file's change tracking id.
+const CHANGES_PAGE_SIZE: i32 = 1000;
+
+/// Which side of the pull request diff a thread is anchored to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffSide {
+    /// The original (target branch) version of the file, e.g. for comments on deleted lines.
+    Left,
+    /// The modified (source branch) version of the file.
+    #[default]
+    Right,
+}
+
+/// A range of lines (and optionally character offsets) within a file.
+///
+/// Lines and offsets start at 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    pub start_line: i32,
+    pub start_offset: i32,
+    pub end_line: i32,
+    pub end_offset: i32,
+}
+
+impl LineRange {
+    /// A range covering whole lines `start_line..=end_line`.
+    pub fn lines(start_line: i32, end_line: i32) -> Self {
+        Self {
+            start_line,
+            start_offset: 1,
+            end_line,
+            // Offsets past the end of the line select up to the end of the line.
+            end_offset: i32::MAX,
+        }
+    }
+
+    fn validate(&self) -> azure_core::Result<()> {
+        if self.start_line < 1 || self.start_offset < 1 || self.end_offset < 1 {
+            return Err(Error::message(
+                ErrorKind::Other,
+                "thread line and offset positions start at 1",
+            ));
+        }
+        if (self.end_line, self.end_offset) < (self.start_line, self.start_offset) {
+            return Err(Error::with_message(ErrorKind::Other, || {
+                format!(
+                    "thread range ends ({}:{}) before it starts ({}:{})",
+                    self.end_line, self.end_offset, self.start_line, self.start_offset
+                )
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl crate::git::Client {
+    /// Creates a builder for a new pull request comment thread.
+    ///
+    /// Without a file the thread is a general comment on the pull request.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `repository_id`: The repository ID of the pull request's target branch.
+    /// * `pull_request_id`: ID of the pull request.
+    /// * `content`: The content of the thread's first comment.
+    pub fn create_pull_request_thread(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        repository_id: impl Into<String>,
+        pull_request_id: i32,
+        content: impl Into<String>,
+    ) -> CreateThreadBuilder {
+        CreateThreadBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            repository_id: repository_id.into(),
+            pull_request_id,
+            content: content.into(),
+            file_path: None,
+            range: None,
+            side: DiffSide::default(),
+            iteration_id: None,
+            compare_to_iteration_id: None,
+            status: comment_thread::Status::Active,
+        }
+    }
+
+    /// Adds a reply to a pull request comment thread.
+    ///
+    /// The reply is attached to the thread's first comment.
+    pub async fn reply_to_pull_request_thread(
+        &self,
+        organization: &str,
+        project: &str,
+        repository_id: &str,
+        pull_request_id: i32,
+        thread_id: i32,
+        content: impl Into<String>,
+    ) -> azure_core::Result<Comment> {
+        let comment = Comment {
+            content: Some(content.into()),
+            comment_type: Some(comment::CommentType::Text),
+            parent_comment_id: Some(1),
+            ..Default::default()
+        };
+        self.pull_request_thread_comments_client()
+            .create(
+                organization,
+                comment,
+                repository_id,
+                pull_request_id,
+                thread_id,
+                project,
+            )
+            .await
+    }
+
+    /// Updates the status of a pull request comment thread.
+    pub async fn set_pull_request_thread_status(
+        &self,
+        organization: &str,
+        project: &str,
+        repository_id: &str,
+        pull_request_id: i32,
+        thread_id: i32,
+        status: comment_thread::Status,
+    ) -> azure_core::Result<GitPullRequestCommentThread> {
+        let thread = GitPullRequestCommentThread {
+            comment_thread: CommentThread {
+                status: Some(status),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        self.pull_request_threads_client()
+            .update(
+                organization,
+                thread,
+                repository_id,
+                pull_request_id,
+                thread_id,
+                project,
+            )
+            .await
+    }
+
+    /// Marks a pull request comment thread as resolved (`fixed`).
+    pub async fn resolve_pull_request_thread(
+        &self,
+        organization: &str,
+        project: &str,
+        repository_id: &str,
+        pull_request_id: i32,
+        thread_id: i32,
+    ) -> azure_core::Result<GitPullRequestCommentThread> {
+        self.set_pull_request_thread_status(
+            organization,
+            project,
+            repository_id,
+            pull_request_id,
+            thread_id,
+            comment_thread::Status::Fixed,
+        )
+        .await
+    }
+
+    /// Reactivates a resolved pull request comment thread.
+    pub async fn reactivate_pull_request_thread(
+        &self,
+        organization: &str,
+        project: &str,
+        repository_id: &str,
+        pull_request_id: i32,
+        thread_id: i32,
+    ) -> azure_core::Result<GitPullRequestCommentThread> {
+        self.set_pull_request_thread_status(
+            organization,
+            project,
+            repository_id,
+            pull_request_id,
+            thread_id,
+            comment_thread::Status::Active,
+        )
+        .await
+    }
+}
+
+/// `CreateThreadBuilder` provides a mechanism for anchoring a new comment thread.
+///
+/// Invoke `.await` to create the thread.
+#[derive(Clone)]
+pub struct CreateThreadBuilder {
+    client: crate::git::Client,
+    organization: String,
+    project: String,
+    repository_id: String,
+    pull_request_id: i32,
+    content: String,
+    file_path: Option<String>,
+    range: Option<LineRange>,
+    side: DiffSide,
+    iteration_id: Option<i32>,
+    compare_to_iteration_id: Option<i32>,
+    status: comment_thread::Status,
+}
+
+impl CreateThreadBuilder {
+    /// The file the thread is left on, relative to the repository root.
+    #[must_use]
+    pub fn file(mut self, file_path: impl Into<String>) -> Self {
+        self.file_path = Some(file_path.into());
+        self
+    }
+
+    /// Anchors the thread to a single line.
+    #[must_use]
+    pub fn line(self, line: i32) -> Self {
+        self.range(LineRange::lines(line, line))
+    }
+
+    /// Anchors the thread to the lines `start_line..=end_line`.
+    #[must_use]
+    pub fn lines(self, start_line: i32, end_line: i32) -> Self {
+        self.range(LineRange::lines(start_line, end_line))
+    }
+
+    /// Anchors the thread to a line and character range.
+    #[must_use]
+    pub fn range(mut self, range: LineRange) -> Self {
+        self.range = Some(range);
+        self
+    }
+
+    /// Which side of the diff the line numbers refer to (default: [`DiffSide::Right`]).
+    #[must_use]
+    pub fn side(mut self, side: DiffSide) -> Self {
+        self.side = side;
+        self
+    }
+
+    /// The pull request iteration the line numbers refer to (default: the latest iteration).
+    #[must_use]
+    pub fn iteration(mut self, iteration_id: i32) -> Self {
+        self.iteration_id = Some(iteration_id);
+        self
+    }
+
+    /// The iteration being compared against (default: 1, i.e. all changes in the pull request).
+    #[must_use]
+    pub fn compare_to_iteration(mut self, iteration_id: i32) -> Self {
+        self.compare_to_iteration_id = Some(iteration_id);
+        self
+    }
+
+    /// The initial status of the thread (default: `active`).
+    #[must_use]
+    pub fn status(mut self, status: comment_thread::Status) -> Self {
+        self.status = status;
+        self
+    }
+
+    async fn latest_iteration(&self) -> azure_core::Result<i32> {
+        let iterations = self
+            .client
+            .pull_request_iterations_client()
+            .list(
+                &self.organization,
+                &self.repository_id,
+                self.pull_request_id,
+                &self.project,
+            )
+            .await?;
+        iterations
+            .value
+            .iter()
+            .filter_map(|iteration| iteration.id)
+            .max()
+            .ok_or_else(|| {
+                Error::with_message(ErrorKind::Other, || {
+                    format!("pull request {} has no iterations", self.pull_request_id)
+                })
+            })
+    }
+
+    /// Looks up the change tracking id of `file_path` in the iteration's changes.
+    async fn change_tracking_id(
+        &self,
+        file_path: &str,
+        iteration_id: i32,
+        compare_to: i32,
+    ) -> azure_core::Result<Option<i32>> {
+        let mut skip = 0;
+        loop {
+            let mut request = self
+                .client
+                .pull_request_iteration_changes_client()
+                .get(
+                    &self.organization,
+                    &self.repository_id,
+                    self.pull_request_id,
+                    iteration_id,
+                    &self.project,
+                )
+                .top(CHANGES_PAGE_SIZE)
+                .skip(skip);
+            // Comparing against the first iteration is the same as comparing against the
+            // common commit, which is the API's default.
+            if compare_to > 1 {
+                request = request.compare_to(compare_to);
+            }
+            let changes = request.await?;
+            let found = changes
+                .change_entries
+                .iter()
+                .find(|change| change_path(&change.git_change.change.item) == Some(file_path));
+            if let Some(change) = found {
+                return Ok(change.change_tracking_id);
+            }
+            match changes.next_skip {
+                Some(next_skip) if next_skip > skip => skip = next_skip,
+                _ => return Ok(None),
+            }
+        }
+    }
+
+    async fn create(self) -> azure_core::Result<GitPullRequestCommentThread> {
+        let mut thread = GitPullRequestCommentThread {
+            comment_thread: CommentThread {
+                comments: vec![Comment {
+                    content: Some(self.content.clone()),
+                    comment_type: Some(comment::CommentType::Text),
+                    parent_comment_id: Some(0),
+                    ..Default::default()
+                }],
+                status: Some(self.status.clone()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        if let Some(file_path) = &self.file_path {
+            let file_path = normalize_path(file_path);
+            if let Some(range) = &self.range {
+                range.validate()?;
+            }
+            let iteration_id = match self.iteration_id {
+                Some(iteration_id) => iteration_id,
+                None => self.latest_iteration().await?,
+            };
+            let compare_to = self.compare_to_iteration_id.unwrap_or(1);
+            if compare_to < 1 || compare_to > iteration_id {
+                return Err(Error::with_message(ErrorKind::Other, || {
+                    format!("cannot compare iteration {iteration_id} to iteration {compare_to}")
+                }));
+            }
+            let change_tracking_id = self
+                .change_tracking_id(&file_path, iteration_id, compare_to)
+                .await?
+                .ok_or_else(|| {
+                    Error::with_message(ErrorKind::Other, || {
+                        format!(
+                            "{file_path} is not changed in iteration {iteration_id} of pull request {}",
+                            self.pull_request_id
+                        )
+                    })
+                })?;
+            thread.comment_thread.thread_context =
+                Some(thread_context(&file_path, self.side, self.range.as_ref()));
+            thread.pull_request_thread_context = Some(GitPullRequestCommentThreadContext {
+                change_tracking_id: Some(change_tracking_id),
+                iteration_context: Some(CommentIterationContext {
+                    first_comparing_iteration: Some(compare_to.into()),
+                    second_comparing_iteration: Some(iteration_id.into()),
+                }),
+                tracking_criteria: None,
+            });
+        } else if self.range.is_some() {
+            return Err(Error::message(
+                ErrorKind::Other,
+                "a thread anchored to lines must also specify a file",
+            ));
+        }
+
+        self.client
+            .pull_request_threads_client()
+            .create(
+                &self.organization,
+                thread,
+                &self.repository_id,
+                self.pull_request_id,
+                &self.project,
+            )
+            .await
+    }
+}
+
+impl std::future::IntoFuture for CreateThreadBuilder {
+    type Output = azure_core::Result<GitPullRequestCommentThread>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<GitPullRequestCommentThread>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.create())
+    }
+}
+
+/// Repository paths in thread contexts are absolute (`/src/main.rs`).
+fn normalize_path(file_path: &str) -> String {
+    let file_path = file_path.replace('\\', "/");
+    if file_path.starts_with('/') {
+        file_path
+    } else {
+        format!("/{file_path}")
+    }
+}
+
+/// The path of a change entry's `item`, which the API models as untyped JSON.
+fn change_path(item: &serde_json::Value) -> Option<&str> {
+    item.get("path").and_then(|path| path.as_str())
+}
+
+fn thread_context(
+    file_path: &str,
+    side: DiffSide,
+    range: Option<&LineRange>,
+) -> CommentThreadContext {
+    let mut context = CommentThreadContext {
+        file_path: Some(file_path.to_string()),
+        ..Default::default()
+    };
+    if let Some(range) = range {
+        let start = Some(CommentPosition {
+            line: Some(range.start_line),
+            offset: Some(range.start_offset),
+        });
+        let end = Some(CommentPosition {
+            line: Some(range.end_line),
+            offset: Some(range.end_offset),
+        });
+        match side {
+            DiffSide::Left => {
+                context.left_file_start = start;
+                context.left_file_end = end;
+            }
+            DiffSide::Right => {
+                context.right_file_start = start;
+                context.right_file_end = end;
+            }
+        }
+    }
+    context
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_thread_context() {
+        let context = thread_context(
+            &normalize_path("src\\main.rs"),
+            DiffSide::Right,
+            Some(&LineRange::lines(10, 12)),
+        );
+        assert_eq!(
+            serde_json::to_value(&context).unwrap(),
+            json!({
+                "filePath": "/src/main.rs",
+                "rightFileStart": { "line": 10, "offset": 1 },
+                "rightFileEnd": { "line": 12, "offset": i32::MAX }
+            })
+        );
+
+        let context = thread_context("/README.md", DiffSide::Left, None);
+        assert_eq!(
+            serde_json::to_value(&context).unwrap(),
+            json!({ "filePath": "/README.md" })
+        );
+    }
+
+    #[test]
+    fn test_line_range_validation() {
+        assert!(LineRange::lines(3, 3).validate().is_ok());
+        assert!(LineRange::lines(0, 3).validate().is_err());
+        assert!(LineRange::lines(5, 3).validate().is_err());
+        let range = LineRange {
+            start_line: 3,
+            start_offset: 10,
+            end_line: 3,
+            end_offset: 4,
+        };
+        assert!(range.validate().is_err());
+    }
+}
@@ -0,0 +1,749 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Pull request lifecycle orchestration.
+//!
+//! Chains the steps usually needed to get a change merged through a pull request:
+//! create the PR, add (required) reviewers, link work items, set auto-complete,
+//! then wait until the PR is completed, reporting what is blocking it in the meantime.
+//!
+//! ```rust
+//!     let workflow = git_client
+//!         .create_pull_request_workflow(&organization, &project, &repository_name, "release/1.2", "main", "Release 1.2")
+//!         .required_reviewer(&release_approver_id)
+//!         .work_item(1234)
+//!         .auto_complete(MergeStrategy::Squash)
+//!         .await?;
+//!     let outcome = workflow
+//!         .wait_until_completed()
+//!         .on_progress(|status| println!("Waiting for: {:?}", status.blocking_reasons))
+//!         .await?;
+//! ```
+
+use crate::git::models::{
+    git_pull_request::{MergeStatus, Status},
+    git_pull_request_completion_options::MergeStrategy,
+    GitPullRequest, GitPullRequestCompletionOptions, GitPullRequestCreateOptions,
+    GitPullRequestUpdateOptions, IdentityId, IdentityRef, IdentityRefWithVote, ResourceRef,
+};
+#[cfg(feature = "policy")]
+use crate::policy::models::{policy_evaluation_record, PolicyEvaluationRecord};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Reviewer vote: approved.
+pub const VOTE_APPROVED: i64 = 10;
+/// Reviewer vote: approved with suggestions.
+pub const VOTE_APPROVED_WITH_SUGGESTIONS: i64 = 5;
+/// Reviewer vote: no vote.
+pub const VOTE_NONE: i64 = 0;
+/// Reviewer vote: waiting for author.
+pub const VOTE_WAITING_FOR_AUTHOR: i64 = -5;
+/// Reviewer vote: rejected.
+pub const VOTE_REJECTED: i64 = -10;
+
+/// A reason why a pull request cannot currently be completed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BlockingReason {
+    /// The pull request is a draft.
+    Draft,
+    /// The merge hasn't been attempted or is still in progress.
+    MergePending,
+    /// The source and target branches have merge conflicts.
+    MergeConflicts,
+    /// The merge failed.
+    MergeFailed { message: Option<String> },
+    /// The merge was rejected by a policy (e.g. file size or path restrictions).
+    MergeRejectedByPolicy { message: Option<String> },
+    /// A blocking policy is still being evaluated (e.g. a build validation is running).
+    PolicyPending { name: String },
+    /// A blocking policy has been rejected (e.g. a build validation failed).
+    PolicyRejected { name: String },
+    /// A required reviewer hasn't approved yet.
+    RequiredReviewerPending { reviewer: String },
+    /// A reviewer voted "waiting for author".
+    WaitingForAuthor { reviewer: String },
+    /// A reviewer rejected the pull request.
+    ReviewerRejected { reviewer: String },
+}
+
+impl BlockingReason {
+    /// Returns `true` if the pull request won't complete without further action
+    /// (new commits, a new vote or a policy re-queue), so waiting is pointless.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            BlockingReason::MergeConflicts
+                | BlockingReason::MergeFailed { .. }
+                | BlockingReason::MergeRejectedByPolicy { .. }
+                | BlockingReason::PolicyRejected { .. }
+                | BlockingReason::WaitingForAuthor { .. }
+                | BlockingReason::ReviewerRejected { .. }
+        )
+    }
+}
+
+/// The current state of a pull request and what is blocking its completion.
+#[derive(Clone, Debug)]
+pub struct PullRequestStatusReport {
+    pub pull_request: GitPullRequest,
+    pub blocking_reasons: Vec<BlockingReason>,
+}
+
+/// The final outcome of [`PullRequestWorkflow::wait_until_completed`].
+#[derive(Clone, Debug)]
+pub enum PullRequestOutcome {
+    /// The pull request was completed (merged).
+    Completed(GitPullRequest),
+    /// The pull request was abandoned.
+    Abandoned(GitPullRequest),
+    /// The pull request is blocked by a terminal [`BlockingReason`].
+    Blocked(PullRequestStatusReport),
+    /// The timeout elapsed before the pull request completed.
+    TimedOut(PullRequestStatusReport),
+}
+
+impl crate::git::Client {
+    /// Creates a pull request workflow builder.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `repository_id`: The name or ID of the repository.
+    /// * `source_branch`: Source branch, with or without the `refs/heads/` prefix.
+    /// * `target_branch`: Target branch, with or without the `refs/heads/` prefix.
+    /// * `title`: Pull request title.
+    pub fn create_pull_request_workflow(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        repository_id: impl Into<String>,
+        source_branch: impl AsRef<str>,
+        target_branch: impl AsRef<str>,
+        title: impl Into<String>,
+    ) -> CreatePullRequestBuilder {
+        let options = GitPullRequestCreateOptions::new(
+            crate::git_commit::branch_ref_name(source_branch.as_ref()),
+            crate::git_commit::branch_ref_name(target_branch.as_ref()),
+            title.into(),
+        );
+        CreatePullRequestBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            repository_id: repository_id.into(),
+            options,
+            required_reviewers: Vec::new(),
+            auto_complete: None,
+        }
+    }
+
+    /// Loads an existing pull request into a workflow.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `pull_request_id`: The ID of the pull request.
+    pub async fn pull_request_workflow(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        pull_request_id: i32,
+    ) -> azure_core::Result<PullRequestWorkflow> {
+        let organization = organization.into();
+        let project = project.into();
+        let pull_request = self
+            .pull_requests_client()
+            .get_pull_request_by_id(&organization, pull_request_id, &project)
+            .await?;
+        Ok(PullRequestWorkflow {
+            #[cfg(feature = "policy")]
+            policy_client: policy_client(self),
+            client: self.clone(),
+            organization,
+            project,
+            pull_request,
+        })
+    }
+}
+
+/// `CreatePullRequestBuilder` provides a mechanism for setting up a new pull request.
+///
+/// Invoke `.await` to create the pull request, add reviewers and set auto-complete,
+/// which returns a [`PullRequestWorkflow`].
+#[derive(Clone)]
+pub struct CreatePullRequestBuilder {
+    client: crate::git::Client,
+    organization: String,
+    project: String,
+    repository_id: String,
+    options: GitPullRequestCreateOptions,
+    required_reviewers: Vec<String>,
+    auto_complete: Option<GitPullRequestCompletionOptions>,
+}
+
+impl CreatePullRequestBuilder {
+    /// The description of the pull request.
+    #[must_use]
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.options.description = Some(description.into());
+        self
+    }
+
+    /// Create the pull request as a draft.
+    #[must_use]
+    pub fn draft(mut self, is_draft: bool) -> Self {
+        self.options.is_draft = Some(is_draft);
+        self
+    }
+
+    /// Adds an optional reviewer, by identity id.
+    #[must_use]
+    pub fn reviewer(mut self, reviewer_id: impl Into<String>) -> Self {
+        self.options
+            .reviewers
+            .push(IdentityId::new(reviewer_id.into()));
+        self
+    }
+
+    /// Adds a required reviewer, by identity id.
+    #[must_use]
+    pub fn required_reviewer(mut self, reviewer_id: impl Into<String>) -> Self {
+        self.required_reviewers.push(reviewer_id.into());
+        self
+    }
+
+    /// Links a work item to the pull request.
+    #[must_use]
+    pub fn work_item(mut self, work_item_id: i32) -> Self {
+        self.options.work_item_refs.push(ResourceRef {
+            id: Some(work_item_id.to_string()),
+            url: None,
+        });
+        self
+    }
+
+    /// Sets auto-complete with the given merge strategy, deleting the source branch
+    /// and transitioning linked work items on completion.
+    #[must_use]
+    pub fn auto_complete(self, merge_strategy: MergeStrategy) -> Self {
+        self.auto_complete_with(GitPullRequestCompletionOptions {
+            merge_strategy: Some(merge_strategy),
+            delete_source_branch: Some(true),
+            transition_work_items: Some(true),
+            ..Default::default()
+        })
+    }
+
+    /// Sets auto-complete with the given completion options.
+    #[must_use]
+    pub fn auto_complete_with(
+        mut self,
+        completion_options: GitPullRequestCompletionOptions,
+    ) -> Self {
+        self.auto_complete = Some(completion_options);
+        self
+    }
+
+    async fn create(self) -> azure_core::Result<PullRequestWorkflow> {
+        let pull_request = self
+            .client
+            .pull_requests_client()
+            .create(
+                &self.organization,
+                &self.repository_id,
+                &self.project,
+                self.options,
+            )
+            .await?;
+        let mut workflow = PullRequestWorkflow {
+            #[cfg(feature = "policy")]
+            policy_client: policy_client(&self.client),
+            client: self.client,
+            organization: self.organization,
+            project: self.project,
+            pull_request,
+        };
+        for reviewer_id in self.required_reviewers {
+            workflow.add_reviewer(reviewer_id, true).await?;
+        }
+        if let Some(completion_options) = self.auto_complete {
+            workflow.set_auto_complete(completion_options).await?;
+        }
+        Ok(workflow)
+    }
+}
+
+impl std::future::IntoFuture for CreatePullRequestBuilder {
+    type Output = azure_core::Result<PullRequestWorkflow>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<PullRequestWorkflow>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.create())
+    }
+}
+
+/// A pull request being driven to completion.
+///
+/// Blocking policies are only reported when the `policy` feature is enabled.
+#[derive(Clone)]
+pub struct PullRequestWorkflow {
+    client: crate::git::Client,
+    #[cfg(feature = "policy")]
+    policy_client: crate::policy::Client,
+    organization: String,
+    project: String,
+    pull_request: GitPullRequest,
+}
+
+/// Creates a policy client with the git client's endpoint, credential and scopes.
+#[cfg(feature = "policy")]
+fn policy_client(client: &crate::git::Client) -> crate::policy::Client {
+    crate::policy::Client::new(
+        client.endpoint().clone(),
+        client.token_credential().clone(),
+        client.scopes().into_iter().map(str::to_owned).collect(),
+        azure_core::ClientOptions::default(),
+    )
+}
+
+impl PullRequestWorkflow {
+    /// Sets the client used to fetch policy evaluations, e.g. to use custom retry or
+    /// transport options. Defaults to a client with the git client's endpoint and credential.
+    #[cfg(feature = "policy")]
+    #[must_use]
+    pub fn with_policy_client(mut self, policy_client: crate::policy::Client) -> Self {
+        self.policy_client = policy_client;
+        self
+    }
+
+    /// The pull request, as of the last call that fetched or updated it.
+    pub fn pull_request(&self) -> &GitPullRequest {
+        &self.pull_request
+    }
+
+    fn repository_id(&self) -> &str {
+        &self.pull_request.repository.id
+    }
+
+    fn pull_request_id(&self) -> i32 {
+        self.pull_request.pull_request_id
+    }
+
+    /// Re-fetches the pull request.
+    pub async fn refresh(&mut self) -> azure_core::Result<&GitPullRequest> {
+        self.pull_request = self
+            .client
+            .pull_requests_client()
+            .get_pull_request(
+                &self.organization,
+                self.repository_id(),
+                self.pull_request_id(),
+                &self.project,
+            )
+            .await?;
+        Ok(&self.pull_request)
+    }
+
+    /// Adds a reviewer, by identity id.
+    pub async fn add_reviewer(
+        &mut self,
+        reviewer_id: impl Into<String>,
+        is_required: bool,
+    ) -> azure_core::Result<IdentityRefWithVote> {
+        let reviewer_id = reviewer_id.into();
+        let reviewer = IdentityRefWithVote {
+            is_required: Some(is_required),
+            vote: Some(VOTE_NONE),
+            ..IdentityRefWithVote::new(IdentityRef::new(reviewer_id.clone()))
+        };
+        let reviewer = self
+            .client
+            .pull_request_reviewers_client()
+            .create_pull_request_reviewer(
+                &self.organization,
+                reviewer,
+                self.repository_id(),
+                self.pull_request_id(),
+                reviewer_id,
+                &self.project,
+            )
+            .await?;
+        self.pull_request
+            .reviewers
+            .retain(|r| r.identity_ref.id != reviewer.identity_ref.id);
+        self.pull_request.reviewers.push(reviewer.clone());
+        Ok(reviewer)
+    }
+
+    /// Enables auto-complete, on behalf of the pull request's creator.
+    pub async fn set_auto_complete(
+        &mut self,
+        completion_options: GitPullRequestCompletionOptions,
+    ) -> azure_core::Result<&GitPullRequest> {
+        let update = GitPullRequestUpdateOptions {
+            auto_complete_set_by: Some(IdentityRef::new(self.pull_request.created_by.id.clone())),
+            completion_options: Some(completion_options),
+            ..Default::default()
+        };
+        self.update(update).await
+    }
+
+    /// Disables auto-complete.
+    pub async fn cancel_auto_complete(&mut self) -> azure_core::Result<&GitPullRequest> {
+        // Setting `autoCompleteSetBy` to the empty GUID clears auto-complete.
+        let update = GitPullRequestUpdateOptions {
+            auto_complete_set_by: Some(IdentityRef::new(
+                "00000000-0000-0000-0000-000000000000".to_string(),
+            )),
+            ..Default::default()
+        };
+        self.update(update).await
+    }
+
+    async fn update(
+        &mut self,
+        update: GitPullRequestUpdateOptions,
+    ) -> azure_core::Result<&GitPullRequest> {
+        self.pull_request = self
+            .client
+            .pull_requests_client()
+            .update(
+                &self.organization,
+                self.repository_id(),
+                &self.project,
+                self.pull_request_id(),
+                update,
+            )
+            .await?;
+        Ok(&self.pull_request)
+    }
+
+    /// Re-fetches the pull request and reports what is blocking its completion.
+    pub async fn status(&mut self) -> azure_core::Result<PullRequestStatusReport> {
+        self.refresh().await?;
+        #[cfg(feature = "policy")]
+        let policy_reasons = policy_blocking_reasons(&self.policy_evaluations().await?);
+        #[cfg(not(feature = "policy"))]
+        let policy_reasons = Vec::new();
+        Ok(PullRequestStatusReport {
+            blocking_reasons: blocking_reasons(&self.pull_request, policy_reasons),
+            pull_request: self.pull_request.clone(),
+        })
+    }
+
+    /// Policy evaluations for the pull request.
+    #[cfg(feature = "policy")]
+    async fn policy_evaluations(&self) -> azure_core::Result<Vec<PolicyEvaluationRecord>> {
+        let project_id = self
+            .pull_request
+            .repository
+            .project
+            .id
+            .as_deref()
+            .unwrap_or(&self.project);
+        let artifact_id = format!(
+            "vstfs:///CodeReview/CodeReviewId/{}/{}",
+            project_id,
+            self.pull_request_id()
+        );
+        Ok(self
+            .policy_client
+            .evaluations_client()
+            .list(&self.organization, &self.project, artifact_id)
+            .await?
+            .value)
+    }
+
+    /// Waits until the pull request is completed or abandoned.
+    pub fn wait_until_completed(self) -> WaitBuilder {
+        WaitBuilder {
+            workflow: self,
+            poll_interval: Duration::from_secs(30),
+            timeout: None,
+            stop_when_blocked: true,
+            on_progress: None,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type ProgressCallback = Box<dyn FnMut(&PullRequestStatusReport) + Send>;
+#[cfg(target_arch = "wasm32")]
+type ProgressCallback = Box<dyn FnMut(&PullRequestStatusReport)>;
+
+/// `WaitBuilder` provides a mechanism for setting optional wait parameters.
+///
+/// Invoke `.await` to poll the pull request until it completes, which returns a [`PullRequestOutcome`].
+pub struct WaitBuilder {
+    workflow: PullRequestWorkflow,
+    poll_interval: Duration,
+    timeout: Option<Duration>,
+    stop_when_blocked: bool,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl WaitBuilder {
+    /// Interval between status checks (default 30 seconds).
+    #[must_use]
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Maximum time to wait.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Whether to stop waiting as soon as the pull request is blocked by a terminal reason
+    /// (see [`BlockingReason::is_terminal`]). Defaults to `true`.
+    #[must_use]
+    pub fn stop_when_blocked(mut self, stop_when_blocked: bool) -> Self {
+        self.stop_when_blocked = stop_when_blocked;
+        self
+    }
+
+    /// Callback invoked with the status of the pull request after each check.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn on_progress(
+        mut self,
+        on_progress: impl FnMut(&PullRequestStatusReport) + Send + 'static,
+    ) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    /// Callback invoked with the status of the pull request after each check.
+    #[cfg(target_arch = "wasm32")]
+    #[must_use]
+    pub fn on_progress(
+        mut self,
+        on_progress: impl FnMut(&PullRequestStatusReport) + 'static,
+    ) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    async fn wait(mut self) -> azure_core::Result<PullRequestOutcome> {
+        let mut waited = Duration::ZERO;
+        loop {
+            let report = self.workflow.status().await?;
+            match report.pull_request.status {
+                Status::Completed => return Ok(PullRequestOutcome::Completed(report.pull_request)),
+                Status::Abandoned => return Ok(PullRequestOutcome::Abandoned(report.pull_request)),
+                _ => {}
+            }
+            if let Some(on_progress) = self.on_progress.as_mut() {
+                on_progress(&report);
+            }
+            if self.stop_when_blocked
+                && report
+                    .blocking_reasons
+                    .iter()
+                    .any(BlockingReason::is_terminal)
+            {
+                return Ok(PullRequestOutcome::Blocked(report));
+            }
+            if self.timeout.map_or(false, |timeout| waited >= timeout) {
+                return Ok(PullRequestOutcome::TimedOut(report));
+            }
+            azure_core::sleep::sleep(self.poll_interval).await;
+            waited += self.poll_interval;
+        }
+    }
+}
+
+impl std::future::IntoFuture for WaitBuilder {
+    type Output = azure_core::Result<PullRequestOutcome>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<PullRequestOutcome>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.wait())
+    }
+}
+
+/// The name of a policy, for reporting.
+#[cfg(feature = "policy")]
+fn policy_name(evaluation: &PolicyEvaluationRecord) -> String {
+    let configuration = evaluation.configuration.as_ref();
+    // Build validation policies carry a more useful display name in their settings.
+    configuration
+        .and_then(|c| c.settings.as_ref())
+        .and_then(|settings| settings.get("displayName"))
+        .and_then(|name| name.as_str())
+        .or_else(|| {
+            configuration
+                .and_then(|c| {
+                    c.versioned_policy_configuration_ref
+                        .policy_configuration_ref
+                        .type_
+                        .as_ref()
+                })
+                .map(|t| t.display_name.as_str())
+        })
+        .unwrap_or("<unknown policy>")
+        .to_string()
+}
+
+/// Blocking policies that are pending or rejected.
+#[cfg(feature = "policy")]
+fn policy_blocking_reasons(evaluations: &[PolicyEvaluationRecord]) -> Vec<BlockingReason> {
+    use policy_evaluation_record::Status;
+
+    evaluations
+        .iter()
+        .filter(|evaluation| {
+            evaluation.configuration.as_ref().map_or(false, |c| {
+                c.is_blocking.unwrap_or(false) && c.is_enabled.unwrap_or(true)
+            })
+        })
+        .filter_map(|evaluation| match evaluation.status {
+            Some(Status::Queued) | Some(Status::Running) => Some(BlockingReason::PolicyPending {
+                name: policy_name(evaluation),
+            }),
+            Some(Status::Rejected) | Some(Status::Broken) => Some(BlockingReason::PolicyRejected {
+                name: policy_name(evaluation),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+fn blocking_reasons(
+    pull_request: &GitPullRequest,
+    policy_reasons: Vec<BlockingReason>,
+) -> Vec<BlockingReason> {
+    let mut reasons = Vec::new();
+    if pull_request.is_draft {
+        reasons.push(BlockingReason::Draft);
+    }
+    match pull_request.merge_status {
+        Some(MergeStatus::Conflicts) => reasons.push(BlockingReason::MergeConflicts),
+        Some(MergeStatus::Failure) => reasons.push(BlockingReason::MergeFailed {
+            message: pull_request.merge_failure_message.clone(),
+        }),
+        Some(MergeStatus::RejectedByPolicy) => {
+            reasons.push(BlockingReason::MergeRejectedByPolicy {
+                message: pull_request.merge_failure_message.clone(),
+            })
+        }
+        Some(MergeStatus::Queued) | Some(MergeStatus::NotSet) | None => {
+            reasons.push(BlockingReason::MergePending)
+        }
+        Some(MergeStatus::Succeeded) => {}
+    }
+    reasons.extend(policy_reasons);
+    for reviewer in &pull_request.reviewers {
+        let name = reviewer
+            .identity_ref
+            .graph_subject_base
+            .display_name
+            .clone()
+            .unwrap_or_else(|| reviewer.identity_ref.id.clone());
+        match reviewer.vote.unwrap_or(VOTE_NONE) {
+            VOTE_REJECTED => reasons.push(BlockingReason::ReviewerRejected { reviewer: name }),
+            VOTE_WAITING_FOR_AUTHOR => {
+                reasons.push(BlockingReason::WaitingForAuthor { reviewer: name })
+            }
+            VOTE_NONE if reviewer.is_required.unwrap_or(false) => {
+                reasons.push(BlockingReason::RequiredReviewerPending { reviewer: name })
+            }
+            _ => {}
+        }
+    }
+    reasons
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn pull_request(merge_status: &str, reviewers: serde_json::Value) -> GitPullRequest {
+        serde_json::from_value(json!({
+            "createdBy": { "id": "creator" },
+            "creationDate": "2024-01-01T00:00:00Z",
+            "isDraft": false,
+            "mergeStatus": merge_status,
+            "pullRequestId": 1,
+            "repository": {
+                "id": "repo",
+                "name": "repo",
+                "project": { "name": "project", "visibility": "private" },
+                "url": "https://dev.azure.com/org/project/_apis/git/repositories/repo"
+            },
+            "reviewers": reviewers,
+            "sourceRefName": "refs/heads/feature",
+            "status": "active",
+            "targetRefName": "refs/heads/main",
+            "url": "https://dev.azure.com/org/project/_apis/git/pullRequests/1"
+        }))
+        .unwrap()
+    }
+
+    #[cfg(feature = "policy")]
+    #[test]
+    fn test_blocking_reasons() {
+        let pull_request = pull_request(
+            "succeeded",
+            json!([
+                { "id": "a", "displayName": "Alice", "vote": 0, "isRequired": true },
+                { "id": "b", "displayName": "Bob", "vote": 10 },
+                { "id": "c", "displayName": "Carol", "vote": -5 }
+            ]),
+        );
+        let policy_type = |name: &str| json!({ "displayName": name, "id": name, "url": name });
+        let evaluations: Vec<PolicyEvaluationRecord> = serde_json::from_value(json!([
+            {
+                "status": "running",
+                "configuration": {
+                    "isBlocking": true,
+                    "isEnabled": true,
+                    "type": policy_type("Build"),
+                    "settings": { "displayName": "CI build" }
+                }
+            },
+            {
+                "status": "rejected",
+                "configuration": { "isBlocking": false, "type": policy_type("Comment requirements") }
+            },
+            {
+                "status": "approved",
+                "configuration": { "isBlocking": true, "type": policy_type("Minimum number of reviewers") }
+            }
+        ]))
+        .unwrap();
+
+        let reasons = blocking_reasons(&pull_request, policy_blocking_reasons(&evaluations));
+        assert_eq!(
+            reasons,
+            vec![
+                BlockingReason::PolicyPending {
+                    name: "CI build".to_string()
+                },
+                BlockingReason::RequiredReviewerPending {
+                    reviewer: "Alice".to_string()
+                },
+                BlockingReason::WaitingForAuthor {
+                    reviewer: "Carol".to_string()
+                },
+            ]
+        );
+        assert!(reasons.iter().any(BlockingReason::is_terminal));
+    }
+
+    #[test]
+    fn test_merge_conflicts_are_terminal() {
+        let reasons = blocking_reasons(&pull_request("conflicts", json!([])), Vec::new());
+        assert_eq!(reasons, vec![BlockingReason::MergeConflicts]);
+        assert!(reasons[0].is_terminal());
+    }
+}
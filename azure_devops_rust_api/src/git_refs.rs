@@ -0,0 +1,690 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Branch and tag management with compare-and-swap semantics.
+//!
+//! Every update states the object id the ref is expected to point at, so a ref that
+//! has moved since it was read is never overwritten. Rejected updates are reported as
+//! [`RefUpdateError`] (or [`RefBatchError`] for batches), which can be recovered from the
+//! returned `azure_core::Error` with `downcast_ref()`.
+//!
+//! ```rust
+//!     let refs = git_client.refs(&organization, &project, &repository_name);
+//!     refs.create_branch("release/1.2", &commit_id).await?;
+//!     refs.batch()
+//!         .move_branch("main", &old_main, &new_main)
+//!         .create_tag("v1.2.0", &new_main)
+//!         .await?;
+//!     if let Err(e) = refs.delete_branch("feature", &expected).await {
+//!         if let Some(RefUpdateError::Stale { .. }) = e.downcast_ref::<RefUpdateError>() {
+//!             // Someone pushed to the branch in the meantime.
+//!         }
+//!     }
+//! ```
+
+use crate::git::models::{
+    git_ref_update_result::UpdateStatus, GitRef, GitRefUpdate, GitRefUpdateResult,
+};
+use crate::git_commit::{branch_ref_name, ZERO_OBJECT_ID};
+use azure_core::error::{Error, ErrorKind};
+use std::fmt;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Returns the full ref name of a tag, adding the `refs/tags/` prefix if necessary.
+pub fn tag_ref_name(tag: &str) -> String {
+    if tag.starts_with("refs/") {
+        tag.to_string()
+    } else {
+        format!("refs/tags/{tag}")
+    }
+}
+
+/// A single compare-and-swap ref update.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefUpdate {
+    /// Full ref name, e.g. `refs/heads/main`.
+    pub name: String,
+    /// The object id the ref must currently point at ([`ZERO_OBJECT_ID`] if it must not exist).
+    pub expected_object_id: String,
+    /// The object id to point the ref at ([`ZERO_OBJECT_ID`] to delete it).
+    pub new_object_id: String,
+}
+
+impl RefUpdate {
+    /// Creates `name`, which must not already exist.
+    pub fn create(name: impl Into<String>, new_object_id: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            expected_object_id: ZERO_OBJECT_ID.to_string(),
+            new_object_id: new_object_id.into(),
+        }
+    }
+
+    /// Deletes `name`, which must currently point at `expected_object_id`.
+    pub fn delete(name: impl Into<String>, expected_object_id: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            expected_object_id: expected_object_id.into(),
+            new_object_id: ZERO_OBJECT_ID.to_string(),
+        }
+    }
+
+    /// Moves `name` from `expected_object_id` to `new_object_id`.
+    pub fn move_to(
+        name: impl Into<String>,
+        expected_object_id: impl Into<String>,
+        new_object_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            expected_object_id: expected_object_id.into(),
+            new_object_id: new_object_id.into(),
+        }
+    }
+
+    /// The update that undoes this one.
+    fn reverse(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            expected_object_id: self.new_object_id.clone(),
+            new_object_id: self.expected_object_id.clone(),
+        }
+    }
+
+    fn to_git_ref_update(&self) -> GitRefUpdate {
+        GitRefUpdate {
+            name: Some(self.name.clone()),
+            old_object_id: Some(self.expected_object_id.clone()),
+            new_object_id: Some(self.new_object_id.clone()),
+            ..Default::default()
+        }
+    }
+}
+
+/// A rejected ref update.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RefUpdateError {
+    /// The ref didn't point at the expected object id (or already existed, for creates).
+    Stale { name: String, expected: String },
+    /// The update was rejected by a branch policy.
+    RejectedByPolicy {
+        name: String,
+        policy: Option<String>,
+        message: Option<String>,
+    },
+    /// The update was rejected by a server plugin.
+    RejectedByPlugin {
+        name: String,
+        plugin: Option<String>,
+        message: Option<String>,
+    },
+    /// The caller lacks a permission (see `status` for which one).
+    PermissionRequired { name: String, status: UpdateStatus },
+    /// The ref is locked.
+    Locked { name: String },
+    /// The ref name is invalid, or conflicts with an existing ref (e.g. `a` vs `a/b`).
+    InvalidName { name: String, status: UpdateStatus },
+    /// The new object id doesn't resolve to a commit.
+    UnresolvableToCommit { name: String },
+    /// Any other failure, including updates the service didn't process.
+    Failed {
+        name: String,
+        status: Option<UpdateStatus>,
+        message: Option<String>,
+    },
+}
+
+impl RefUpdateError {
+    /// The name of the ref that wasn't updated.
+    pub fn name(&self) -> &str {
+        match self {
+            RefUpdateError::Stale { name, .. }
+            | RefUpdateError::RejectedByPolicy { name, .. }
+            | RefUpdateError::RejectedByPlugin { name, .. }
+            | RefUpdateError::PermissionRequired { name, .. }
+            | RefUpdateError::Locked { name }
+            | RefUpdateError::InvalidName { name, .. }
+            | RefUpdateError::UnresolvableToCommit { name }
+            | RefUpdateError::Failed { name, .. } => name,
+        }
+    }
+
+    /// Interprets a ref update result, returning `None` if it succeeded.
+    fn from_result(update: &RefUpdate, result: &GitRefUpdateResult) -> Option<Self> {
+        let name = update.name.clone();
+        let message = result.custom_message.clone();
+        let error = match result.update_status.clone() {
+            Some(UpdateStatus::Succeeded)
+            | Some(UpdateStatus::SucceededNonExistentRef)
+            | Some(UpdateStatus::SucceededCorruptRef) => return None,
+            None if result.success == Some(true) => return None,
+            Some(UpdateStatus::StaleOldObjectId) | Some(UpdateStatus::ForcePushRequired) => {
+                RefUpdateError::Stale {
+                    name,
+                    expected: update.expected_object_id.clone(),
+                }
+            }
+            Some(UpdateStatus::RejectedByPolicy) => RefUpdateError::RejectedByPolicy {
+                name,
+                policy: result.rejected_by.clone(),
+                message,
+            },
+            Some(UpdateStatus::RejectedByPlugin) => RefUpdateError::RejectedByPlugin {
+                name,
+                plugin: result.rejected_by.clone(),
+                message,
+            },
+            Some(
+                status @ (UpdateStatus::WritePermissionRequired
+                | UpdateStatus::ManageNotePermissionRequired
+                | UpdateStatus::CreateBranchPermissionRequired
+                | UpdateStatus::CreateTagPermissionRequired),
+            ) => RefUpdateError::PermissionRequired { name, status },
+            Some(UpdateStatus::Locked) => RefUpdateError::Locked { name },
+            Some(status @ (UpdateStatus::InvalidRefName | UpdateStatus::RefNameConflict)) => {
+                RefUpdateError::InvalidName { name, status }
+            }
+            Some(UpdateStatus::UnresolvableToCommit) => {
+                RefUpdateError::UnresolvableToCommit { name }
+            }
+            status => RefUpdateError::Failed {
+                name,
+                status,
+                message,
+            },
+        };
+        Some(error)
+    }
+}
+
+impl fmt::Display for RefUpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefUpdateError::Stale { name, expected } => {
+                write!(f, "{name} does not point at the expected object {expected}")
+            }
+            RefUpdateError::RejectedByPolicy {
+                name,
+                policy,
+                message,
+            } => {
+                write!(f, "update of {name} was rejected by policy")?;
+                if let Some(policy) = policy {
+                    write!(f, " {policy}")?;
+                }
+                if let Some(message) = message {
+                    write!(f, ": {message}")?;
+                }
+                Ok(())
+            }
+            RefUpdateError::RejectedByPlugin {
+                name,
+                plugin,
+                message,
+            } => {
+                write!(f, "update of {name} was rejected by plugin")?;
+                if let Some(plugin) = plugin {
+                    write!(f, " {plugin}")?;
+                }
+                if let Some(message) = message {
+                    write!(f, ": {message}")?;
+                }
+                Ok(())
+            }
+            RefUpdateError::PermissionRequired { name, status } => {
+                write!(f, "update of {name} failed: {status:?}")
+            }
+            RefUpdateError::Locked { name } => write!(f, "{name} is locked"),
+            RefUpdateError::InvalidName { name, status } => {
+                write!(f, "invalid ref name {name}: {status:?}")
+            }
+            RefUpdateError::UnresolvableToCommit { name } => {
+                write!(f, "new object id for {name} does not resolve to a commit")
+            }
+            RefUpdateError::Failed {
+                name,
+                status,
+                message,
+            } => {
+                write!(f, "update of {name} failed")?;
+                if let Some(status) = status {
+                    write!(f, ": {status:?}")?;
+                }
+                if let Some(message) = message {
+                    write!(f, ": {message}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for RefUpdateError {}
+
+/// An all-or-nothing batch that failed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RefBatchError {
+    /// The updates that were rejected.
+    pub failures: Vec<RefUpdateError>,
+    /// Refs that were updated and then restored to their original object ids.
+    pub rolled_back: Vec<String>,
+    /// Rollbacks that were themselves rejected; these refs are left updated.
+    pub rollback_failures: Vec<RefUpdateError>,
+}
+
+impl fmt::Display for RefBatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ref update batch failed")?;
+        for failure in &self.failures {
+            write!(f, "; {failure}")?;
+        }
+        if !self.rollback_failures.is_empty() {
+            write!(f, "; rollback failed")?;
+            for failure in &self.rollback_failures {
+                write!(f, "; {failure}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RefBatchError {}
+
+impl crate::git::Client {
+    /// Returns a handle for managing the branches and tags of a repository.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `repository_id`: The name or ID of the repository.
+    pub fn refs(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        repository_id: impl Into<String>,
+    ) -> RefManager {
+        RefManager {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            repository_id: repository_id.into(),
+        }
+    }
+}
+
+/// Branch and tag management for a repository, created by [`crate::git::Client::refs`].
+#[derive(Clone)]
+pub struct RefManager {
+    client: crate::git::Client,
+    organization: String,
+    project: String,
+    repository_id: String,
+}
+
+impl RefManager {
+    /// Returns the ref with the full name `name` (e.g. `refs/heads/main`), if it exists.
+    pub async fn get(&self, name: &str) -> azure_core::Result<Option<GitRef>> {
+        let refs = self
+            .client
+            .refs_client()
+            .list(&self.organization, &self.repository_id, &self.project)
+            .filter(name.trim_start_matches("refs/"))
+            .await?
+            .value;
+        Ok(refs.into_iter().find(|git_ref| git_ref.name == name))
+    }
+
+    /// Returns the object id `branch` points at, if it exists.
+    pub async fn branch_head(&self, branch: &str) -> azure_core::Result<Option<String>> {
+        Ok(self
+            .get(&branch_ref_name(branch))
+            .await?
+            .map(|git_ref| git_ref.object_id))
+    }
+
+    /// Creates `branch` at `object_id`. Fails with [`RefUpdateError::Stale`] if it already exists.
+    pub async fn create_branch(
+        &self,
+        branch: &str,
+        object_id: &str,
+    ) -> azure_core::Result<GitRefUpdateResult> {
+        self.update(RefUpdate::create(branch_ref_name(branch), object_id))
+            .await
+    }
+
+    /// Deletes `branch`, which must point at `expected_object_id`.
+    pub async fn delete_branch(
+        &self,
+        branch: &str,
+        expected_object_id: &str,
+    ) -> azure_core::Result<GitRefUpdateResult> {
+        self.update(RefUpdate::delete(
+            branch_ref_name(branch),
+            expected_object_id,
+        ))
+        .await
+    }
+
+    /// Moves `branch` from `expected_object_id` to `new_object_id`.
+    ///
+    /// Unlike a push, this can move the branch to any commit, including non-descendants.
+    pub async fn move_branch(
+        &self,
+        branch: &str,
+        expected_object_id: &str,
+        new_object_id: &str,
+    ) -> azure_core::Result<GitRefUpdateResult> {
+        self.update(RefUpdate::move_to(
+            branch_ref_name(branch),
+            expected_object_id,
+            new_object_id,
+        ))
+        .await
+    }
+
+    /// Creates a lightweight `tag` at `object_id`. Fails with [`RefUpdateError::Stale`] if it already exists.
+    pub async fn create_tag(
+        &self,
+        tag: &str,
+        object_id: &str,
+    ) -> azure_core::Result<GitRefUpdateResult> {
+        self.update(RefUpdate::create(tag_ref_name(tag), object_id))
+            .await
+    }
+
+    /// Deletes `tag`, which must point at `expected_object_id`.
+    pub async fn delete_tag(
+        &self,
+        tag: &str,
+        expected_object_id: &str,
+    ) -> azure_core::Result<GitRefUpdateResult> {
+        self.update(RefUpdate::delete(tag_ref_name(tag), expected_object_id))
+            .await
+    }
+
+    /// Moves `tag` from `expected_object_id` to `new_object_id`.
+    pub async fn move_tag(
+        &self,
+        tag: &str,
+        expected_object_id: &str,
+        new_object_id: &str,
+    ) -> azure_core::Result<GitRefUpdateResult> {
+        self.update(RefUpdate::move_to(
+            tag_ref_name(tag),
+            expected_object_id,
+            new_object_id,
+        ))
+        .await
+    }
+
+    /// Applies a single ref update.
+    ///
+    /// A rejected update fails with a [`RefUpdateError`].
+    pub async fn update(&self, update: RefUpdate) -> azure_core::Result<GitRefUpdateResult> {
+        let mut results = self.send(std::slice::from_ref(&update)).await?;
+        let result = results.pop().ok_or_else(|| {
+            Error::message(ErrorKind::DataConversion, "no ref update result returned")
+        })?;
+        match RefUpdateError::from_result(&update, &result) {
+            Some(error) => Err(Error::new(ErrorKind::Other, error)),
+            None => Ok(result),
+        }
+    }
+
+    /// Locks `branch`, preventing updates by anyone but the locker.
+    pub async fn lock_branch(&self, branch: &str) -> azure_core::Result<GitRef> {
+        self.set_locked(branch, true).await
+    }
+
+    /// Unlocks `branch`.
+    pub async fn unlock_branch(&self, branch: &str) -> azure_core::Result<GitRef> {
+        self.set_locked(branch, false).await
+    }
+
+    async fn set_locked(&self, branch: &str, is_locked: bool) -> azure_core::Result<GitRef> {
+        let name = branch_ref_name(branch);
+        let update = GitRefUpdate {
+            is_locked: Some(is_locked),
+            ..Default::default()
+        };
+        self.client
+            .refs_client()
+            .update_ref(
+                &self.organization,
+                update,
+                &self.repository_id,
+                name.trim_start_matches("refs/"),
+                &self.project,
+            )
+            .await
+    }
+
+    /// Creates a batch of ref updates, applied all-or-nothing by default.
+    pub fn batch(&self) -> RefBatchBuilder {
+        RefBatchBuilder {
+            manager: self.clone(),
+            updates: Vec::new(),
+            atomic: true,
+        }
+    }
+
+    async fn send(&self, updates: &[RefUpdate]) -> azure_core::Result<Vec<GitRefUpdateResult>> {
+        let body = updates.iter().map(RefUpdate::to_git_ref_update).collect();
+        let results = self
+            .client
+            .refs_client()
+            .update_refs(&self.organization, body, &self.repository_id, &self.project)
+            .await?
+            .value;
+        if results.len() != updates.len() {
+            return Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!(
+                    "expected {} ref update results, got {}",
+                    updates.len(),
+                    results.len()
+                )
+            }));
+        }
+        Ok(results)
+    }
+}
+
+/// `RefBatchBuilder` collects ref updates to apply together.
+///
+/// Invoke `.await` to apply the updates, which returns the per-ref results. In atomic mode
+/// (the default) a batch with any rejected update is rolled back and fails with a [`RefBatchError`].
+#[derive(Clone)]
+pub struct RefBatchBuilder {
+    manager: RefManager,
+    updates: Vec<RefUpdate>,
+    atomic: bool,
+}
+
+impl RefBatchBuilder {
+    /// Adds an update.
+    #[must_use]
+    pub fn update(mut self, update: RefUpdate) -> Self {
+        self.updates.push(update);
+        self
+    }
+
+    /// Creates `branch` at `object_id`.
+    #[must_use]
+    pub fn create_branch(self, branch: &str, object_id: impl Into<String>) -> Self {
+        self.update(RefUpdate::create(branch_ref_name(branch), object_id))
+    }
+
+    /// Deletes `branch`, which must point at `expected_object_id`.
+    #[must_use]
+    pub fn delete_branch(self, branch: &str, expected_object_id: impl Into<String>) -> Self {
+        self.update(RefUpdate::delete(
+            branch_ref_name(branch),
+            expected_object_id,
+        ))
+    }
+
+    /// Moves `branch` from `expected_object_id` to `new_object_id`.
+    #[must_use]
+    pub fn move_branch(
+        self,
+        branch: &str,
+        expected_object_id: impl Into<String>,
+        new_object_id: impl Into<String>,
+    ) -> Self {
+        self.update(RefUpdate::move_to(
+            branch_ref_name(branch),
+            expected_object_id,
+            new_object_id,
+        ))
+    }
+
+    /// Creates a lightweight `tag` at `object_id`.
+    #[must_use]
+    pub fn create_tag(self, tag: &str, object_id: impl Into<String>) -> Self {
+        self.update(RefUpdate::create(tag_ref_name(tag), object_id))
+    }
+
+    /// Deletes `tag`, which must point at `expected_object_id`.
+    #[must_use]
+    pub fn delete_tag(self, tag: &str, expected_object_id: impl Into<String>) -> Self {
+        self.update(RefUpdate::delete(tag_ref_name(tag), expected_object_id))
+    }
+
+    /// Whether to roll back successful updates if any update in the batch is rejected
+    /// (default `true`). When `false`, per-ref failures are left to the caller to inspect.
+    #[must_use]
+    pub fn atomic(mut self, atomic: bool) -> Self {
+        self.atomic = atomic;
+        self
+    }
+
+    async fn apply(self) -> azure_core::Result<Vec<GitRefUpdateResult>> {
+        if self.updates.is_empty() {
+            return Ok(Vec::new());
+        }
+        let results = self.manager.send(&self.updates).await?;
+        let failures: Vec<RefUpdateError> = self
+            .updates
+            .iter()
+            .zip(&results)
+            .filter_map(|(update, result)| RefUpdateError::from_result(update, result))
+            .collect();
+        if failures.is_empty() || !self.atomic {
+            return Ok(results);
+        }
+
+        // The service applies each update independently, so undo the ones that succeeded.
+        let rollback = rollback_updates(&self.updates, &results);
+        let mut batch_error = RefBatchError {
+            failures,
+            rolled_back: Vec::new(),
+            rollback_failures: Vec::new(),
+        };
+        if !rollback.is_empty() {
+            match self.manager.send(&rollback).await {
+                Ok(rollback_results) => {
+                    for (update, result) in rollback.iter().zip(&rollback_results) {
+                        match RefUpdateError::from_result(update, result) {
+                            Some(error) => batch_error.rollback_failures.push(error),
+                            None => batch_error.rolled_back.push(update.name.clone()),
+                        }
+                    }
+                }
+                Err(e) => {
+                    return Err(Error::full(
+                        ErrorKind::Other,
+                        batch_error,
+                        format!("ref update batch failed and could not be rolled back: {e}"),
+                    ))
+                }
+            }
+        }
+        Err(Error::new(ErrorKind::Other, batch_error))
+    }
+}
+
+impl std::future::IntoFuture for RefBatchBuilder {
+    type Output = azure_core::Result<Vec<GitRefUpdateResult>>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<Vec<GitRefUpdateResult>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.apply())
+    }
+}
+
+/// The updates that restore the refs that were successfully updated.
+fn rollback_updates(updates: &[RefUpdate], results: &[GitRefUpdateResult]) -> Vec<RefUpdate> {
+    updates
+        .iter()
+        .zip(results)
+        .filter(|(update, result)| RefUpdateError::from_result(update, result).is_none())
+        .map(|(update, _)| update.reverse())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn result(status: &str) -> GitRefUpdateResult {
+        serde_json::from_value(json!({
+            "updateStatus": status,
+            "success": status == "succeeded",
+            "rejectedBy": "Require a minimum number of reviewers",
+            "customMessage": "Branch is protected"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_result_errors() {
+        let update = RefUpdate::move_to("refs/heads/main", "a".repeat(40), "b".repeat(40));
+        assert_eq!(
+            RefUpdateError::from_result(&update, &result("succeeded")),
+            None
+        );
+        assert_eq!(
+            RefUpdateError::from_result(&update, &result("staleOldObjectId")),
+            Some(RefUpdateError::Stale {
+                name: "refs/heads/main".to_string(),
+                expected: "a".repeat(40)
+            })
+        );
+        let error = RefUpdateError::from_result(&update, &result("rejectedByPolicy")).unwrap();
+        assert_eq!(
+            error.to_string(),
+            "update of refs/heads/main was rejected by policy Require a minimum number of reviewers: Branch is protected"
+        );
+        let error = Error::new(ErrorKind::Other, error);
+        assert!(matches!(
+            error.downcast_ref::<RefUpdateError>(),
+            Some(RefUpdateError::RejectedByPolicy { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rollback_updates() {
+        let updates = vec![
+            RefUpdate::create("refs/heads/new", "a".repeat(40)),
+            RefUpdate::delete("refs/tags/v1", "b".repeat(40)),
+            RefUpdate::move_to("refs/heads/main", "c".repeat(40), "d".repeat(40)),
+        ];
+        let results = vec![
+            result("succeeded"),
+            result("succeeded"),
+            result("staleOldObjectId"),
+        ];
+        assert_eq!(
+            rollback_updates(&updates, &results),
+            vec![
+                RefUpdate::delete("refs/heads/new", "a".repeat(40)),
+                RefUpdate::create("refs/tags/v1", "b".repeat(40)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,480 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Graph subject descriptors and identity descriptors.
+//!
+//! The `graph` APIs identify users and groups by subject descriptors such as
+//! `aad.MjU5ZTI1ODktYWQ2Yy03NzQ5LWI1ZTYtNTI0MmI0ZmNhNjI3` (a subject type and a base64 encoded
+//! identifier), while the `security` APIs use identity descriptors such as
+//! `Microsoft.TeamFoundation.Identity;S-1-9-1551374245-...` (an identity type and an
+//! identifier). [`SubjectDescriptor`] and [`IdentityDescriptor`] parse and format both.
+//!
+//! Group subject descriptors are converted to identity descriptors locally; other conversions
+//! use the `graph` descriptor/storage key APIs and the `ims` identities API:
+//! * `graph::Client::storage_key()`: subject descriptor to storage key (the identity ID)
+//! * `graph::Client::subject_descriptor()`: storage key to subject descriptor
+//! * `ims::Client::identity_descriptor()`: subject descriptor to identity descriptor
+//! * `ims::Client::subject_descriptor()`: identity descriptor to subject descriptor
+//!
+//! ```rust
+//!     let group: SubjectDescriptor = group.descriptor.parse()?;
+//!     assert_eq!(group.kind(), SubjectKind::VstsGroup);
+//!     let identity = group.to_identity_descriptor().expect("groups convert locally");
+//!     let storage_key = graph_client.storage_key(&organization, &group).await?;
+//! ```
+
+use azure_core::error::{Error, ErrorKind, ResultExt};
+use base64::{prelude::BASE64_STANDARD_NO_PAD, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// The identity type of Azure DevOps groups and other Team Foundation identities.
+pub const TEAM_FOUNDATION_IDENTITY: &str = "Microsoft.TeamFoundation.Identity";
+/// The identity type of Azure Active Directory and Microsoft account users.
+pub const CLAIMS_IDENTITY: &str = "Microsoft.IdentityModel.Claims.ClaimsIdentity";
+/// The identity type of service identities, such as build service accounts.
+pub const SERVICE_IDENTITY: &str = "Microsoft.TeamFoundation.ServiceIdentity";
+
+/// The kind of subject, from the subject type prefix of a [`SubjectDescriptor`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SubjectKind {
+    /// `aad`
+    AadUser,
+    /// `msa`
+    MsaUser,
+    /// `aadsp`
+    AadServicePrincipal,
+    /// `win`
+    WindowsUser,
+    /// `imp`
+    ImportedUser,
+    /// `bnd`
+    BindPendingUser,
+    /// `unusr`
+    UnknownUser,
+    /// `aadgp`
+    AadGroup,
+    /// `vssgp`
+    VstsGroup,
+    /// `ungrp`
+    UnknownGroup,
+    /// `svc`
+    ServiceIdentity,
+    /// `s2s`
+    ServerToServer,
+    /// Any other subject type.
+    Other(String),
+}
+
+impl SubjectKind {
+    pub fn from_subject_type(subject_type: &str) -> Self {
+        match subject_type {
+            "aad" => Self::AadUser,
+            "msa" => Self::MsaUser,
+            "aadsp" => Self::AadServicePrincipal,
+            "win" => Self::WindowsUser,
+            "imp" => Self::ImportedUser,
+            "bnd" => Self::BindPendingUser,
+            "unusr" => Self::UnknownUser,
+            "aadgp" => Self::AadGroup,
+            "vssgp" => Self::VstsGroup,
+            "ungrp" => Self::UnknownGroup,
+            "svc" => Self::ServiceIdentity,
+            "s2s" => Self::ServerToServer,
+            other => Self::Other(other.to_string()),
+        }
+    }
+
+    pub fn subject_type(&self) -> &str {
+        match self {
+            Self::AadUser => "aad",
+            Self::MsaUser => "msa",
+            Self::AadServicePrincipal => "aadsp",
+            Self::WindowsUser => "win",
+            Self::ImportedUser => "imp",
+            Self::BindPendingUser => "bnd",
+            Self::UnknownUser => "unusr",
+            Self::AadGroup => "aadgp",
+            Self::VstsGroup => "vssgp",
+            Self::UnknownGroup => "ungrp",
+            Self::ServiceIdentity => "svc",
+            Self::ServerToServer => "s2s",
+            Self::Other(subject_type) => subject_type,
+        }
+    }
+
+    pub fn is_group(&self) -> bool {
+        matches!(self, Self::AadGroup | Self::VstsGroup | Self::UnknownGroup)
+    }
+
+    pub fn is_user(&self) -> bool {
+        matches!(
+            self,
+            Self::AadUser
+                | Self::MsaUser
+                | Self::WindowsUser
+                | Self::ImportedUser
+                | Self::BindPendingUser
+                | Self::UnknownUser
+        )
+    }
+}
+
+/// A graph subject descriptor: `<subject type>.<base64 identifier>`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SubjectDescriptor {
+    subject_type: String,
+    identifier: String,
+}
+
+impl SubjectDescriptor {
+    /// Builds a descriptor from a subject type and a decoded identifier, e.g. `vssgp` and a SID.
+    pub fn new(subject_type: impl Into<String>, identifier: &str) -> Self {
+        Self {
+            subject_type: subject_type.into(),
+            identifier: BASE64_STANDARD_NO_PAD.encode(identifier),
+        }
+    }
+
+    pub fn subject_type(&self) -> &str {
+        &self.subject_type
+    }
+
+    pub fn kind(&self) -> SubjectKind {
+        SubjectKind::from_subject_type(&self.subject_type)
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.kind().is_group()
+    }
+
+    pub fn is_user(&self) -> bool {
+        self.kind().is_user()
+    }
+
+    /// The encoded identifier, as it appears in the descriptor.
+    pub fn encoded_identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// Decodes the identifier: a SID for groups, and an ID (often the storage key) for users.
+    pub fn identifier(&self) -> azure_core::Result<String> {
+        let encoded = self
+            .identifier
+            .trim_end_matches('=')
+            .replace('-', "+")
+            .replace('_', "/");
+        let decoded = BASE64_STANDARD_NO_PAD
+            .decode(encoded)
+            .with_context(ErrorKind::DataConversion, || {
+                format!("subject descriptor {self} has an invalid identifier")
+            })?;
+        String::from_utf8(decoded).with_context(ErrorKind::DataConversion, || {
+            format!("subject descriptor {self} has an invalid identifier")
+        })
+    }
+
+    /// Converts a group descriptor to its identity descriptor without a service call.
+    ///
+    /// Returns `None` for other subjects, whose identity descriptors can only be looked up
+    /// with `ims::Client::identity_descriptor()`.
+    pub fn to_identity_descriptor(&self) -> Option<IdentityDescriptor> {
+        match self.kind() {
+            SubjectKind::VstsGroup | SubjectKind::AadGroup => {
+                let sid = self.identifier().ok()?;
+                sid.starts_with("S-1-")
+                    .then(|| IdentityDescriptor::new(TEAM_FOUNDATION_IDENTITY, sid))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SubjectDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.subject_type, self.identifier)
+    }
+}
+
+impl FromStr for SubjectDescriptor {
+    type Err = Error;
+
+    fn from_str(descriptor: &str) -> azure_core::Result<Self> {
+        match descriptor.split_once('.') {
+            Some((subject_type, identifier))
+                if !subject_type.is_empty() && !identifier.is_empty() =>
+            {
+                Ok(Self {
+                    subject_type: subject_type.to_string(),
+                    identifier: identifier.to_string(),
+                })
+            }
+            _ => Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!("invalid subject descriptor: {descriptor}")
+            })),
+        }
+    }
+}
+
+/// An identity descriptor: `<identity type>;<identifier>`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct IdentityDescriptor {
+    identity_type: String,
+    identifier: String,
+}
+
+impl IdentityDescriptor {
+    pub fn new(identity_type: impl Into<String>, identifier: impl Into<String>) -> Self {
+        Self {
+            identity_type: identity_type.into(),
+            identifier: identifier.into(),
+        }
+    }
+
+    pub fn identity_type(&self) -> &str {
+        &self.identity_type
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// Whether this is a Team Foundation group (or other Team Foundation identity) SID.
+    pub fn is_team_foundation_identity(&self) -> bool {
+        self.identity_type
+            .eq_ignore_ascii_case(TEAM_FOUNDATION_IDENTITY)
+    }
+}
+
+impl fmt::Display for IdentityDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{};{}", self.identity_type, self.identifier)
+    }
+}
+
+impl FromStr for IdentityDescriptor {
+    type Err = Error;
+
+    fn from_str(descriptor: &str) -> azure_core::Result<Self> {
+        match descriptor.split_once(';') {
+            Some((identity_type, identifier))
+                if !identity_type.is_empty() && !identifier.is_empty() =>
+            {
+                Ok(Self::new(identity_type, identifier))
+            }
+            _ => Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!("invalid identity descriptor: {descriptor}")
+            })),
+        }
+    }
+}
+
+macro_rules! serde_as_string {
+    ($type:ty) => {
+        impl Serialize for $type {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $type {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let descriptor = String::deserialize(deserializer)?;
+                descriptor.parse().map_err(serde::de::Error::custom)
+            }
+        }
+    };
+}
+
+serde_as_string!(SubjectDescriptor);
+serde_as_string!(IdentityDescriptor);
+
+#[cfg(feature = "security")]
+impl From<IdentityDescriptor> for crate::security::models::IdentityDescriptor {
+    fn from(descriptor: IdentityDescriptor) -> Self {
+        Self {
+            identity_type: Some(descriptor.identity_type),
+            identifier: Some(descriptor.identifier),
+        }
+    }
+}
+
+impl crate::graph::Client {
+    /// Looks up the storage key (identity ID) of a subject.
+    pub async fn storage_key(
+        &self,
+        organization: impl Into<String>,
+        subject: &SubjectDescriptor,
+    ) -> azure_core::Result<String> {
+        let result = self
+            .storage_keys_client()
+            .get(subject.to_string(), organization)
+            .await?;
+        result.value.ok_or_else(|| {
+            Error::with_message(ErrorKind::DataConversion, || {
+                format!("no storage key returned for {subject}")
+            })
+        })
+    }
+
+    /// Looks up the subject descriptor of a storage key (identity ID).
+    pub async fn subject_descriptor(
+        &self,
+        organization: impl Into<String>,
+        storage_key: impl Into<String>,
+    ) -> azure_core::Result<SubjectDescriptor> {
+        let storage_key = storage_key.into();
+        let result = self
+            .descriptors_client()
+            .get(storage_key.clone(), organization)
+            .await?;
+        match result.value {
+            Some(descriptor) => descriptor.parse(),
+            None => Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!("no subject descriptor returned for storage key {storage_key}")
+            })),
+        }
+    }
+}
+
+#[cfg(feature = "ims")]
+impl crate::ims::Client {
+    /// Looks up the identity descriptor of a subject.
+    ///
+    /// Group descriptors are converted without a service call.
+    pub async fn identity_descriptor(
+        &self,
+        organization: impl Into<String>,
+        subject: &SubjectDescriptor,
+    ) -> azure_core::Result<IdentityDescriptor> {
+        if let Some(descriptor) = subject.to_identity_descriptor() {
+            return Ok(descriptor);
+        }
+        let identity = self
+            .identities_client()
+            .read_identities(organization)
+            .subject_descriptors(subject.to_string())
+            .await?
+            .value
+            .into_iter()
+            .next();
+        match identity.and_then(|identity| identity.identity_base.descriptor) {
+            Some(descriptor) => descriptor.parse(),
+            None => Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!("no identity found for {subject}")
+            })),
+        }
+    }
+
+    /// Looks up the subject descriptor of an identity.
+    pub async fn subject_descriptor(
+        &self,
+        organization: impl Into<String>,
+        identity: &IdentityDescriptor,
+    ) -> azure_core::Result<SubjectDescriptor> {
+        let found = self
+            .identities_client()
+            .read_identities(organization)
+            .descriptors(identity.to_string())
+            .await?
+            .value
+            .into_iter()
+            .next();
+        match found.and_then(|found| found.identity_base.subject_descriptor) {
+            Some(descriptor) => descriptor.parse(),
+            None => Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!("no identity found for {identity}")
+            })),
+        }
+    }
+
+    /// Looks up the identity descriptor and subject descriptor of an identity ID.
+    pub async fn identity_descriptors_by_id(
+        &self,
+        organization: impl Into<String>,
+        identity_id: impl Into<String>,
+    ) -> azure_core::Result<(IdentityDescriptor, SubjectDescriptor)> {
+        let identity_id = identity_id.into();
+        let found = self
+            .identities_client()
+            .read_identities(organization)
+            .identity_ids(identity_id.clone())
+            .await?
+            .value
+            .into_iter()
+            .next()
+            .map(|found| found.identity_base);
+        match found.and_then(|found| found.descriptor.zip(found.subject_descriptor)) {
+            Some((descriptor, subject_descriptor)) => {
+                Ok((descriptor.parse()?, subject_descriptor.parse()?))
+            }
+            None => Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!("no identity found for ID {identity_id}")
+            })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SID: &str = "S-1-9-1551374245-1204400969-2402986413-2179408616-0-0-0-0-1";
+
+    #[test]
+    fn test_subject_descriptor() {
+        let descriptor: SubjectDescriptor = "aad.MjU5ZTI1ODktYWQ2Yy03NzQ5LWI1ZTYtNTI0MmI0ZmNhNjI3"
+            .parse()
+            .unwrap();
+        assert_eq!(descriptor.kind(), SubjectKind::AadUser);
+        assert!(descriptor.is_user());
+        assert_eq!(
+            descriptor.identifier().unwrap(),
+            "259e2589-ad6c-7749-b5e6-5242b4fca627"
+        );
+        assert_eq!(descriptor.to_identity_descriptor(), None);
+        assert_eq!(
+            descriptor.to_string(),
+            "aad.MjU5ZTI1ODktYWQ2Yy03NzQ5LWI1ZTYtNTI0MmI0ZmNhNjI3"
+        );
+        assert!("aad".parse::<SubjectDescriptor>().is_err());
+        assert!(".abc".parse::<SubjectDescriptor>().is_err());
+        assert_eq!(
+            "xyz.abc".parse::<SubjectDescriptor>().unwrap().kind(),
+            SubjectKind::Other("xyz".to_string())
+        );
+    }
+
+    #[test]
+    fn test_group_to_identity_descriptor() {
+        let group = SubjectDescriptor::new("vssgp", SID);
+        assert!(group.is_group());
+        let identity = group.to_identity_descriptor().unwrap();
+        assert!(identity.is_team_foundation_identity());
+        assert_eq!(identity.identifier(), SID);
+        assert_eq!(
+            identity.to_string(),
+            format!("Microsoft.TeamFoundation.Identity;{SID}")
+        );
+        assert_eq!(
+            group.to_string().parse::<SubjectDescriptor>().unwrap(),
+            group
+        );
+    }
+
+    #[test]
+    fn test_identity_descriptor_serde() {
+        let descriptor: IdentityDescriptor = serde_json::from_value(serde_json::json!(
+            "Microsoft.IdentityModel.Claims.ClaimsIdentity;tenant\\user@example.com"
+        ))
+        .unwrap();
+        assert_eq!(descriptor.identity_type(), CLAIMS_IDENTITY);
+        assert_eq!(descriptor.identifier(), "tenant\\user@example.com");
+        assert_eq!(
+            serde_json::to_value(&descriptor).unwrap(),
+            serde_json::json!(descriptor.to_string())
+        );
+        assert!(serde_json::from_value::<IdentityDescriptor>(serde_json::json!("nope")).is_err());
+    }
+}
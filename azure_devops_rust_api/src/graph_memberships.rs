@@ -0,0 +1,330 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Transitive group membership resolution.
+//!
+//! `graph::memberships::list` only returns direct memberships. [`MembershipResolver`] follows
+//! them transitively, either up (the groups a subject is effectively in) or down (everything a
+//! group effectively contains), and records the path that grants each membership. Direct
+//! memberships are cached by the resolver, so answering several questions with one resolver
+//! shares the service calls. Membership cycles are followed once.
+//!
+//! ```rust
+//!     let resolver = graph_client.membership_resolver(&organization);
+//!     if let Some(path) = resolver.membership_path(&user, &project_administrators).await? {
+//!         println!("granted through {path:?}");
+//!     }
+//!     let members = resolver.members_of(&group).await?;
+//!     for user in members.users() {
+//!         println!("{user}");
+//!     }
+//! ```
+
+use crate::graph_descriptors::SubjectDescriptor;
+use futures::{StreamExt, TryStreamExt};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// The direction to follow memberships in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MembershipDirection {
+    /// From a subject to the groups that contain it.
+    Up,
+    /// From a group to its members.
+    Down,
+}
+
+impl MembershipDirection {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+        }
+    }
+}
+
+/// The subjects reached from a root subject by following memberships transitively.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpandedMemberships {
+    /// The subject the memberships were expanded from.
+    pub root: SubjectDescriptor,
+    pub direction: MembershipDirection,
+    /// For each subject reached, a shortest path of subjects from the root to it (excluding
+    /// the root, ending with the subject). For [`MembershipDirection::Up`] the path lists the
+    /// groups through which the root is a member; for [`MembershipDirection::Down`] it lists
+    /// the groups through which the subject is a member of the root.
+    pub paths: BTreeMap<SubjectDescriptor, Vec<SubjectDescriptor>>,
+}
+
+impl ExpandedMemberships {
+    pub fn contains(&self, subject: &SubjectDescriptor) -> bool {
+        self.paths.contains_key(subject)
+    }
+
+    /// The path to a subject, if it was reached.
+    pub fn path(&self, subject: &SubjectDescriptor) -> Option<&[SubjectDescriptor]> {
+        self.paths.get(subject).map(Vec::as_slice)
+    }
+
+    /// All subjects reached.
+    pub fn subjects(&self) -> impl Iterator<Item = &SubjectDescriptor> {
+        self.paths.keys()
+    }
+
+    /// The groups reached.
+    pub fn groups(&self) -> impl Iterator<Item = &SubjectDescriptor> {
+        self.subjects().filter(|subject| subject.is_group())
+    }
+
+    /// The users reached.
+    pub fn users(&self) -> impl Iterator<Item = &SubjectDescriptor> {
+        self.subjects().filter(|subject| subject.is_user())
+    }
+
+    /// Subjects that are reached directly from the root.
+    pub fn direct(&self) -> impl Iterator<Item = &SubjectDescriptor> {
+        self.paths
+            .iter()
+            .filter(|(_, path)| path.len() == 1)
+            .map(|(subject, _)| subject)
+    }
+}
+
+type Cache = HashMap<(SubjectDescriptor, MembershipDirection), Vec<SubjectDescriptor>>;
+
+impl crate::graph::Client {
+    /// Creates a resolver for the transitive group memberships of an organization.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    pub fn membership_resolver(&self, organization: impl Into<String>) -> MembershipResolver {
+        MembershipResolver {
+            client: self.clone(),
+            organization: organization.into(),
+            max_depth: None,
+            concurrency: 4,
+            cache: Arc::default(),
+        }
+    }
+}
+
+/// Resolves transitive group memberships, caching direct memberships.
+///
+/// Clones share the cache.
+#[derive(Clone)]
+pub struct MembershipResolver {
+    client: crate::graph::Client,
+    organization: String,
+    max_depth: Option<usize>,
+    concurrency: usize,
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl MembershipResolver {
+    /// Maximum number of memberships to follow from the root (default: unlimited).
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Maximum number of membership requests in flight at once.
+    #[must_use]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Discards the cached direct memberships.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// The groups that a subject is a member of, directly or through other groups.
+    pub async fn groups_of(
+        &self,
+        subject: &SubjectDescriptor,
+    ) -> azure_core::Result<ExpandedMemberships> {
+        self.expand(subject, MembershipDirection::Up).await
+    }
+
+    /// The users and groups that are members of a group, directly or through other groups.
+    pub async fn members_of(
+        &self,
+        group: &SubjectDescriptor,
+    ) -> azure_core::Result<ExpandedMemberships> {
+        self.expand(group, MembershipDirection::Down).await
+    }
+
+    /// The groups through which a subject is a member of a group (ending with the group), or
+    /// `None` if it isn't a member.
+    pub async fn membership_path(
+        &self,
+        subject: &SubjectDescriptor,
+        group: &SubjectDescriptor,
+    ) -> azure_core::Result<Option<Vec<SubjectDescriptor>>> {
+        let groups = self.groups_of(subject).await?;
+        Ok(groups.path(group).map(<[SubjectDescriptor]>::to_vec))
+    }
+
+    /// Follows memberships from a subject, breadth first.
+    pub async fn expand(
+        &self,
+        root: &SubjectDescriptor,
+        direction: MembershipDirection,
+    ) -> azure_core::Result<ExpandedMemberships> {
+        let mut expanded = ExpandedMemberships {
+            root: root.clone(),
+            direction,
+            paths: BTreeMap::new(),
+        };
+        let mut seen: HashSet<SubjectDescriptor> = HashSet::from([root.clone()]);
+        let mut frontier = vec![root.clone()];
+        let mut depth = 0;
+        while !frontier.is_empty() && self.max_depth.map_or(true, |max| depth < max) {
+            // Only groups have members.
+            frontier.retain(|subject| direction == MembershipDirection::Up || !subject.is_user());
+            let direct: Vec<(SubjectDescriptor, Vec<SubjectDescriptor>)> =
+                futures::stream::iter(frontier)
+                    .map(|subject| async move {
+                        let related = self.direct(&subject, direction).await?;
+                        Ok::<_, azure_core::Error>((subject, related))
+                    })
+                    .buffered(self.concurrency)
+                    .try_collect()
+                    .await?;
+            let mut next = Vec::new();
+            for (subject, related) in direct {
+                let path = expanded.paths.get(&subject).cloned().unwrap_or_default();
+                for related in related {
+                    if seen.insert(related.clone()) {
+                        let mut related_path = path.clone();
+                        related_path.push(related.clone());
+                        expanded.paths.insert(related.clone(), related_path);
+                        next.push(related);
+                    }
+                }
+            }
+            frontier = next;
+            depth += 1;
+        }
+        Ok(expanded)
+    }
+
+    /// The direct memberships of a subject, from the cache or the service.
+    async fn direct(
+        &self,
+        subject: &SubjectDescriptor,
+        direction: MembershipDirection,
+    ) -> azure_core::Result<Vec<SubjectDescriptor>> {
+        let key = (subject.clone(), direction);
+        if let Some(related) = self.cache.lock().unwrap().get(&key) {
+            return Ok(related.clone());
+        }
+        let memberships = self
+            .client
+            .memberships_client()
+            .list(&self.organization, subject.to_string())
+            .direction(direction.as_str())
+            .await?
+            .value;
+        let related = memberships
+            .into_iter()
+            .filter_map(|membership| match direction {
+                MembershipDirection::Up => membership.container_descriptor,
+                MembershipDirection::Down => membership.member_descriptor,
+            })
+            .map(|descriptor| descriptor.parse())
+            .collect::<azure_core::Result<Vec<SubjectDescriptor>>>()?;
+        self.cache.lock().unwrap().insert(key, related.clone());
+        Ok(related)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subject(subject_type: &str, name: &str) -> SubjectDescriptor {
+        SubjectDescriptor::new(subject_type, name)
+    }
+
+    /// A resolver whose cache holds the memberships: user in team, team in admins and
+    /// contributors, admins in team (a cycle).
+    fn resolver() -> MembershipResolver {
+        let client = crate::graph::ClientBuilder::new(crate::Credential::unauthenticated()).build();
+        let resolver = client.membership_resolver("org");
+        let (user, team, admins, contributors) = (
+            subject("aad", "user"),
+            subject("vssgp", "team"),
+            subject("vssgp", "admins"),
+            subject("vssgp", "contributors"),
+        );
+        let mut cache = resolver.cache.lock().unwrap();
+        let up = MembershipDirection::Up;
+        let down = MembershipDirection::Down;
+        cache.insert((user.clone(), up), vec![team.clone()]);
+        cache.insert(
+            (team.clone(), up),
+            vec![admins.clone(), contributors.clone()],
+        );
+        cache.insert((admins.clone(), up), vec![team.clone()]);
+        cache.insert((contributors.clone(), up), vec![]);
+        cache.insert((admins.clone(), down), vec![team.clone()]);
+        cache.insert((team.clone(), down), vec![user, admins]);
+        drop(cache);
+        resolver
+    }
+
+    #[test]
+    fn test_groups_of() {
+        let resolver = resolver();
+        let groups =
+            futures::executor::block_on(resolver.groups_of(&subject("aad", "user"))).unwrap();
+        assert_eq!(groups.groups().count(), 3);
+        assert_eq!(
+            groups.path(&subject("vssgp", "admins")).unwrap(),
+            [subject("vssgp", "team"), subject("vssgp", "admins")]
+        );
+        assert_eq!(
+            groups.direct().collect::<Vec<_>>(),
+            [&subject("vssgp", "team")]
+        );
+    }
+
+    #[test]
+    fn test_members_of_with_cycle() {
+        let resolver = resolver();
+        let members =
+            futures::executor::block_on(resolver.members_of(&subject("vssgp", "admins"))).unwrap();
+        assert_eq!(
+            members.users().collect::<Vec<_>>(),
+            [&subject("aad", "user")]
+        );
+        assert!(!members.contains(&subject("vssgp", "admins")));
+        assert_eq!(
+            members.path(&subject("aad", "user")).unwrap(),
+            [subject("vssgp", "team"), subject("aad", "user")]
+        );
+    }
+
+    #[test]
+    fn test_membership_path_and_max_depth() {
+        let resolver = resolver();
+        let path = futures::executor::block_on(
+            resolver.membership_path(&subject("aad", "user"), &subject("vssgp", "contributors")),
+        )
+        .unwrap();
+        assert_eq!(
+            path,
+            Some(vec![
+                subject("vssgp", "team"),
+                subject("vssgp", "contributors")
+            ])
+        );
+        let groups =
+            futures::executor::block_on(resolver.max_depth(1).groups_of(&subject("aad", "user")))
+                .unwrap();
+        assert_eq!(groups.subjects().count(), 1);
+    }
+}
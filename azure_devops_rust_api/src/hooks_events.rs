@@ -0,0 +1,586 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Typed service hook events.
+//!
+//! [`WebhookParser`] parses the body of a request POSTed by a service hook "Web Hooks"
+//! subscription into a [`ServiceHookEvent`], after checking the request's basic authentication
+//! and/or shared secret header. It is independent of any web framework: pass it the request
+//! headers as name/value pairs and the body as bytes.
+//!
+//! The resource of common events is parsed into the crate's `git`, `build`, `wit` and
+//! `pipelines` models (when those features are enabled); other events are returned as
+//! [`EventResource::Unknown`].
+//!
+//! ```rust
+//!     let parser = WebhookParser::new().basic_auth("hooks", webhook_password);
+//!     let event = parser.parse(request.headers(), &body)?;
+//!     match event.resource {
+//!         EventResource::GitPush(push) => println!("push {:?}", push.git_push_ref.push_id),
+//!         EventResource::BuildCompleted(build) => println!("build {} {:?}", build.id, build.result),
+//!         _ => {}
+//!     }
+//! ```
+
+use crate::hooks::models::{FormattedEventMessage, ResourceContainer};
+use crate::secret::Secret;
+use azure_core::error::{Error, ErrorKind, ResultExt};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// An event POSTed by a service hook subscription.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ServiceHookEvent {
+    /// The ID of the event.
+    pub id: Option<String>,
+    /// The type of the event, e.g. `git.push`.
+    pub event_type: String,
+    /// The ID of the publisher that raised the event, e.g. `tfs`.
+    pub publisher_id: Option<String>,
+    pub created_date: Option<time::OffsetDateTime>,
+    pub message: Option<FormattedEventMessage>,
+    pub detailed_message: Option<FormattedEventMessage>,
+    /// The version of the resource schema, e.g. `1.0`.
+    pub resource_version: Option<String>,
+    pub resource_containers: ResourceContainers,
+    pub resource: EventResource,
+}
+
+/// The organization and project that raised an event.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct ResourceContainers {
+    pub collection: Option<ResourceContainer>,
+    pub account: Option<ResourceContainer>,
+    pub project: Option<ResourceContainer>,
+}
+
+/// The resource of a service hook event.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EventResource {
+    /// `git.push`
+    #[cfg(feature = "git")]
+    GitPush(Box<crate::git::models::GitPush>),
+    /// `git.pullrequest.created`
+    #[cfg(feature = "git")]
+    PullRequestCreated(Box<crate::git::models::GitPullRequest>),
+    /// `git.pullrequest.updated`
+    #[cfg(feature = "git")]
+    PullRequestUpdated(Box<crate::git::models::GitPullRequest>),
+    /// `git.pullrequest.merged` (a merge was attempted; see `merge_status`)
+    #[cfg(feature = "git")]
+    PullRequestMerged(Box<crate::git::models::GitPullRequest>),
+    /// `build.complete`
+    #[cfg(feature = "build")]
+    BuildCompleted(Box<crate::build::models::Build>),
+    /// `workitem.updated`
+    #[cfg(feature = "wit")]
+    WorkItemUpdated(Box<WorkItemUpdatedResource>),
+    /// `ms.vss-pipelines.run-state-changed-event`
+    #[cfg(feature = "pipelines")]
+    RunStateChanged(Box<RunStateChangedResource>),
+    /// Any other event (or an event whose model feature is not enabled), with its raw resource.
+    Unknown(Value),
+}
+
+/// The resource of a `workitem.updated` event: the update, and the work item after it.
+#[cfg(feature = "wit")]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct WorkItemUpdatedResource {
+    #[serde(flatten)]
+    pub update: crate::wit::models::WorkItemUpdate,
+    /// The work item at the revision created by the update.
+    #[serde(default)]
+    pub revision: Option<crate::wit::models::WorkItem>,
+}
+
+/// The resource of a `ms.vss-pipelines.run-state-changed-event` event.
+#[cfg(feature = "pipelines")]
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunStateChangedResource {
+    pub run: crate::pipelines::models::Run,
+    #[serde(default)]
+    pub pipeline: Option<crate::pipelines::models::PipelineReference>,
+    #[serde(default)]
+    pub run_id: Option<i32>,
+    #[serde(default)]
+    pub run_url: Option<String>,
+}
+
+impl EventResource {
+    fn parse(event_type: &str, resource: Value) -> azure_core::Result<Self> {
+        #[allow(unused_macros)]
+        macro_rules! typed {
+            ($variant:ident) => {
+                EventResource::$variant(Box::new(
+                    serde_json::from_value(resource)
+                        .with_context(ErrorKind::DataConversion, || {
+                            format!("failed to parse the resource of a {event_type} event")
+                        })?,
+                ))
+            };
+        }
+        let resource = match event_type {
+            #[cfg(feature = "git")]
+            "git.push" => typed!(GitPush),
+            #[cfg(feature = "git")]
+            "git.pullrequest.created" => typed!(PullRequestCreated),
+            #[cfg(feature = "git")]
+            "git.pullrequest.updated" => typed!(PullRequestUpdated),
+            #[cfg(feature = "git")]
+            "git.pullrequest.merged" => typed!(PullRequestMerged),
+            #[cfg(feature = "build")]
+            "build.complete" => typed!(BuildCompleted),
+            #[cfg(feature = "wit")]
+            "workitem.updated" => typed!(WorkItemUpdated),
+            #[cfg(feature = "pipelines")]
+            "ms.vss-pipelines.run-state-changed-event" => typed!(RunStateChanged),
+            _ => EventResource::Unknown(resource),
+        };
+        Ok(resource)
+    }
+}
+
+/// Parses and authenticates the requests of a service hook "Web Hooks" subscription.
+#[derive(Clone, Debug, Default)]
+pub struct WebhookParser {
+    basic_auth: Option<(String, Secret)>,
+    shared_secrets: Vec<(String, Secret)>,
+}
+
+impl WebhookParser {
+    /// A parser that accepts any request.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requires the basic authentication username and password configured on the subscription.
+    #[must_use]
+    pub fn basic_auth(mut self, username: impl Into<String>, password: impl Into<Secret>) -> Self {
+        self.basic_auth = Some((username.into(), password.into()));
+        self
+    }
+
+    /// Requires an HTTP header configured on the subscription (e.g. `X-Hook-Secret: <secret>`).
+    /// May be called multiple times.
+    #[must_use]
+    pub fn shared_secret(
+        mut self,
+        header_name: impl Into<String>,
+        value: impl Into<Secret>,
+    ) -> Self {
+        self.shared_secrets.push((header_name.into(), value.into()));
+        self
+    }
+
+    /// Authenticates a request and parses its body.
+    ///
+    /// Fails with [`ErrorKind::Credential`] if the request isn't authenticated as required,
+    /// and with [`ErrorKind::DataConversion`] if the body is not a valid event.
+    pub fn parse<I, K, V>(&self, headers: I, body: &[u8]) -> azure_core::Result<ServiceHookEvent>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let headers: Vec<(K, V)> = headers.into_iter().collect();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.as_ref().eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_ref())
+        };
+        if let Some((username, password)) = &self.basic_auth {
+            let credentials = header("authorization")
+                .and_then(|value| value.strip_prefix("Basic "))
+                .and_then(|value| BASE64_STANDARD.decode(value.trim()).ok())
+                .unwrap_or_default();
+            let expected = format!("{username}:{}", password.secret());
+            if !constant_time_eq(&credentials, expected.as_bytes()) {
+                return Err(Error::message(
+                    ErrorKind::Credential,
+                    "service hook request has invalid basic authentication",
+                ));
+            }
+        }
+        for (name, secret) in &self.shared_secrets {
+            let value = header(name).unwrap_or_default();
+            if !constant_time_eq(value.as_bytes(), secret.secret().as_bytes()) {
+                return Err(Error::with_message(ErrorKind::Credential, || {
+                    format!("service hook request has an invalid {name} header")
+                }));
+            }
+        }
+        parse_event(body)
+    }
+}
+
+/// Parses the body of a service hook request, without authenticating it.
+pub fn parse_event(body: &[u8]) -> azure_core::Result<ServiceHookEvent> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "camelCase")]
+    struct Envelope {
+        id: Option<String>,
+        event_type: String,
+        publisher_id: Option<String>,
+        #[serde(default, with = "crate::date_time::rfc3339::option")]
+        created_date: Option<time::OffsetDateTime>,
+        message: Option<FormattedEventMessage>,
+        detailed_message: Option<FormattedEventMessage>,
+        resource_version: Option<String>,
+        #[serde(default)]
+        resource_containers: ResourceContainers,
+        #[serde(default)]
+        resource: Value,
+    }
+
+    let envelope: Envelope = serde_json::from_slice(body)
+        .with_context(ErrorKind::DataConversion, || {
+            "failed to parse service hook event"
+        })?;
+    Ok(ServiceHookEvent {
+        resource: EventResource::parse(&envelope.event_type, envelope.resource)?,
+        id: envelope.id,
+        event_type: envelope.event_type,
+        publisher_id: envelope.publisher_id,
+        created_date: envelope.created_date,
+        message: envelope.message,
+        detailed_message: envelope.detailed_message,
+        resource_version: envelope.resource_version,
+        resource_containers: envelope.resource_containers,
+    })
+}
+
+/// Compares secrets in time independent of where they differ.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn body(event_type: &str, resource: Value) -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "subscriptionId": "00000000-0000-0000-0000-000000000000",
+            "id": "event",
+            "eventType": event_type,
+            "publisherId": "tfs",
+            "message": { "text": "Something happened" },
+            "resource": resource,
+            "resourceVersion": "1.0",
+            "resourceContainers": {
+                "collection": { "id": "c" },
+                "project": { "id": "p" }
+            },
+            "createdDate": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_authentication() {
+        let parser = WebhookParser::new()
+            .basic_auth("hooks", "p@ss")
+            .shared_secret("X-Hook-Secret", "s3cret");
+        let body = body("custom.event", json!({ "a": 1 }));
+        let authorization = format!("Basic {}", BASE64_STANDARD.encode("hooks:p@ss"));
+        let event = parser
+            .parse(
+                [
+                    ("Authorization", authorization.as_str()),
+                    ("x-hook-secret", "s3cret"),
+                ],
+                &body,
+            )
+            .unwrap();
+        assert_eq!(event.event_type, "custom.event");
+        assert_eq!(event.resource, EventResource::Unknown(json!({ "a": 1 })));
+        assert_eq!(
+            event
+                .resource_containers
+                .project
+                .and_then(|project| project.id),
+            Some("p".to_string())
+        );
+
+        let wrong_secret = parser.parse(
+            [
+                ("Authorization", authorization.as_str()),
+                ("X-Hook-Secret", "guess"),
+            ],
+            &body,
+        );
+        assert!(matches!(
+            wrong_secret.unwrap_err().kind(),
+            ErrorKind::Credential
+        ));
+        let no_auth = parser.parse([("X-Hook-Secret", "s3cret")], &body);
+        assert!(no_auth.is_err());
+    }
+
+    #[test]
+    fn test_invalid_body() {
+        let error = parse_event(b"not json").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::DataConversion));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_git_push() {
+        let event = parse_event(&body(
+            "git.push",
+            json!({
+                "commits": [{ "commitId": "abc", "comment": "Fix" }],
+                "refUpdates": [{
+                    "name": "refs/heads/main",
+                    "oldObjectId": "000",
+                    "newObjectId": "abc"
+                }],
+                "pushId": 14,
+                "date": "2024-01-01T00:00:00Z"
+            }),
+        ))
+        .unwrap();
+        match event.resource {
+            EventResource::GitPush(push) => {
+                assert_eq!(push.git_push_ref.push_id, Some(14));
+                assert_eq!(push.ref_updates[0].name.as_deref(), Some("refs/heads/main"));
+            }
+            resource => panic!("unexpected resource {resource:?}"),
+        }
+    }
+
+    /// An identity as it appears in event payloads.
+    #[cfg(any(feature = "build", feature = "wit"))]
+    fn identity() -> Value {
+        json!({
+            "displayName": "Jamal Hartnett",
+            "url": "https://spsprodweu5.vssps.visualstudio.com/A1/_apis/Identities/d6245f20",
+            "_links": { "avatar": { "href": "https://dev.azure.com/fabrikam/_apis/GraphProfile/MemberAvatars/aad.YTkz" } },
+            "id": "d6245f20-2af8-44f4-9451-8107cb2767db",
+            "uniqueName": "fabrikamfiber4@hotmail.com",
+            "imageUrl": "https://dev.azure.com/fabrikam/_apis/GraphProfile/MemberAvatars/aad.YTkz",
+            "descriptor": "aad.YTkzODFkODYtNTYxYS03ZDdiLWJjM2QtZDUzMjllMjM5OTAz"
+        })
+    }
+
+    #[cfg(feature = "build")]
+    #[test]
+    fn test_build_complete() {
+        let project = json!({
+            "id": "6ce954b1-ce1f-45d1-b94d-e6bf2464ba2c",
+            "name": "Fabrikam",
+            "url": "https://dev.azure.com/fabrikam/_apis/projects/6ce954b1-ce1f-45d1-b94d-e6bf2464ba2c",
+            "state": "wellFormed",
+            "revision": 11,
+            "visibility": "private",
+            "lastUpdateTime": "2024-01-01T00:00:00Z"
+        });
+        let event = parse_event(&body(
+            "build.complete",
+            json!({
+                "_links": {
+                    "self": { "href": "https://dev.azure.com/fabrikam/6ce954b1/_apis/build/Builds/1234" },
+                    "web": { "href": "https://dev.azure.com/fabrikam/6ce954b1/_build/results?buildId=1234" }
+                },
+                "properties": {},
+                "tags": [],
+                "validationResults": [],
+                "plans": [{ "planId": "f5e4b2a8-0d34-4cf4-8d0b-8d6c4f2b8e10" }],
+                "triggerInfo": { "ci.sourceBranch": "refs/heads/main" },
+                "id": 1234,
+                "buildNumber": "20240101.1",
+                "status": "completed",
+                "result": "succeeded",
+                "queueTime": "2024-01-01T00:00:00.1234567Z",
+                "startTime": "2024-01-01T00:00:05.1234567Z",
+                "finishTime": "2024-01-01T00:03:05.1234567Z",
+                "url": "https://dev.azure.com/fabrikam/6ce954b1/_apis/build/Builds/1234",
+                "definition": {
+                    "drafts": [],
+                    "id": 5,
+                    "name": "CI",
+                    "url": "https://dev.azure.com/fabrikam/6ce954b1/_apis/build/Definitions/5?revision=3",
+                    "uri": "vstfs:///Build/Definition/5",
+                    "path": "\\",
+                    "type": "build",
+                    "queueStatus": "enabled",
+                    "revision": 3,
+                    "project": project
+                },
+                "buildNumberRevision": 1,
+                "project": project,
+                "uri": "vstfs:///Build/Build/1234",
+                "sourceBranch": "refs/heads/main",
+                "sourceVersion": "600c52d2d5b655caa111abfd863e5a9bd304bb0e",
+                "queue": {
+                    "id": 9,
+                    "name": "Azure Pipelines",
+                    "pool": { "id": 9, "name": "Azure Pipelines", "isHosted": true }
+                },
+                "priority": "normal",
+                "reason": "individualCI",
+                "requestedFor": identity(),
+                "requestedBy": identity(),
+                "lastChangedDate": "2024-01-01T00:03:06.1234567Z",
+                "lastChangedBy": identity(),
+                "orchestrationPlan": { "planId": "f5e4b2a8-0d34-4cf4-8d0b-8d6c4f2b8e10" },
+                "logs": {
+                    "id": 0,
+                    "type": "Container",
+                    "url": "https://dev.azure.com/fabrikam/6ce954b1/_apis/build/builds/1234/logs"
+                },
+                "repository": {
+                    "id": "278d5cd2-584d-4b63-824a-2ba458937249",
+                    "type": "TfsGit",
+                    "name": "Fabrikam",
+                    "url": "https://dev.azure.com/fabrikam/Fabrikam/_git/Fabrikam",
+                    "clean": null,
+                    "checkoutSubmodules": false
+                },
+                "retainedByRelease": false,
+                "triggeredByBuild": null,
+                "appendCommitMessageToRunName": true
+            }),
+        ))
+        .unwrap();
+        match event.resource {
+            EventResource::BuildCompleted(build) => {
+                assert_eq!(build.id, 1234);
+                assert_eq!(
+                    build.result,
+                    Some(crate::build::models::build::Result::Succeeded)
+                );
+                assert_eq!(build.source_branch.as_deref(), Some("refs/heads/main"));
+            }
+            resource => panic!("unexpected resource {resource:?}"),
+        }
+    }
+
+    #[cfg(feature = "wit")]
+    #[test]
+    fn test_work_item_updated() {
+        let event = parse_event(&body(
+            "workitem.updated",
+            json!({
+                "id": 2,
+                "workItemId": 5,
+                "rev": 2,
+                "revisedBy": identity(),
+                "revisedDate": "2024-01-01T00:00:00.663Z",
+                "fields": {
+                    "System.Rev": { "oldValue": 1, "newValue": 2 },
+                    "System.State": { "oldValue": "New", "newValue": "Approved" },
+                    "System.AssignedTo": { "newValue": identity() },
+                    "System.ChangedDate": {
+                        "oldValue": "2023-12-31T00:00:00Z",
+                        "newValue": "2024-01-01T00:00:00Z"
+                    }
+                },
+                "_links": {
+                    "self": { "href": "https://dev.azure.com/fabrikam/_apis/wit/workItems/5/updates/2" },
+                    "workItemUpdates": { "href": "https://dev.azure.com/fabrikam/_apis/wit/workItems/5/updates" },
+                    "parent": { "href": "https://dev.azure.com/fabrikam/_apis/wit/workItems/5" }
+                },
+                "url": "https://dev.azure.com/fabrikam/_apis/wit/workItems/5/updates/2",
+                "revision": {
+                    "id": 5,
+                    "rev": 2,
+                    "fields": {
+                        "System.AreaPath": "Fabrikam",
+                        "System.TeamProject": "Fabrikam",
+                        "System.WorkItemType": "Bug",
+                        "System.State": "Approved",
+                        "System.Title": "Some great new idea!"
+                    },
+                    "url": "https://dev.azure.com/fabrikam/_apis/wit/workItems/5/revisions/2"
+                }
+            }),
+        ))
+        .unwrap();
+        match event.resource {
+            EventResource::WorkItemUpdated(resource) => {
+                assert_eq!(resource.update.work_item_id, Some(5));
+                assert_eq!(resource.update.rev, Some(2));
+                assert_eq!(
+                    resource.update.fields.as_ref().unwrap()["System.State"]["newValue"],
+                    "Approved"
+                );
+                let revision = resource.revision.unwrap();
+                assert_eq!(revision.id, 5);
+                assert_eq!(revision.fields["System.Title"], "Some great new idea!");
+            }
+            resource => panic!("unexpected resource {resource:?}"),
+        }
+    }
+
+    #[cfg(feature = "pipelines")]
+    #[test]
+    fn test_run_state_changed() {
+        let pipeline = json!({
+            "url": "https://dev.azure.com/fabrikam/6ce954b1/_apis/pipelines/4?revision=2",
+            "id": 4,
+            "revision": 2,
+            "name": "FabrikamFiber",
+            "folder": "\\"
+        });
+        let event = parse_event(&body(
+            "ms.vss-pipelines.run-state-changed-event",
+            json!({
+                "run": {
+                    "_links": {
+                        "self": { "href": "https://dev.azure.com/fabrikam/6ce954b1/_apis/pipelines/4/runs/11" },
+                        "web": { "href": "https://dev.azure.com/fabrikam/6ce954b1/_build/results?buildId=11" },
+                        "pipeline.web": { "href": "https://dev.azure.com/fabrikam/6ce954b1/_build/definition?definitionId=4" },
+                        "pipeline": { "href": "https://dev.azure.com/fabrikam/6ce954b1/_apis/pipelines/4?revision=2" }
+                    },
+                    "pipeline": pipeline,
+                    "state": "completed",
+                    "result": "succeeded",
+                    "createdDate": "2024-01-01T00:00:55.2096337Z",
+                    "finishedDate": "2024-01-01T00:01:24.6627187Z",
+                    "url": "https://dev.azure.com/fabrikam/6ce954b1/_apis/pipelines/4/runs/11",
+                    "resources": {
+                        "repositories": {
+                            "self": {
+                                "repository": {
+                                    "id": "278d5cd2-584d-4b63-824a-2ba458937249",
+                                    "type": "azureReposGit"
+                                },
+                                "refName": "refs/heads/main",
+                                "version": "600c52d2d5b655caa111abfd863e5a9bd304bb0e"
+                            }
+                        }
+                    },
+                    "id": 11,
+                    "name": "20240101.1"
+                },
+                "pipeline": pipeline,
+                "runId": 11,
+                "runUrl": "https://dev.azure.com/fabrikam/6ce954b1/_apis/pipelines/4/runs/11"
+            }),
+        ))
+        .unwrap();
+        match event.resource {
+            EventResource::RunStateChanged(resource) => {
+                assert_eq!(resource.run_id, Some(11));
+                assert_eq!(resource.run.run_reference.id, 11);
+                assert_eq!(
+                    resource.run.state,
+                    crate::pipelines::models::run::State::Completed
+                );
+                assert_eq!(
+                    resource
+                        .pipeline
+                        .map(|pipeline| pipeline.pipeline_base.name),
+                    Some("FabrikamFiber".to_string())
+                );
+            }
+            resource => panic!("unexpected resource {resource:?}"),
+        }
+    }
+}
@@ -0,0 +1,921 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Typed service hook subscriptions.
+//!
+//! [`NewSubscription`] builds the subscription to create, with constructors for common
+//! combinations such as a Git push or a completed build posted to a webhook.
+//! [`SubscriptionCatalog`] lists the publishers and consumers of an organization and validates
+//! a subscription's inputs against their input descriptors.
+//! `hooks::Client::reconcile_subscriptions()` creates, updates and deletes subscriptions to
+//! match a desired set.
+//!
+//! ```rust
+//!     let subscription = NewSubscription::git_push_to_webhook(&project_id, "https://example.com/hook")
+//!         .repository(&repository_id)
+//!         .branch("main")
+//!         .basic_auth("hooks", webhook_password);
+//!     let plan = hooks_client
+//!         .reconcile_subscriptions(&organization, [subscription])
+//!         .project(&project_id)
+//!         .await?;
+//!     println!("created {}, deleted {}", plan.create.len(), plan.delete.len());
+//! ```
+
+use crate::hooks::models::{
+    subscription, Consumer, ConsumerAction, EventTypeDescriptor, InputDescriptor, Publisher,
+    Subscription,
+};
+use crate::secret::Secret;
+use azure_core::error::{Error, ErrorKind};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+const TFS_PUBLISHER: &str = "tfs";
+const WEBHOOKS_CONSUMER: &str = "webHooks";
+const HTTP_REQUEST_ACTION: &str = "httpRequest";
+
+/// Publisher inputs that the service adds to the subscriptions it stores.
+const SERVICE_PUBLISHER_INPUTS: &[&str] = &["tfsSubscriptionId"];
+
+/// A service hook subscription to create.
+///
+/// Consumer input values are held as [`Secret`]s, as they include passwords and webhook URLs
+/// that embed tokens.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewSubscription {
+    publisher_id: String,
+    event_type: String,
+    consumer_id: String,
+    consumer_action_id: String,
+    resource_version: Option<String>,
+    publisher_inputs: BTreeMap<String, String>,
+    consumer_inputs: BTreeMap<String, Secret>,
+}
+
+impl NewSubscription {
+    pub fn new(
+        publisher_id: impl Into<String>,
+        event_type: impl Into<String>,
+        consumer_id: impl Into<String>,
+        consumer_action_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            publisher_id: publisher_id.into(),
+            event_type: event_type.into(),
+            consumer_id: consumer_id.into(),
+            consumer_action_id: consumer_action_id.into(),
+            resource_version: None,
+            publisher_inputs: BTreeMap::new(),
+            consumer_inputs: BTreeMap::new(),
+        }
+    }
+
+    /// Posts the events of a publisher to a webhook.
+    pub fn webhook(
+        publisher_id: impl Into<String>,
+        event_type: impl Into<String>,
+        url: impl Into<Secret>,
+    ) -> Self {
+        Self::new(
+            publisher_id,
+            event_type,
+            WEBHOOKS_CONSUMER,
+            HTTP_REQUEST_ACTION,
+        )
+        .consumer_input("url", url)
+    }
+
+    /// Posts the `git.push` events of a project to a webhook.
+    ///
+    /// Use [`repository()`](Self::repository) and [`branch()`](Self::branch) to filter the pushes.
+    pub fn git_push_to_webhook(project_id: impl Into<String>, url: impl Into<Secret>) -> Self {
+        Self::webhook(TFS_PUBLISHER, "git.push", url).publisher_input("projectId", project_id)
+    }
+
+    /// Posts the `build.complete` events of a project to a webhook.
+    ///
+    /// Use [`build_definition()`](Self::build_definition) and [`build_status()`](Self::build_status)
+    /// to filter the builds.
+    pub fn build_completed_to_webhook(
+        project_id: impl Into<String>,
+        url: impl Into<Secret>,
+    ) -> Self {
+        Self::webhook(TFS_PUBLISHER, "build.complete", url).publisher_input("projectId", project_id)
+    }
+
+    /// Sets a publisher input, e.g. `projectId`.
+    #[must_use]
+    pub fn publisher_input(mut self, id: impl Into<String>, value: impl Into<String>) -> Self {
+        self.publisher_inputs.insert(id.into(), value.into());
+        self
+    }
+
+    /// Sets a consumer input, e.g. `url`.
+    #[must_use]
+    pub fn consumer_input(mut self, id: impl Into<String>, value: impl Into<Secret>) -> Self {
+        self.consumer_inputs.insert(id.into(), value.into());
+        self
+    }
+
+    /// The version of the event resource to send (default: the latest).
+    #[must_use]
+    pub fn resource_version(mut self, resource_version: impl Into<String>) -> Self {
+        self.resource_version = Some(resource_version.into());
+        self
+    }
+
+    /// Only raises events for a Git repository (ID).
+    #[must_use]
+    pub fn repository(self, repository_id: impl Into<String>) -> Self {
+        self.publisher_input("repository", repository_id)
+    }
+
+    /// Only raises events for a Git branch, e.g. `main` or `refs/heads/main`.
+    #[must_use]
+    pub fn branch(self, branch: impl Into<String>) -> Self {
+        let branch = branch.into();
+        let branch = branch.strip_prefix("refs/heads/").unwrap_or(&branch);
+        self.publisher_input("branch", branch)
+    }
+
+    /// Only raises events for a build definition (name).
+    #[must_use]
+    pub fn build_definition(self, definition_name: impl Into<String>) -> Self {
+        self.publisher_input("definitionName", definition_name)
+    }
+
+    /// Only raises events for builds with a status, e.g. `Succeeded` or `Failed`.
+    #[must_use]
+    pub fn build_status(self, status: impl Into<String>) -> Self {
+        self.publisher_input("buildStatus", status)
+    }
+
+    /// Sets the basic authentication credentials of a webhook.
+    #[must_use]
+    pub fn basic_auth(self, username: impl Into<String>, password: impl Into<Secret>) -> Self {
+        self.consumer_input("basicAuthUsername", username.into())
+            .consumer_input("basicAuthPassword", password)
+    }
+
+    /// Adds an HTTP header to the requests of a webhook.
+    #[must_use]
+    pub fn http_header(self, name: &str, value: impl Into<Secret>) -> Self {
+        let value = value.into();
+        let headers = match self.consumer_inputs.get("httpHeaders") {
+            Some(headers) if !headers.secret().is_empty() => {
+                format!("{}\n{name}:{}", headers.secret(), value.secret())
+            }
+            _ => format!("{name}:{}", value.secret()),
+        };
+        self.consumer_input("httpHeaders", headers)
+    }
+
+    pub fn publisher_id(&self) -> &str {
+        &self.publisher_id
+    }
+
+    pub fn event_type(&self) -> &str {
+        &self.event_type
+    }
+
+    pub fn consumer_id(&self) -> &str {
+        &self.consumer_id
+    }
+
+    pub fn consumer_action_id(&self) -> &str {
+        &self.consumer_action_id
+    }
+
+    pub fn publisher_inputs(&self) -> &BTreeMap<String, String> {
+        &self.publisher_inputs
+    }
+
+    pub fn consumer_inputs(&self) -> &BTreeMap<String, Secret> {
+        &self.consumer_inputs
+    }
+
+    fn url(&self) -> Option<&str> {
+        self.consumer_inputs
+            .get("url")
+            .map(|url| url.secret().as_str())
+    }
+}
+
+impl From<NewSubscription> for Subscription {
+    fn from(new: NewSubscription) -> Self {
+        let publisher_inputs: Map<String, Value> = new
+            .publisher_inputs
+            .into_iter()
+            .map(|(id, value)| (id, Value::String(value)))
+            .collect();
+        let consumer_inputs: Map<String, Value> = new
+            .consumer_inputs
+            .iter()
+            .map(|(id, value)| (id.clone(), Value::String(value.secret().clone())))
+            .collect();
+        Subscription {
+            publisher_id: Some(new.publisher_id),
+            event_type: Some(new.event_type),
+            consumer_id: Some(new.consumer_id),
+            consumer_action_id: Some(new.consumer_action_id),
+            resource_version: new.resource_version,
+            publisher_inputs: Some(Value::Object(publisher_inputs)),
+            consumer_inputs: Some(Value::Object(consumer_inputs)),
+            ..Default::default()
+        }
+    }
+}
+
+/// The publishers and consumers of an organization.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SubscriptionCatalog {
+    publishers: Vec<Publisher>,
+    consumers: Vec<Consumer>,
+}
+
+impl SubscriptionCatalog {
+    pub fn new(publishers: Vec<Publisher>, consumers: Vec<Consumer>) -> Self {
+        Self {
+            publishers,
+            consumers,
+        }
+    }
+
+    pub fn publishers(&self) -> &[Publisher] {
+        &self.publishers
+    }
+
+    pub fn consumers(&self) -> &[Consumer] {
+        &self.consumers
+    }
+
+    pub fn publisher(&self, publisher_id: &str) -> Option<&Publisher> {
+        self.publishers
+            .iter()
+            .find(|publisher| id_eq(&publisher.id, publisher_id))
+    }
+
+    pub fn consumer(&self, consumer_id: &str) -> Option<&Consumer> {
+        self.consumers
+            .iter()
+            .find(|consumer| id_eq(&consumer.id, consumer_id))
+    }
+
+    pub fn event_type(&self, publisher_id: &str, event_type: &str) -> Option<&EventTypeDescriptor> {
+        self.publisher(publisher_id)?
+            .supported_events
+            .iter()
+            .find(|event| id_eq(&event.id, event_type))
+    }
+
+    pub fn consumer_action(&self, consumer_id: &str, action_id: &str) -> Option<&ConsumerAction> {
+        self.consumer(consumer_id)?
+            .actions
+            .iter()
+            .find(|action| id_eq(&action.id, action_id))
+    }
+
+    /// Checks that the publisher, event type, consumer and consumer action of a subscription
+    /// exist and that its inputs are valid for their input descriptors.
+    ///
+    /// Inputs must be known, required inputs must be set, and values must match the data type,
+    /// length and value range of their descriptor and, for inputs that are limited to a static
+    /// list of possible values, be one of those values. Patterns are not checked.
+    pub fn validate(&self, subscription: &NewSubscription) -> azure_core::Result<()> {
+        let mut problems = Vec::new();
+        match self.publisher(&subscription.publisher_id) {
+            None => problems.push(format!("unknown publisher {}", subscription.publisher_id)),
+            Some(publisher) => {
+                match self.event_type(&subscription.publisher_id, &subscription.event_type) {
+                    None => problems.push(format!(
+                        "publisher {} has no event type {}",
+                        subscription.publisher_id, subscription.event_type
+                    )),
+                    Some(event_type) => {
+                        let descriptors: Vec<&InputDescriptor> = publisher
+                            .input_descriptors
+                            .iter()
+                            .chain(&event_type.input_descriptors)
+                            .collect();
+                        let inputs = subscription
+                            .publisher_inputs
+                            .iter()
+                            .map(|(id, value)| (id.as_str(), value.as_str()))
+                            .collect();
+                        validate_inputs("publisher", &descriptors, &inputs, &mut problems);
+                    }
+                }
+            }
+        }
+        match self.consumer(&subscription.consumer_id) {
+            None => problems.push(format!("unknown consumer {}", subscription.consumer_id)),
+            Some(consumer) => match self
+                .consumer_action(&subscription.consumer_id, &subscription.consumer_action_id)
+            {
+                None => problems.push(format!(
+                    "consumer {} has no action {}",
+                    subscription.consumer_id, subscription.consumer_action_id
+                )),
+                Some(action) => {
+                    if !action.supported_event_types.is_empty()
+                        && !action.supported_event_types.iter().any(|event_type| {
+                            event_type == "*" || event_type == &subscription.event_type
+                        })
+                    {
+                        problems.push(format!(
+                            "consumer action {} does not support {} events",
+                            subscription.consumer_action_id, subscription.event_type
+                        ));
+                    }
+                    let descriptors: Vec<&InputDescriptor> = consumer
+                        .input_descriptors
+                        .iter()
+                        .chain(&action.input_descriptors)
+                        .collect();
+                    let inputs = subscription
+                        .consumer_inputs
+                        .iter()
+                        .map(|(id, value)| (id.as_str(), value.secret().as_str()))
+                        .collect();
+                    validate_inputs("consumer", &descriptors, &inputs, &mut problems);
+                }
+            },
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::with_message(ErrorKind::DataConversion, || {
+                format!(
+                    "invalid {} subscription: {}",
+                    subscription.event_type,
+                    problems.join("; ")
+                )
+            }))
+        }
+    }
+
+    /// The IDs of the confidential consumer inputs of a subscription, which the service
+    /// does not return.
+    fn confidential_inputs(&self, subscription: &NewSubscription) -> BTreeSet<String> {
+        let consumer = self.consumer(&subscription.consumer_id);
+        let action =
+            self.consumer_action(&subscription.consumer_id, &subscription.consumer_action_id);
+        consumer
+            .map(|consumer| consumer.input_descriptors.iter())
+            .into_iter()
+            .flatten()
+            .chain(
+                action
+                    .map(|action| action.input_descriptors.iter())
+                    .into_iter()
+                    .flatten(),
+            )
+            .filter(|descriptor| descriptor.is_confidential == Some(true))
+            .filter_map(|descriptor| descriptor.id.clone())
+            .collect()
+    }
+}
+
+fn id_eq(id: &Option<String>, expected: &str) -> bool {
+    id.as_deref()
+        .map_or(false, |id| id.eq_ignore_ascii_case(expected))
+}
+
+fn validate_inputs(
+    kind: &str,
+    descriptors: &[&InputDescriptor],
+    inputs: &BTreeMap<&str, &str>,
+    problems: &mut Vec<String>,
+) {
+    use crate::hooks::models::input_validation::DataType;
+
+    for id in inputs.keys() {
+        if !descriptors
+            .iter()
+            .any(|descriptor| id_eq(&descriptor.id, id))
+        {
+            problems.push(format!("unknown {kind} input {id}"));
+        }
+    }
+    for descriptor in descriptors {
+        let id = match descriptor.id.as_deref() {
+            Some(id) => id,
+            None => continue,
+        };
+        let value = inputs
+            .iter()
+            .find(|(input, _)| input.eq_ignore_ascii_case(id))
+            .map(|(_, value)| *value)
+            .filter(|value| !value.is_empty());
+        let validation = descriptor.validation.clone().unwrap_or_default();
+        let values = descriptor.values.clone().unwrap_or_default();
+        let value = match value {
+            Some(value) => value,
+            None => {
+                if validation.is_required == Some(true) && values.default_value.is_none() {
+                    problems.push(format!("missing required {kind} input {id}"));
+                }
+                continue;
+            }
+        };
+        let valid_type = match validation.data_type {
+            Some(DataType::Number) => value.parse::<f64>().map_or(false, |number| {
+                validation
+                    .min_value
+                    .map_or(true, |min| number >= f64::from(min))
+                    && validation
+                        .max_value
+                        .map_or(true, |max| number <= f64::from(max))
+            }),
+            Some(DataType::Boolean) => {
+                value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("false")
+            }
+            Some(DataType::Guid) => is_guid(value),
+            Some(DataType::Uri) => azure_core::Url::parse(value).is_ok(),
+            Some(DataType::None) | Some(DataType::String) | None => true,
+        };
+        if !valid_type {
+            problems.push(format!("{kind} input {id} has an invalid value"));
+        }
+        let length = value.chars().count();
+        if validation
+            .min_length
+            .map_or(false, |min| length < min as usize)
+            || validation
+                .max_length
+                .map_or(false, |max| length > max as usize)
+        {
+            problems.push(format!("{kind} input {id} has an invalid length"));
+        }
+        if values.is_limited_to_possible_values == Some(true)
+            && descriptor.has_dynamic_value_information != Some(true)
+            && !values.possible_values.is_empty()
+            && !values
+                .possible_values
+                .iter()
+                .any(|possible| possible.value.as_deref() == Some(value))
+        {
+            problems.push(format!(
+                "{kind} input {id} is not one of its possible values"
+            ));
+        }
+    }
+}
+
+fn is_guid(value: &str) -> bool {
+    let value = value.trim_start_matches('{').trim_end_matches('}');
+    let groups: Vec<&str> = value.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+impl crate::hooks::Client {
+    /// Lists the publishers and consumers of an organization, to validate subscriptions.
+    pub async fn subscription_catalog(
+        &self,
+        organization: impl Into<String>,
+    ) -> azure_core::Result<SubscriptionCatalog> {
+        let organization = organization.into();
+        let publishers = self.publishers_client().list(&organization).await?.value;
+        let consumers = self.consumers_client().list(&organization).await?.value;
+        Ok(SubscriptionCatalog::new(publishers, consumers))
+    }
+
+    /// Creates, updates and deletes service hook subscriptions to match a desired set.
+    ///
+    /// Existing subscriptions are matched to the desired subscriptions by publisher, event type,
+    /// consumer, consumer action, publisher inputs and consumer URL. Unmatched existing
+    /// subscriptions with the consumer and consumer action of a desired subscription are deleted
+    /// if they post to the URL of a desired subscription, or belong to a project added with
+    /// [`ReconcileSubscriptionsBuilder::project()`]. Other subscriptions (e.g. email
+    /// notifications set up by someone else) are left alone.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `desired`: The subscriptions that should exist.
+    pub fn reconcile_subscriptions(
+        &self,
+        organization: impl Into<String>,
+        desired: impl IntoIterator<Item = NewSubscription>,
+    ) -> ReconcileSubscriptionsBuilder {
+        ReconcileSubscriptionsBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            desired: desired.into_iter().collect(),
+            projects: Vec::new(),
+        }
+    }
+}
+
+/// The changes to make to the subscriptions of an organization.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReconcilePlan {
+    /// Subscriptions to create.
+    pub create: Vec<NewSubscription>,
+    /// Subscriptions to replace, by ID, because their consumer inputs or resource version
+    /// changed or they were disabled.
+    pub update: Vec<(String, NewSubscription)>,
+    /// IDs of subscriptions to delete.
+    pub delete: Vec<String>,
+    /// IDs of subscriptions that already match.
+    pub unchanged: Vec<String>,
+}
+
+impl ReconcilePlan {
+    pub fn is_empty(&self) -> bool {
+        self.create.is_empty() && self.update.is_empty() && self.delete.is_empty()
+    }
+}
+
+/// `ReconcileSubscriptionsBuilder` provides a mechanism for setting the scope of a
+/// reconciliation.
+///
+/// To apply the changes, call `.await`, which returns the [`ReconcilePlan`] that was applied.
+/// To only compute the changes, call [`plan()`](Self::plan). All desired subscriptions are
+/// validated against the [`SubscriptionCatalog`] before any change is made.
+#[derive(Clone)]
+pub struct ReconcileSubscriptionsBuilder {
+    client: crate::hooks::Client,
+    organization: String,
+    desired: Vec<NewSubscription>,
+    projects: Vec<String>,
+}
+
+impl ReconcileSubscriptionsBuilder {
+    /// Also deletes the unmatched subscriptions of a project (ID) that have the consumer and
+    /// consumer action of a desired subscription.
+    #[must_use]
+    pub fn project(mut self, project_id: impl Into<String>) -> Self {
+        self.projects.push(project_id.into());
+        self
+    }
+
+    /// Computes the changes without applying them.
+    pub fn plan(self) -> BoxFuture<'static, azure_core::Result<ReconcilePlan>> {
+        Box::pin(async move {
+            let catalog = self.client.subscription_catalog(&self.organization).await?;
+            for subscription in &self.desired {
+                catalog.validate(subscription)?;
+            }
+            let existing = self
+                .client
+                .subscriptions_client()
+                .list(&self.organization)
+                .await?
+                .value;
+            Ok(plan_reconcile(
+                &existing,
+                &self.desired,
+                &self.projects,
+                &catalog,
+            ))
+        })
+    }
+}
+
+impl std::future::IntoFuture for ReconcileSubscriptionsBuilder {
+    type Output = azure_core::Result<ReconcilePlan>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<ReconcilePlan>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            let subscriptions = self.client.subscriptions_client();
+            let organization = self.organization.clone();
+            let plan = self.plan().await?;
+            for subscription in &plan.create {
+                subscriptions
+                    .create(&organization, Subscription::from(subscription.clone()))
+                    .await?;
+            }
+            for (id, subscription) in &plan.update {
+                let body = Subscription {
+                    id: Some(id.clone()),
+                    status: Some(subscription::Status::Enabled),
+                    ..Subscription::from(subscription.clone())
+                };
+                subscriptions
+                    .replace_subscription(&organization, body, id)
+                    .await?;
+            }
+            for id in &plan.delete {
+                subscriptions.delete(&organization, id).await?;
+            }
+            Ok(plan)
+        })
+    }
+}
+
+/// The string inputs of an existing subscription.
+fn inputs(inputs: &Option<Value>) -> BTreeMap<String, String> {
+    let inputs = match inputs {
+        Some(Value::Object(inputs)) => inputs,
+        _ => return BTreeMap::new(),
+    };
+    inputs
+        .iter()
+        .map(|(id, value)| {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                Value::Null => String::new(),
+                value => value.to_string(),
+            };
+            (id.clone(), value)
+        })
+        .collect()
+}
+
+fn input_value<'a>(inputs: &'a BTreeMap<String, String>, id: &str) -> &'a str {
+    inputs
+        .iter()
+        .find(|(input, _)| input.eq_ignore_ascii_case(id))
+        .map_or("", |(_, value)| value.as_str())
+}
+
+/// Whether an existing subscription is the same subscription as a desired one (it may still
+/// differ in consumer inputs other than the URL).
+fn is_match(existing: &Subscription, desired: &NewSubscription) -> bool {
+    let same_id = |id: &Option<String>, expected: &str| id_eq(id, expected);
+    if !(same_id(&existing.publisher_id, &desired.publisher_id)
+        && existing.event_type.as_deref() == Some(desired.event_type.as_str())
+        && same_id(&existing.consumer_id, &desired.consumer_id)
+        && same_id(&existing.consumer_action_id, &desired.consumer_action_id))
+    {
+        return false;
+    }
+    let publisher_inputs = inputs(&existing.publisher_inputs);
+    let same_publisher_inputs = desired
+        .publisher_inputs
+        .iter()
+        .all(|(id, value)| input_value(&publisher_inputs, id) == value)
+        && publisher_inputs.iter().all(|(id, value)| {
+            value.is_empty()
+                || SERVICE_PUBLISHER_INPUTS.contains(&id.as_str())
+                || desired
+                    .publisher_inputs
+                    .keys()
+                    .any(|desired| desired.eq_ignore_ascii_case(id))
+        });
+    let consumer_inputs = inputs(&existing.consumer_inputs);
+    same_publisher_inputs
+        && input_value(&consumer_inputs, "url") == desired.url().unwrap_or_default()
+}
+
+/// Whether a matched subscription must be replaced.
+fn needs_update(
+    existing: &Subscription,
+    desired: &NewSubscription,
+    confidential: &BTreeSet<String>,
+) -> bool {
+    let consumer_inputs = inputs(&existing.consumer_inputs);
+    let inputs_changed = desired
+        .consumer_inputs
+        .iter()
+        .filter(|(id, _)| !confidential.contains(*id))
+        .any(|(id, value)| input_value(&consumer_inputs, id) != value.secret());
+    let version_changed = desired.resource_version.as_ref().map_or(false, |version| {
+        existing.resource_version.as_ref() != Some(version)
+    });
+    let disabled = matches!(
+        existing.status,
+        Some(subscription::Status::DisabledByUser)
+            | Some(subscription::Status::DisabledBySystem)
+            | Some(subscription::Status::DisabledByInactiveIdentity)
+    );
+    inputs_changed || version_changed || disabled
+}
+
+fn plan_reconcile(
+    existing: &[Subscription],
+    desired: &[NewSubscription],
+    projects: &[String],
+    catalog: &SubscriptionCatalog,
+) -> ReconcilePlan {
+    let mut plan = ReconcilePlan::default();
+    let mut matched = vec![false; existing.len()];
+    for subscription in desired {
+        let found = existing
+            .iter()
+            .enumerate()
+            .find(|(index, existing)| !matched[*index] && is_match(existing, subscription));
+        match found {
+            Some((index, existing)) => {
+                matched[index] = true;
+                let id = existing.id.clone().unwrap_or_default();
+                let confidential = catalog.confidential_inputs(subscription);
+                if needs_update(existing, subscription, &confidential) {
+                    plan.update.push((id, subscription.clone()));
+                } else {
+                    plan.unchanged.push(id);
+                }
+            }
+            None => plan.create.push(subscription.clone()),
+        }
+    }
+    let urls: BTreeSet<&str> = desired.iter().filter_map(NewSubscription::url).collect();
+    for (existing, _) in existing.iter().zip(matched).filter(|(_, matched)| !matched) {
+        let same_consumer = desired.iter().any(|desired| {
+            id_eq(&existing.consumer_id, &desired.consumer_id)
+                && id_eq(&existing.consumer_action_id, &desired.consumer_action_id)
+        });
+        if !same_consumer {
+            continue;
+        }
+        let consumer_inputs = inputs(&existing.consumer_inputs);
+        let project_id = input_value(&inputs(&existing.publisher_inputs), "projectId").to_string();
+        let managed = urls.contains(input_value(&consumer_inputs, "url"))
+            || projects
+                .iter()
+                .any(|project| project.eq_ignore_ascii_case(&project_id));
+        if let (true, Some(id)) = (managed, &existing.id) {
+            plan.delete.push(id.clone());
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PROJECT: &str = "6ce954b1-ce1f-45d1-b94d-e6bf2464ba2c";
+    const URL: &str = "https://example.com/hook";
+
+    fn catalog() -> SubscriptionCatalog {
+        let publisher: Publisher = serde_json::from_value(json!({
+            "id": "tfs",
+            "supportedEvents": [{
+                "id": "git.push",
+                "inputDescriptors": [
+                    {
+                        "id": "projectId",
+                        "validation": { "dataType": "guid", "isRequired": true }
+                    },
+                    { "id": "repository", "hasDynamicValueInformation": true },
+                    { "id": "branch", "hasDynamicValueInformation": true }
+                ]
+            }]
+        }))
+        .unwrap();
+        let consumer: Consumer = serde_json::from_value(json!({
+            "id": "webHooks",
+            "inputDescriptors": [
+                {
+                    "id": "url",
+                    "validation": { "dataType": "uri", "isRequired": true }
+                },
+                { "id": "basicAuthUsername" },
+                { "id": "basicAuthPassword", "isConfidential": true },
+                {
+                    "id": "resourceDetailsToSend",
+                    "values": {
+                        "defaultValue": "all",
+                        "isLimitedToPossibleValues": true,
+                        "possibleValues": [{ "value": "all" }, { "value": "none" }]
+                    }
+                }
+            ],
+            "actions": [{ "id": "httpRequest", "supportedEventTypes": ["git.push"] }]
+        }))
+        .unwrap();
+        SubscriptionCatalog::new(vec![publisher], vec![consumer])
+    }
+
+    fn existing(id: &str, publisher_inputs: Value, consumer_inputs: Value) -> Subscription {
+        Subscription {
+            id: Some(id.to_string()),
+            publisher_id: Some("tfs".to_string()),
+            event_type: Some("git.push".to_string()),
+            consumer_id: Some("webHooks".to_string()),
+            consumer_action_id: Some("httpRequest".to_string()),
+            publisher_inputs: Some(publisher_inputs),
+            consumer_inputs: Some(consumer_inputs),
+            status: Some(subscription::Status::Enabled),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_git_push_to_webhook() {
+        let subscription = NewSubscription::git_push_to_webhook(PROJECT, URL)
+            .branch("refs/heads/main")
+            .basic_auth("hooks", "s3cret");
+        assert!(!format!("{subscription:?}").contains("s3cret"));
+        assert_eq!(
+            serde_json::to_value(Subscription::from(subscription)).unwrap(),
+            json!({
+                "publisherId": "tfs",
+                "eventType": "git.push",
+                "consumerId": "webHooks",
+                "consumerActionId": "httpRequest",
+                "publisherInputs": { "projectId": PROJECT, "branch": "main" },
+                "consumerInputs": {
+                    "url": URL,
+                    "basicAuthUsername": "hooks",
+                    "basicAuthPassword": "s3cret"
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        let catalog = catalog();
+        let valid = NewSubscription::git_push_to_webhook(PROJECT, URL).branch("main");
+        catalog.validate(&valid).unwrap();
+
+        let invalid = NewSubscription::git_push_to_webhook("not-a-guid", URL)
+            .consumer_input("resourceDetailsToSend", "some")
+            .consumer_input("colour", "blue");
+        let message = catalog.validate(&invalid).unwrap_err().to_string();
+        assert!(message.contains("publisher input projectId has an invalid value"));
+        assert!(message.contains("not one of its possible values"));
+        assert!(message.contains("unknown consumer input colour"));
+
+        let missing = NewSubscription::webhook("tfs", "git.push", URL);
+        let message = catalog.validate(&missing).unwrap_err().to_string();
+        assert!(message.contains("missing required publisher input projectId"));
+
+        let unsupported = NewSubscription::build_completed_to_webhook(PROJECT, URL);
+        assert!(catalog.validate(&unsupported).is_err());
+    }
+
+    #[test]
+    fn test_plan_reconcile() {
+        let existing = [
+            existing(
+                "unchanged",
+                json!({ "projectId": PROJECT, "branch": "main", "tfsSubscriptionId": "x" }),
+                json!({ "url": URL, "basicAuthUsername": "hooks", "basicAuthPassword": "********" }),
+            ),
+            existing(
+                "updated",
+                json!({ "projectId": PROJECT, "branch": "release" }),
+                json!({ "url": URL, "basicAuthUsername": "old" }),
+            ),
+            existing(
+                "stale",
+                json!({ "projectId": PROJECT, "branch": "dev" }),
+                json!({ "url": URL }),
+            ),
+            existing(
+                "unmanaged",
+                json!({ "projectId": "other" }),
+                json!({ "url": "https://example.com/other" }),
+            ),
+        ];
+        let desired = [
+            NewSubscription::git_push_to_webhook(PROJECT, URL)
+                .branch("main")
+                .basic_auth("hooks", "new password"),
+            NewSubscription::git_push_to_webhook(PROJECT, URL)
+                .branch("release")
+                .basic_auth("hooks", "s3cret"),
+            NewSubscription::git_push_to_webhook(PROJECT, URL),
+        ];
+        let plan = plan_reconcile(&existing, &desired, &[], &catalog());
+        assert_eq!(plan.unchanged, ["unchanged"]);
+        assert_eq!(plan.update.len(), 1);
+        assert_eq!(plan.update[0].0, "updated");
+        assert_eq!(plan.create, [desired[2].clone()]);
+        assert_eq!(plan.delete, ["stale"]);
+
+        let plan = plan_reconcile(&existing, &desired, &["other".to_string()], &catalog());
+        assert_eq!(plan.delete, ["stale", "unmanaged"]);
+    }
+
+    #[test]
+    fn test_plan_reconcile_keeps_other_consumers() {
+        let email = Subscription {
+            id: Some("email".to_string()),
+            publisher_id: Some("tfs".to_string()),
+            event_type: Some("git.pullrequest.created".to_string()),
+            consumer_id: Some("email".to_string()),
+            consumer_action_id: Some("sendEmail".to_string()),
+            publisher_inputs: Some(json!({ "projectId": PROJECT })),
+            consumer_inputs: Some(json!({ "address": "team@example.com" })),
+            ..Default::default()
+        };
+        let existing = [
+            email,
+            existing(
+                "stale",
+                json!({ "projectId": PROJECT, "branch": "dev" }),
+                json!({ "url": "https://example.com/old" }),
+            ),
+        ];
+        let desired = [NewSubscription::git_push_to_webhook(PROJECT, URL)];
+        let plan = plan_reconcile(&existing, &desired, &[PROJECT.to_string()], &catalog());
+        assert_eq!(plan.create, desired);
+        assert_eq!(plan.delete, ["stale"]);
+    }
+}
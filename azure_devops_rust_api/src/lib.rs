@@ -134,9 +134,86 @@ pub mod work;
 mod auth;
 pub use auth::Credential;
 
+/// Pending approvals of YAML pipeline runs
+#[cfg(feature = "approvals_and_checks")]
+pub mod approvals_and_checks_pending;
+/// Download build and pipeline artifacts
+#[cfg(all(
+    any(feature = "build_artifacts", feature = "pipelines_artifacts"),
+    not(target_arch = "wasm32")
+))]
+pub mod build_artifacts;
+/// Follow the logs of a running build
+#[cfg(feature = "build")]
+pub mod build_logs;
+/// Queue a build and follow it to completion
+#[cfg(feature = "build")]
+pub mod build_run;
+/// Build timeline tree model
+#[cfg(feature = "build")]
+pub mod build_timeline;
 pub mod date_time;
+/// Agent pool monitor
+#[cfg(feature = "distributed_task")]
+pub mod distributed_task_pools;
+/// Variable group editor
+#[cfg(feature = "distributed_task")]
+pub mod distributed_task_variable_groups;
+/// Git commit builder
+#[cfg(feature = "git")]
+pub mod git_commit;
+/// Unified diffs between commits, branches and pull request iterations
+#[cfg(feature = "git")]
+pub mod git_diff;
+/// Pull request comment threads anchored to file positions
+#[cfg(feature = "git")]
+pub mod git_pr_threads;
+/// Pull request lifecycle orchestration
+#[cfg(feature = "git")]
+pub mod git_pr_workflow;
+/// Branch and tag management with compare-and-swap semantics
+#[cfg(feature = "git")]
+pub mod git_refs;
+/// Graph subject descriptor and identity descriptor parsing and conversion
+#[cfg(feature = "graph")]
+pub mod graph_descriptors;
+/// Transitive group membership resolution
+#[cfg(feature = "graph")]
+pub mod graph_memberships;
 pub mod headers;
+/// Typed service hook event payloads and webhook parser
+#[cfg(feature = "hooks")]
+pub mod hooks_events;
+/// Typed service hook subscriptions
+#[cfg(feature = "hooks")]
+pub mod hooks_subscriptions;
+/// Permissions report generation and download
+#[cfg(feature = "permissions_report")]
+pub mod permissions_report_generate;
+/// Typed pipeline run parameters
+#[cfg(feature = "pipelines")]
+pub mod pipelines_run;
+/// Release deployment orchestration
+#[cfg(feature = "release")]
+pub mod release_deploy;
+/// Secret values, redacted in `Debug` output
+pub mod secret;
+/// Effective permission evaluation from security namespaces and ACLs
+#[cfg(feature = "security")]
+pub mod security_permissions;
+/// Security token builders for common namespaces
+#[cfg(feature = "security")]
+pub mod security_tokens;
+/// Typed service endpoint authorization schemes and endpoint data
+#[cfg(feature = "service_endpoint")]
+pub mod service_endpoint_types;
 pub mod telemetry;
+/// Streaming work item attachment upload and download
+#[cfg(feature = "wit")]
+pub mod wit_attachments;
+/// Work item link graph traversal
+#[cfg(feature = "wit")]
+pub mod wit_graph;
 
 pub(crate) mod serde;
 
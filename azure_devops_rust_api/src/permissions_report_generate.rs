@@ -0,0 +1,560 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Generate and download permissions reports.
+//!
+//! A permissions report is created asynchronously by the service: it must be requested, polled
+//! until it is ready and then downloaded. `permissions_report::Client::generate_report()` does
+//! all three, polling with exponential backoff, and parses the downloaded JSON as it streams in
+//! into a [`ResourcePermissions`] record for each user (or group) and resource.
+//!
+//! ```rust
+//!     let mut records = permissions_report_client
+//!         .generate_report(&organization, "repo-audit-2024-06")
+//!         .repository(&repository_id, "my-repo")
+//!         .descriptor(&user_descriptor)
+//!         .stream();
+//!     while let Some(record) = records.next().await {
+//!         let record = record?;
+//!         for permission in record.permissions.iter().filter(|p| p.is_allowed()) {
+//!             println!("{} {}: {}", record.account_name, record.resource_name, permission.name);
+//!         }
+//!     }
+//! ```
+
+use crate::permissions_report::models::{
+    permissions_report::ReportStatus, permissions_report_resource::ResourceType, PermissionsReport,
+    PermissionsReportRequest, PermissionsReportResource,
+};
+use azure_core::error::{Error, ErrorKind, ResultExt};
+use futures::StreamExt;
+use serde::Deserialize;
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream::BoxStream;
+#[cfg(target_arch = "wasm32")]
+use futures::stream::LocalBoxStream as BoxStream;
+
+/// The permissions of a user or group on a resource, from a permissions report.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResourcePermissions {
+    /// The identity descriptor of the user or group.
+    pub descriptor: String,
+    pub account_name: String,
+    pub display_name: String,
+    pub resource_id: String,
+    pub resource_name: String,
+    /// The resource type, e.g. `repo` or `ref`.
+    pub resource_type: String,
+    pub permissions: Vec<ReportedPermission>,
+}
+
+/// A permission in a permissions report.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReportedPermission {
+    #[serde(alias = "permissionName", alias = "displayName")]
+    pub name: String,
+    #[serde(alias = "permissionBit")]
+    pub bit: i32,
+    pub namespace_id: String,
+    /// The permission's status as reported, e.g. `Allow`, `Deny`, `Inherited allow` or
+    /// `Not set`.
+    #[serde(alias = "permissionStatus", alias = "status")]
+    pub effective_permission: String,
+}
+
+impl ReportedPermission {
+    /// Whether the permission is allowed, explicitly or by inheritance.
+    pub fn is_allowed(&self) -> bool {
+        self.effective_permission
+            .to_ascii_lowercase()
+            .replace(' ', "")
+            .ends_with("allow")
+    }
+}
+
+/// A user or group entry of the downloaded report.
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ReportEntry {
+    descriptor: String,
+    account_name: String,
+    display_name: String,
+    #[serde(alias = "resourcePermissions")]
+    resources: Vec<ReportEntryResource>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ReportEntryResource {
+    resource_id: String,
+    resource_name: String,
+    resource_type: String,
+    permissions: Vec<ReportedPermission>,
+}
+
+impl ReportEntry {
+    fn into_records(self) -> impl Iterator<Item = ResourcePermissions> {
+        let (descriptor, account_name, display_name) =
+            (self.descriptor, self.account_name, self.display_name);
+        self.resources
+            .into_iter()
+            .map(move |resource| ResourcePermissions {
+                descriptor: descriptor.clone(),
+                account_name: account_name.clone(),
+                display_name: display_name.clone(),
+                resource_id: resource.resource_id,
+                resource_name: resource.resource_name,
+                resource_type: resource.resource_type,
+                permissions: resource.permissions,
+            })
+    }
+}
+
+impl crate::permissions_report::Client {
+    /// Creates a builder that requests a permissions report, waits for it and downloads it.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `report_name`: A unique name for the report, used to find it once it is requested.
+    pub fn generate_report(
+        &self,
+        organization: impl Into<String>,
+        report_name: impl Into<String>,
+    ) -> GenerateReportBuilder {
+        GenerateReportBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            request: PermissionsReportRequest {
+                report_name: Some(report_name.into()),
+                ..Default::default()
+            },
+            poll_interval: Duration::from_secs(2),
+            max_poll_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(30 * 60),
+        }
+    }
+}
+
+/// `GenerateReportBuilder` provides a mechanism for setting the resources and descriptors of a
+/// permissions report.
+///
+/// Call [`submit()`](Self::submit) to request the report and wait for it to be ready, or
+/// [`stream()`](Self::stream) to also download it. Invoking `.await` downloads all records.
+#[derive(Clone)]
+pub struct GenerateReportBuilder {
+    client: crate::permissions_report::Client,
+    organization: String,
+    request: PermissionsReportRequest,
+    poll_interval: Duration,
+    max_poll_interval: Duration,
+    timeout: Duration,
+}
+
+impl GenerateReportBuilder {
+    /// Adds a resource to report permissions on.
+    #[must_use]
+    pub fn resource(mut self, resource_type: ResourceType, resource_id: impl Into<String>) -> Self {
+        self.request.resources.push(PermissionsReportResource {
+            resource_id: Some(resource_id.into()),
+            resource_name: None,
+            resource_type: Some(resource_type),
+        });
+        self
+    }
+
+    /// Adds a Git repository (ID and name) to report permissions on.
+    #[must_use]
+    pub fn repository(
+        mut self,
+        repository_id: impl Into<String>,
+        repository_name: impl Into<String>,
+    ) -> Self {
+        self.request.resources.push(PermissionsReportResource {
+            resource_id: Some(repository_id.into()),
+            resource_name: Some(repository_name.into()),
+            resource_type: Some(ResourceType::Repo),
+        });
+        self
+    }
+
+    /// Adds a user or group (subject descriptor) to report permissions for. By default the
+    /// report covers all users and groups of the organization.
+    #[must_use]
+    pub fn descriptor(mut self, descriptor: impl Into<String>) -> Self {
+        self.request.descriptors.push(descriptor.into());
+        self
+    }
+
+    /// Initial interval between checks of the report status (default 2 seconds), doubled after
+    /// each check up to [`max_poll_interval()`](Self::max_poll_interval).
+    #[must_use]
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Maximum interval between checks of the report status (default 30 seconds).
+    #[must_use]
+    pub fn max_poll_interval(mut self, max_poll_interval: Duration) -> Self {
+        self.max_poll_interval = max_poll_interval;
+        self
+    }
+
+    /// How long to wait for the report to be ready (default 30 minutes).
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Requests the report and waits until it has completed successfully.
+    pub fn submit(self) -> BoxFuture<'static, azure_core::Result<PermissionsReport>> {
+        Box::pin(async move {
+            let reports = self.client.permissions_report_client();
+            let report_name = self.request.report_name.clone().unwrap_or_default();
+            let existing: HashSet<String> = reports
+                .list(&self.organization)
+                .await?
+                .value
+                .into_iter()
+                .filter(|report| report.report_name.as_deref() == Some(&report_name))
+                .filter_map(|report| report.id)
+                .collect();
+            reports
+                .create(&self.organization, self.request.clone())
+                .await?;
+
+            let mut interval = self.poll_interval;
+            let mut waited = Duration::ZERO;
+            loop {
+                let listed = reports.list(&self.organization).await?.value;
+                match find_report(listed, &report_name, &existing) {
+                    Some(report)
+                        if report.report_status == Some(ReportStatus::CompletedSuccessfully) =>
+                    {
+                        return Ok(report)
+                    }
+                    Some(PermissionsReport {
+                        report_status:
+                            Some(ReportStatus::CompletedWithErrors) | Some(ReportStatus::Deleted),
+                        error,
+                        ..
+                    }) => {
+                        let error = error.unwrap_or_default();
+                        return Err(Error::with_message(ErrorKind::Other, || {
+                            format!("permissions report {report_name} failed: {error}")
+                        }));
+                    }
+                    _ => {}
+                }
+                if waited >= self.timeout {
+                    return Err(Error::with_message(ErrorKind::Other, || {
+                        format!("permissions report {report_name} was not ready in time")
+                    }));
+                }
+                azure_core::sleep::sleep(interval).await;
+                waited += interval;
+                interval = (interval * 2).min(self.max_poll_interval);
+            }
+        })
+    }
+
+    /// Requests the report, waits for it and streams its records as it is downloaded.
+    pub fn stream(self) -> BoxStream<'static, azure_core::Result<ResourcePermissions>> {
+        let state = DownloadState {
+            builder: Some(self),
+            body: None,
+            splitter: ArraySplitter::default(),
+            pending: VecDeque::new(),
+            done: false,
+        };
+        Box::pin(futures::stream::unfold(state, |mut state| async move {
+            loop {
+                if let Some(record) = state.pending.pop_front() {
+                    return Some((Ok(record), state));
+                }
+                if state.done {
+                    return None;
+                }
+                if let Err(e) = state.read().await {
+                    state.done = true;
+                    return Some((Err(e), state));
+                }
+            }
+        }))
+    }
+}
+
+/// Finds the latest report with the given name whose ID is not in `existing` (the reports with
+/// that name listed before the request). The create response has no ID, so the report is found
+/// by its name; older reports with the same name are ignored.
+fn find_report(
+    reports: Vec<PermissionsReport>,
+    report_name: &str,
+    existing: &HashSet<String>,
+) -> Option<PermissionsReport> {
+    reports
+        .into_iter()
+        .filter(|report| report.report_name.as_deref() == Some(report_name))
+        .filter(|report| {
+            report
+                .id
+                .as_ref()
+                .map_or(false, |id| !existing.contains(id))
+        })
+        .max_by_key(|report| report.requested_time)
+}
+
+impl std::future::IntoFuture for GenerateReportBuilder {
+    type Output = azure_core::Result<Vec<ResourcePermissions>>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<Vec<ResourcePermissions>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        use futures::TryStreamExt;
+        Box::pin(self.stream().try_collect())
+    }
+}
+
+struct DownloadState {
+    /// Taken once the report has been submitted.
+    builder: Option<GenerateReportBuilder>,
+    body: Option<azure_core::ResponseBody>,
+    splitter: ArraySplitter,
+    pending: VecDeque<ResourcePermissions>,
+    done: bool,
+}
+
+impl DownloadState {
+    async fn read(&mut self) -> azure_core::Result<()> {
+        if let Some(builder) = self.builder.take() {
+            let client = builder.client.clone();
+            let organization = builder.organization.clone();
+            let report = builder.submit().await?;
+            let id = report.id.unwrap_or_default();
+            let response = client
+                .permissions_report_download_client()
+                .download(organization, id)
+                .send()
+                .await?;
+            self.body = Some(response.into_raw_response().into_body());
+        }
+        let chunk = match self.body.as_mut() {
+            Some(body) => body.next().await.transpose()?,
+            None => None,
+        };
+        match chunk {
+            Some(chunk) => {
+                for element in self.splitter.push(&chunk)? {
+                    let entry: ReportEntry = serde_json::from_slice(&element).context(
+                        ErrorKind::DataConversion,
+                        "invalid permissions report entry",
+                    )?;
+                    self.pending.extend(entry.into_records());
+                }
+            }
+            None => {
+                self.splitter.finish()?;
+                self.done = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a JSON array arriving in chunks into its elements.
+#[derive(Default)]
+struct ArraySplitter {
+    started: bool,
+    ended: bool,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+    element: Vec<u8>,
+}
+
+impl ArraySplitter {
+    fn invalid() -> Error {
+        Error::message(
+            ErrorKind::DataConversion,
+            "permissions report is not a JSON array",
+        )
+    }
+
+    /// Consumes a chunk, returning the elements it completes.
+    fn push(&mut self, chunk: &[u8]) -> azure_core::Result<Vec<Vec<u8>>> {
+        let mut elements = Vec::new();
+        for &byte in chunk {
+            if !self.started {
+                match byte {
+                    b'[' => self.started = true,
+                    // Byte order mark and whitespace.
+                    0xEF | 0xBB | 0xBF => {}
+                    byte if byte.is_ascii_whitespace() => {}
+                    _ => return Err(Self::invalid()),
+                }
+                continue;
+            }
+            if self.ended {
+                if !byte.is_ascii_whitespace() {
+                    return Err(Self::invalid());
+                }
+                continue;
+            }
+            if self.in_string {
+                self.element.push(byte);
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                }
+                continue;
+            }
+            match byte {
+                b',' | b']' if self.depth == 0 => {
+                    if !self.element.iter().all(u8::is_ascii_whitespace) {
+                        elements.push(std::mem::take(&mut self.element));
+                    }
+                    self.element.clear();
+                    self.ended = byte == b']';
+                }
+                _ => {
+                    match byte {
+                        b'"' => self.in_string = true,
+                        b'{' | b'[' => self.depth += 1,
+                        b'}' | b']' => self.depth = self.depth.saturating_sub(1),
+                        _ => {}
+                    }
+                    self.element.push(byte);
+                }
+            }
+        }
+        Ok(elements)
+    }
+
+    /// Checks that the array was complete.
+    fn finish(&self) -> azure_core::Result<()> {
+        if self.started && !self.ended {
+            return Err(Self::invalid());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn report() -> Vec<u8> {
+        serde_json::to_vec(&json!([
+            {
+                "descriptor": "Microsoft.IdentityModel.Claims.ClaimsIdentity;user",
+                "accountName": "user@example.com",
+                "displayName": "User \"[1]\"",
+                "resources": [
+                    {
+                        "resourceId": "repo-id",
+                        "resourceName": "repo",
+                        "resourceType": "repo",
+                        "permissions": [
+                            { "permissionName": "Read", "permissionBit": 2, "namespaceId": "ns", "effectivePermission": "Inherited allow" },
+                            { "permissionName": "Contribute", "permissionBit": 4, "namespaceId": "ns", "effectivePermission": "Not set" }
+                        ]
+                    },
+                    { "resourceId": "other-id", "resourceName": "other", "resourceType": "repo", "permissions": [] }
+                ]
+            },
+            { "descriptor": "group", "accountName": "[group]", "resources": [] }
+        ]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_find_report_ignores_earlier_reports() {
+        let report = |name: &str, requested_time: &str, status: &str| -> PermissionsReport {
+            serde_json::from_value(json!({
+                "id": format!("{name}-{requested_time}"),
+                "reportName": name,
+                "requestedTime": requested_time,
+                "reportStatus": status,
+            }))
+            .unwrap()
+        };
+        let existing = HashSet::from(["audit-2024-06-01T11:00:00Z".to_string()]);
+        let reports = vec![
+            report("audit", "2024-06-01T11:00:00Z", "completedSuccessfully"),
+            report("other", "2024-06-01T12:00:02Z", "completedSuccessfully"),
+        ];
+        assert!(find_report(reports.clone(), "audit", &existing).is_none());
+
+        // The server clock may be behind the local one, so only the ID tells the reports apart.
+        let mut reports = reports;
+        reports.push(report("audit", "2024-06-01T10:59:59Z", "inProgress"));
+        let found = find_report(reports, "audit", &existing).unwrap();
+        assert_eq!(found.report_status, Some(ReportStatus::InProgress));
+    }
+
+    #[test]
+    fn test_split_in_chunks() {
+        let report = report();
+        for chunk_size in [1, 7, report.len()] {
+            let mut splitter = ArraySplitter::default();
+            let mut elements = Vec::new();
+            for chunk in report.chunks(chunk_size) {
+                elements.extend(splitter.push(chunk).unwrap());
+            }
+            splitter.finish().unwrap();
+            assert_eq!(elements.len(), 2, "chunk size {chunk_size}");
+            let entry: ReportEntry = serde_json::from_slice(&elements[0]).unwrap();
+            assert_eq!(entry.display_name, "User \"[1]\"");
+        }
+    }
+
+    #[test]
+    fn test_split_errors() {
+        let mut splitter = ArraySplitter::default();
+        assert!(splitter.push(b"{}").is_err());
+        let mut splitter = ArraySplitter::default();
+        splitter.push(b"[{\"a\": 1}").unwrap();
+        assert!(splitter.finish().is_err());
+        let mut splitter = ArraySplitter::default();
+        assert!(splitter.push(b" [ ] ").unwrap().is_empty());
+        splitter.finish().unwrap();
+    }
+
+    #[test]
+    fn test_records() {
+        let mut splitter = ArraySplitter::default();
+        let records: Vec<ResourcePermissions> = splitter
+            .push(&report())
+            .unwrap()
+            .into_iter()
+            .flat_map(|element| {
+                serde_json::from_slice::<ReportEntry>(&element)
+                    .unwrap()
+                    .into_records()
+            })
+            .collect();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].account_name, "user@example.com");
+        assert_eq!(records[0].resource_name, "repo");
+        let allowed: Vec<&str> = records[0]
+            .permissions
+            .iter()
+            .filter(|permission| permission.is_allowed())
+            .map(|permission| permission.name.as_str())
+            .collect();
+        assert_eq!(allowed, ["Read"]);
+        assert_eq!(records[1].resource_id, "other-id");
+    }
+}
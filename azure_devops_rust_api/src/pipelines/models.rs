@@ -691,7 +691,7 @@ pub struct Variable {
     #[serde(rename = "isSecret", default, skip_serializing_if = "Option::is_none")]
     pub is_secret: Option<bool>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
+    pub value: Option<crate::secret::Secret<String>>,
 }
 impl Variable {
     pub fn new() -> Self {
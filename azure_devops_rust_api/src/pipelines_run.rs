@@ -0,0 +1,591 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Typed pipeline run parameters.
+//!
+//! [`RunPipelineParameters`] describes template parameters, variables and resources as
+//! loosely typed JSON maps, so mistakes are only reported by the service (as a 400 response).
+//! [`RunParameters`] builds them from typed values and checks them before the request is sent.
+//! `pipelines::Client::queue_run()` queues the run, or previews the expanded YAML without
+//! queueing it.
+//!
+//! ```rust
+//!     let parameters = RunParameters::new()
+//!         .template_parameter("environment", "staging")
+//!         .template_parameter("runTests", true)
+//!         .secret_variable("deployKey", deploy_key)
+//!         .repository_ref("self", "feature/login")
+//!         .skip_stage("Publish");
+//!     let yaml = pipelines_client
+//!         .queue_run(&organization, &project, pipeline_id, parameters.clone())
+//!         .preview()
+//!         .await?;
+//!     println!("{yaml}");
+//!     let run = pipelines_client
+//!         .queue_run(&organization, &project, pipeline_id, parameters)
+//!         .await?;
+//! ```
+
+use crate::pipelines::models::{
+    BuildResourceParameters, ContainerResourceParameters, PackageResourceParameters,
+    PipelineResourceParameters, RepositoryResourceParameters, Run, RunPipelineParameters,
+    RunResourcesParameters, Variable,
+};
+use crate::secret::Secret;
+use azure_core::error::{Error, ErrorKind, ResultExt};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// The value of a pipeline template parameter.
+///
+/// The service accepts template parameter values as strings: booleans and numbers are
+/// converted to their YAML representation, and objects and lists to (YAML compatible) JSON.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParameterValue {
+    String(String),
+    Boolean(bool),
+    Number(serde_json::Number),
+    /// A list or object value.
+    Object(serde_json::Value),
+}
+
+impl ParameterValue {
+    /// The value as sent to the service.
+    pub fn to_template_value(&self) -> String {
+        match self {
+            ParameterValue::String(value) => value.clone(),
+            ParameterValue::Boolean(value) => value.to_string(),
+            ParameterValue::Number(value) => value.to_string(),
+            ParameterValue::Object(serde_json::Value::String(value)) => value.clone(),
+            ParameterValue::Object(value) => value.to_string(),
+        }
+    }
+}
+
+impl From<&str> for ParameterValue {
+    fn from(value: &str) -> Self {
+        ParameterValue::String(value.to_string())
+    }
+}
+
+impl From<String> for ParameterValue {
+    fn from(value: String) -> Self {
+        ParameterValue::String(value)
+    }
+}
+
+impl From<bool> for ParameterValue {
+    fn from(value: bool) -> Self {
+        ParameterValue::Boolean(value)
+    }
+}
+
+impl From<i32> for ParameterValue {
+    fn from(value: i32) -> Self {
+        ParameterValue::Number(value.into())
+    }
+}
+
+impl From<i64> for ParameterValue {
+    fn from(value: i64) -> Self {
+        ParameterValue::Number(value.into())
+    }
+}
+
+impl From<u32> for ParameterValue {
+    fn from(value: u32) -> Self {
+        ParameterValue::Number(value.into())
+    }
+}
+
+impl From<f64> for ParameterValue {
+    fn from(value: f64) -> Self {
+        serde_json::Number::from_f64(value)
+            .map(ParameterValue::Number)
+            .unwrap_or_else(|| ParameterValue::String(value.to_string()))
+    }
+}
+
+impl From<serde_json::Value> for ParameterValue {
+    fn from(value: serde_json::Value) -> Self {
+        ParameterValue::Object(value)
+    }
+}
+
+impl<T: Into<ParameterValue>> From<Vec<T>> for ParameterValue {
+    fn from(values: Vec<T>) -> Self {
+        let values = values
+            .into_iter()
+            .map(|value| match value.into() {
+                ParameterValue::String(value) => serde_json::Value::String(value),
+                ParameterValue::Boolean(value) => serde_json::Value::Bool(value),
+                ParameterValue::Number(value) => serde_json::Value::Number(value),
+                ParameterValue::Object(value) => value,
+            })
+            .collect();
+        ParameterValue::Object(serde_json::Value::Array(values))
+    }
+}
+
+/// Typed parameters for a pipeline run.
+///
+/// Converts into [`RunPipelineParameters`] with `try_into()`, so it can also be passed to the
+/// generated `pipelines::runs::Client::run_pipeline()` and `pipelines::preview::Client::preview()`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunParameters {
+    template_parameters: BTreeMap<String, ParameterValue>,
+    variables: BTreeMap<String, Variable>,
+    repositories: BTreeMap<String, RepositoryResourceParameters>,
+    pipelines: BTreeMap<String, PipelineResourceParameters>,
+    builds: BTreeMap<String, BuildResourceParameters>,
+    containers: BTreeMap<String, ContainerResourceParameters>,
+    packages: BTreeMap<String, PackageResourceParameters>,
+    stages_to_skip: Vec<String>,
+    yaml_override: Option<String>,
+}
+
+impl RunParameters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the value of a template parameter declared in the pipeline's `parameters` section.
+    #[must_use]
+    pub fn template_parameter(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<ParameterValue>,
+    ) -> Self {
+        self.template_parameters.insert(name.into(), value.into());
+        self
+    }
+
+    /// Sets a variable. The variable must be settable at queue time.
+    #[must_use]
+    pub fn variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(
+            name.into(),
+            Variable {
+                is_secret: None,
+                value: Some(Secret::new(value.into())),
+            },
+        );
+        self
+    }
+
+    /// Sets a secret variable, whose value is masked in logs.
+    #[must_use]
+    pub fn secret_variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(
+            name.into(),
+            Variable {
+                is_secret: Some(true),
+                value: Some(Secret::new(value.into())),
+            },
+        );
+        self
+    }
+
+    /// Sets the ref of a repository resource (`self` for the pipeline's own repository).
+    ///
+    /// Branch names are qualified with `refs/heads/`; full ref names are used as given.
+    #[must_use]
+    pub fn repository_ref(mut self, alias: impl Into<String>, ref_name: impl Into<String>) -> Self {
+        let ref_name = ref_name.into();
+        let ref_name = if ref_name.starts_with("refs/") {
+            ref_name
+        } else {
+            format!("refs/heads/{ref_name}")
+        };
+        self.repositories.entry(alias.into()).or_default().ref_name = Some(ref_name);
+        self
+    }
+
+    /// Sets the commit of a repository resource (`self` for the pipeline's own repository).
+    #[must_use]
+    pub fn repository_version(
+        mut self,
+        alias: impl Into<String>,
+        commit_id: impl Into<String>,
+    ) -> Self {
+        self.repositories.entry(alias.into()).or_default().version = Some(commit_id.into());
+        self
+    }
+
+    /// Sets the run of a pipeline resource, identified by its run name (build number).
+    #[must_use]
+    pub fn pipeline_resource(
+        mut self,
+        alias: impl Into<String>,
+        version: impl Into<String>,
+    ) -> Self {
+        self.pipelines.insert(
+            alias.into(),
+            PipelineResourceParameters {
+                version: Some(version.into()),
+            },
+        );
+        self
+    }
+
+    /// Sets the version of a build resource.
+    #[must_use]
+    pub fn build_resource(mut self, alias: impl Into<String>, version: impl Into<String>) -> Self {
+        self.builds.insert(
+            alias.into(),
+            BuildResourceParameters {
+                version: Some(version.into()),
+            },
+        );
+        self
+    }
+
+    /// Sets the version (tag) of a container resource.
+    #[must_use]
+    pub fn container_resource(
+        mut self,
+        alias: impl Into<String>,
+        version: impl Into<String>,
+    ) -> Self {
+        self.containers.insert(
+            alias.into(),
+            ContainerResourceParameters {
+                version: Some(version.into()),
+            },
+        );
+        self
+    }
+
+    /// Sets the version of a package resource.
+    #[must_use]
+    pub fn package_resource(
+        mut self,
+        alias: impl Into<String>,
+        version: impl Into<String>,
+    ) -> Self {
+        self.packages.insert(
+            alias.into(),
+            PackageResourceParameters {
+                version: Some(version.into()),
+            },
+        );
+        self
+    }
+
+    /// Skips a stage of the run. May be called multiple times.
+    #[must_use]
+    pub fn skip_stage(mut self, stage: impl Into<String>) -> Self {
+        let stage = stage.into();
+        if !self.stages_to_skip.contains(&stage) {
+            self.stages_to_skip.push(stage);
+        }
+        self
+    }
+
+    /// YAML to use instead of the pipeline's YAML file. Only supported when previewing a run.
+    #[must_use]
+    pub fn yaml_override(mut self, yaml: impl Into<String>) -> Self {
+        self.yaml_override = Some(yaml.into());
+        self
+    }
+
+    /// Checks the parameters for mistakes that the service would reject, and that they can be
+    /// converted to a request.
+    pub fn validate(&self) -> azure_core::Result<()> {
+        for name in self.template_parameters.keys() {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+            {
+                return Err(invalid(format!(
+                    "invalid template parameter name: {name:?}"
+                )));
+            }
+        }
+        for name in self.variables.keys() {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '-')
+            {
+                return Err(invalid(format!("invalid variable name: {name:?}")));
+            }
+            let lowercase = name.to_ascii_lowercase();
+            if let Some(prefix) = RESERVED_VARIABLE_PREFIXES
+                .iter()
+                .find(|prefix| lowercase.starts_with(*prefix))
+            {
+                return Err(invalid(format!(
+                    "variable names may not start with {prefix:?}: {name:?}"
+                )));
+            }
+        }
+        let aliases = self
+            .repositories
+            .keys()
+            .chain(self.pipelines.keys())
+            .chain(self.builds.keys())
+            .chain(self.containers.keys())
+            .chain(self.packages.keys());
+        for alias in aliases {
+            if alias.is_empty() {
+                return Err(invalid("resource alias must not be empty".to_string()));
+            }
+        }
+        for (alias, repository) in &self.repositories {
+            if let Some(commit_id) = &repository.version {
+                if commit_id.len() != 40 || !commit_id.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return Err(invalid(format!(
+                        "repository {alias:?} version must be a full commit id: {commit_id:?}"
+                    )));
+                }
+            }
+        }
+        if self.stages_to_skip.iter().any(String::is_empty) {
+            return Err(invalid("stage name must not be empty".to_string()));
+        }
+        self.clone().into_parameters(false).map(drop)
+    }
+
+    fn into_parameters(self, preview_run: bool) -> azure_core::Result<RunPipelineParameters> {
+        let template_parameters = to_json_map(
+            self.template_parameters
+                .iter()
+                .map(|(name, value)| (name.clone(), value.to_template_value()))
+                .collect(),
+        )?;
+        let resources = RunResourcesParameters {
+            builds: to_json_map(self.builds)?,
+            containers: to_json_map(self.containers)?,
+            packages: to_json_map(self.packages)?,
+            pipelines: to_json_map(self.pipelines)?,
+            repositories: to_json_map(self.repositories)?,
+        };
+        Ok(RunPipelineParameters {
+            preview_run: preview_run.then_some(true),
+            resources: (resources != RunResourcesParameters::default()).then_some(resources),
+            stages_to_skip: self.stages_to_skip,
+            template_parameters,
+            variables: to_json_map(self.variables)?,
+            yaml_override: self.yaml_override,
+        })
+    }
+}
+
+impl TryFrom<RunParameters> for RunPipelineParameters {
+    type Error = Error;
+
+    fn try_from(parameters: RunParameters) -> azure_core::Result<Self> {
+        parameters.into_parameters(false)
+    }
+}
+
+/// Variable name prefixes reserved by the service.
+const RESERVED_VARIABLE_PREFIXES: &[&str] = &["endpoint", "input", "secret", "path", "securefile"];
+
+fn invalid(message: String) -> Error {
+    Error::message(ErrorKind::DataConversion, message)
+}
+
+fn to_json_map<T: Serialize>(
+    map: BTreeMap<String, T>,
+) -> azure_core::Result<Option<serde_json::Value>> {
+    if map.is_empty() {
+        return Ok(None);
+    }
+    serde_json::to_value(map).map(Some).context(
+        ErrorKind::DataConversion,
+        "failed to serialize run parameters",
+    )
+}
+
+impl crate::pipelines::Client {
+    /// Creates a builder that queues (or previews) a run of a pipeline with typed parameters.
+    ///
+    /// The parameters are validated before any request is sent.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `pipeline_id`: The pipeline ID.
+    /// * `parameters`: The parameters of the run.
+    pub fn queue_run(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        pipeline_id: i32,
+        parameters: RunParameters,
+    ) -> QueueRunBuilder {
+        QueueRunBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            pipeline_id,
+            pipeline_version: None,
+            parameters,
+        }
+    }
+}
+
+/// `QueueRunBuilder` provides a mechanism for setting optional parameters of a pipeline run.
+///
+/// Await the builder to queue the run, or call [`preview()`](QueueRunBuilder::preview) to
+/// return the final YAML document without queueing it.
+#[derive(Clone)]
+pub struct QueueRunBuilder {
+    client: crate::pipelines::Client,
+    organization: String,
+    project: String,
+    pipeline_id: i32,
+    pipeline_version: Option<i32>,
+    parameters: RunParameters,
+}
+
+impl QueueRunBuilder {
+    /// The pipeline version to run (defaults to the latest).
+    #[must_use]
+    pub fn pipeline_version(mut self, pipeline_version: i32) -> Self {
+        self.pipeline_version = Some(pipeline_version);
+        self
+    }
+
+    /// Validates the run and returns the final YAML document after expanding templates,
+    /// without queueing the run.
+    pub fn preview(self) -> BoxFuture<'static, azure_core::Result<String>> {
+        Box::pin(async move {
+            self.parameters.validate()?;
+            let mut request = self.client.preview_client().preview(
+                &self.organization,
+                self.parameters.into_parameters(true)?,
+                &self.project,
+                self.pipeline_id,
+            );
+            if let Some(pipeline_version) = self.pipeline_version {
+                request = request.pipeline_version(pipeline_version);
+            }
+            request.await?.final_yaml.ok_or_else(|| {
+                Error::message(ErrorKind::DataConversion, "preview returned no YAML")
+            })
+        })
+    }
+
+    async fn queue(self) -> azure_core::Result<Run> {
+        self.parameters.validate()?;
+        if self.parameters.yaml_override.is_some() {
+            return Err(invalid(
+                "yaml_override is only supported when previewing a run".to_string(),
+            ));
+        }
+        let mut request = self.client.runs_client().run_pipeline(
+            &self.organization,
+            self.parameters.into_parameters(false)?,
+            &self.project,
+            self.pipeline_id,
+        );
+        if let Some(pipeline_version) = self.pipeline_version {
+            request = request.pipeline_version(pipeline_version);
+        }
+        request.await
+    }
+}
+
+impl std::future::IntoFuture for QueueRunBuilder {
+    type Output = azure_core::Result<Run>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<Run>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.queue())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parameter_values() {
+        assert_eq!(ParameterValue::from("a").to_template_value(), "a");
+        assert_eq!(ParameterValue::from(true).to_template_value(), "true");
+        assert_eq!(ParameterValue::from(3).to_template_value(), "3");
+        assert_eq!(ParameterValue::from(1.5).to_template_value(), "1.5");
+        assert_eq!(
+            ParameterValue::from(vec!["x", "y"]).to_template_value(),
+            r#"["x","y"]"#
+        );
+        assert_eq!(
+            ParameterValue::from(json!({ "region": "westus", "count": 2 })).to_template_value(),
+            r#"{"count":2,"region":"westus"}"#
+        );
+    }
+
+    #[test]
+    fn test_run_pipeline_parameters() {
+        let parameters: RunPipelineParameters = RunParameters::new()
+            .template_parameter("environment", "staging")
+            .template_parameter("runTests", false)
+            .variable("configuration", "Release")
+            .secret_variable("deployKey", "hunter2")
+            .repository_ref("self", "main")
+            .repository_ref("tools", "refs/tags/v1")
+            .repository_version("self", "0123456789abcdef0123456789abcdef01234567")
+            .pipeline_resource("upstream", "20240101.1")
+            .skip_stage("Publish")
+            .skip_stage("Publish")
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(parameters).unwrap(),
+            json!({
+                "resources": {
+                    "pipelines": { "upstream": { "version": "20240101.1" } },
+                    "repositories": {
+                        "self": {
+                            "refName": "refs/heads/main",
+                            "version": "0123456789abcdef0123456789abcdef01234567"
+                        },
+                        "tools": { "refName": "refs/tags/v1" }
+                    }
+                },
+                "stagesToSkip": ["Publish"],
+                "templateParameters": { "environment": "staging", "runTests": "false" },
+                "variables": {
+                    "configuration": { "value": "Release" },
+                    "deployKey": { "isSecret": true, "value": "hunter2" }
+                }
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(RunPipelineParameters::try_from(RunParameters::new()).unwrap())
+                .unwrap(),
+            json!({})
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(RunParameters::new()
+            .template_parameter("env", "a")
+            .variable("system.debug", "true")
+            .validate()
+            .is_ok());
+        assert!(RunParameters::new()
+            .template_parameter("my param", "a")
+            .validate()
+            .is_err());
+        assert!(RunParameters::new()
+            .variable("secret.token", "x")
+            .validate()
+            .is_err());
+        assert!(RunParameters::new()
+            .repository_version("self", "main")
+            .validate()
+            .is_err());
+    }
+}
@@ -1082,7 +1082,7 @@ pub struct ConfigurationVariableValue {
     pub is_secret: Option<bool>,
     #[doc = "Gets and sets value of the configuration variable."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
+    pub value: Option<crate::secret::Secret<String>>,
 }
 impl ConfigurationVariableValue {
     pub fn new() -> Self {
@@ -6783,7 +6783,7 @@ pub struct VariableValue {
     pub is_secret: Option<bool>,
     #[doc = "Gets or sets the value."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub value: Option<String>,
+    pub value: Option<crate::secret::Secret<String>>,
 }
 impl VariableValue {
     pub fn new() -> Self {
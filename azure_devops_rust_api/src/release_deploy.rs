@@ -0,0 +1,654 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Release deployment orchestration.
+//!
+//! `release::Client::deploy_release()` deploys a release to one or more environments in
+//! turn. While a deployment runs it reports what the deployment is waiting for (approvals,
+//! gates and manual interventions), optionally approves, ignores or resumes them, and
+//! returns the outcome of each environment's deployment.
+//!
+//! ```rust
+//!     let deployments = release_client
+//!         .deploy_release(&organization, &project, release_id)
+//!         .environment("Staging")
+//!         .environment("Production")
+//!         .comment("Deployed by release tooling")
+//!         .approve(true)
+//!         .on_progress(|progress| println!("{}: waiting for {:?}", progress.name, progress.pending))
+//!         .await?;
+//!     for deployment in deployments {
+//!         println!("{}: {:?}", deployment.name, deployment.outcome);
+//!     }
+//! ```
+
+use crate::release::models::{
+    deployment_attempt, manual_intervention, manual_intervention_update_metadata, release_approval,
+    release_environment, release_environment_update_metadata, release_gates, DeploymentAttempt,
+    GateUpdateMetadata, ManualIntervention, ManualInterventionUpdateMetadata, ReleaseApproval,
+    ReleaseEnvironment, ReleaseEnvironmentUpdateMetadata, ReleaseGates,
+};
+use azure_core::error::{Error, ErrorKind};
+use std::collections::HashSet;
+use std::time::Duration;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Whether gates run before or after the deployment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GateStage {
+    PreDeployment,
+    PostDeployment,
+}
+
+/// Something a deployment is waiting for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PendingAction {
+    /// A pre- or post-deployment approval.
+    Approval(Box<ReleaseApproval>),
+    /// Deployment gates that are being evaluated.
+    Gates {
+        stage: GateStage,
+        gates: ReleaseGates,
+    },
+    /// A manual intervention task waiting to be resumed or rejected.
+    ManualIntervention(Box<ManualIntervention>),
+}
+
+/// The state of an environment deployment, reported after each check.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeploymentProgress {
+    /// The name of the environment.
+    pub name: String,
+    /// The environment, as last fetched.
+    pub environment: ReleaseEnvironment,
+    /// What the deployment is waiting for.
+    pub pending: Vec<PendingAction>,
+}
+
+/// How an environment deployment ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeploymentOutcome {
+    Succeeded,
+    PartiallySucceeded,
+    /// The deployment failed.
+    Failed,
+    /// An approval, gate or manual intervention rejected the deployment.
+    Rejected,
+    Canceled,
+    /// The timeout elapsed before the deployment finished.
+    TimedOut,
+    /// The deployment was not attempted because the deployment to a previous environment did
+    /// not succeed.
+    Skipped,
+}
+
+impl DeploymentOutcome {
+    /// Whether the deployment succeeded (possibly partially).
+    pub fn is_success(&self) -> bool {
+        matches!(
+            self,
+            DeploymentOutcome::Succeeded | DeploymentOutcome::PartiallySucceeded
+        )
+    }
+}
+
+/// The outcome of deploying a release to an environment.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvironmentDeployment {
+    /// The ID of the release environment.
+    pub environment_id: i32,
+    /// The name of the environment.
+    pub name: String,
+    pub outcome: DeploymentOutcome,
+    /// The environment, as last fetched.
+    pub environment: ReleaseEnvironment,
+}
+
+/// An action already completed by the builder, by ID.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum HandledAction {
+    Approval(i32),
+    Gates(i32),
+    ManualIntervention(i32),
+}
+
+#[derive(Clone, Debug)]
+enum EnvironmentSelector {
+    Name(String),
+    Id(i32),
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type ProgressCallback = Box<dyn FnMut(&DeploymentProgress) + Send>;
+#[cfg(target_arch = "wasm32")]
+type ProgressCallback = Box<dyn FnMut(&DeploymentProgress)>;
+
+impl crate::release::Client {
+    /// Creates a builder that deploys a release to one or more environments and waits for
+    /// the deployments to finish.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `release_id`: Id of the release.
+    pub fn deploy_release(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        release_id: i32,
+    ) -> DeployReleaseBuilder {
+        DeployReleaseBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            release_id,
+            environments: Vec::new(),
+            comment: None,
+            approve: false,
+            ignore_gates: false,
+            resume_manual_interventions: false,
+            poll_interval: Duration::from_secs(15),
+            timeout: None,
+            on_progress: None,
+        }
+    }
+}
+
+/// `DeployReleaseBuilder` provides a mechanism for setting deployment options.
+///
+/// Invoke `.await` to deploy the release, which returns the outcome of each environment's
+/// deployment in the order the environments were given. Environments are deployed one at a
+/// time; once a deployment does not succeed, the remaining environments are
+/// [`Skipped`](DeploymentOutcome::Skipped).
+pub struct DeployReleaseBuilder {
+    client: crate::release::Client,
+    organization: String,
+    project: String,
+    release_id: i32,
+    environments: Vec<EnvironmentSelector>,
+    comment: Option<String>,
+    approve: bool,
+    ignore_gates: bool,
+    resume_manual_interventions: bool,
+    poll_interval: Duration,
+    timeout: Option<Duration>,
+    on_progress: Option<ProgressCallback>,
+}
+
+impl DeployReleaseBuilder {
+    /// Deploys to the environment with this name. May be called multiple times.
+    #[must_use]
+    pub fn environment(mut self, name: impl Into<String>) -> Self {
+        self.environments
+            .push(EnvironmentSelector::Name(name.into()));
+        self
+    }
+
+    /// Deploys to the release environment with this ID. May be called multiple times.
+    #[must_use]
+    pub fn environment_id(mut self, environment_id: i32) -> Self {
+        self.environments
+            .push(EnvironmentSelector::Id(environment_id));
+        self
+    }
+
+    /// Comment recorded on the deployment and on any approvals and manual interventions
+    /// completed by this builder.
+    #[must_use]
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+
+    /// Whether to approve pending approvals (default `false`). Only approvals that the caller
+    /// is allowed to complete can be approved; others fail the deployment with an error.
+    #[must_use]
+    pub fn approve(mut self, approve: bool) -> Self {
+        self.approve = approve;
+        self
+    }
+
+    /// Whether to ignore deployment gates that are being evaluated (default `false`).
+    #[must_use]
+    pub fn ignore_gates(mut self, ignore_gates: bool) -> Self {
+        self.ignore_gates = ignore_gates;
+        self
+    }
+
+    /// Whether to resume pending manual interventions (default `false`).
+    #[must_use]
+    pub fn resume_manual_interventions(mut self, resume_manual_interventions: bool) -> Self {
+        self.resume_manual_interventions = resume_manual_interventions;
+        self
+    }
+
+    /// Interval between status checks (default 15 seconds).
+    #[must_use]
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Maximum time to wait for each environment's deployment.
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Callback invoked with the state of the current deployment after each check.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn on_progress(
+        mut self,
+        on_progress: impl FnMut(&DeploymentProgress) + Send + 'static,
+    ) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    /// Callback invoked with the state of the current deployment after each check.
+    #[cfg(target_arch = "wasm32")]
+    #[must_use]
+    pub fn on_progress(mut self, on_progress: impl FnMut(&DeploymentProgress) + 'static) -> Self {
+        self.on_progress = Some(Box::new(on_progress));
+        self
+    }
+
+    async fn deploy(mut self) -> azure_core::Result<Vec<EnvironmentDeployment>> {
+        if self.environments.is_empty() {
+            return Err(Error::message(
+                ErrorKind::Other,
+                "no environments to deploy to",
+            ));
+        }
+        let release = self
+            .client
+            .releases_client()
+            .get_release(&self.organization, &self.project, self.release_id)
+            .await?;
+        let mut targets = Vec::new();
+        for selector in &self.environments {
+            let environment = release
+                .environments
+                .iter()
+                .find(|environment| match selector {
+                    EnvironmentSelector::Name(name) => environment
+                        .name
+                        .as_deref()
+                        .map_or(false, |n| n.eq_ignore_ascii_case(name)),
+                    EnvironmentSelector::Id(id) => environment.id == Some(*id),
+                })
+                .ok_or_else(|| {
+                    Error::with_message(ErrorKind::Other, || {
+                        format!(
+                            "release {} has no environment {:?}",
+                            self.release_id, selector
+                        )
+                    })
+                })?;
+            targets.push(environment.clone());
+        }
+
+        let mut deployments = Vec::new();
+        let mut failed = false;
+        for environment in targets {
+            let environment_id = environment.id.unwrap_or_default();
+            let name = environment.name.clone().unwrap_or_default();
+            if failed {
+                deployments.push(EnvironmentDeployment {
+                    environment_id,
+                    name,
+                    outcome: DeploymentOutcome::Skipped,
+                    environment,
+                });
+                continue;
+            }
+            let deployment = self.deploy_environment(environment).await?;
+            failed = !deployment.outcome.is_success();
+            deployments.push(deployment);
+        }
+        Ok(deployments)
+    }
+
+    async fn deploy_environment(
+        &mut self,
+        environment: ReleaseEnvironment,
+    ) -> azure_core::Result<EnvironmentDeployment> {
+        let environment_id = environment.id.unwrap_or_default();
+        let name = environment.name.clone().unwrap_or_default();
+        // Outcomes of deployment attempts up to this one describe earlier deployments.
+        let mut baseline_attempt = None;
+        if !is_deploying(&environment) {
+            baseline_attempt = Some(
+                latest_attempt(&environment)
+                    .map_or(0, |attempt| attempt.attempt.unwrap_or_default()),
+            );
+            self.client
+                .releases_client()
+                .update_release_environment(
+                    &self.organization,
+                    ReleaseEnvironmentUpdateMetadata {
+                        comment: self.comment.clone(),
+                        status: Some(release_environment_update_metadata::Status::InProgress),
+                        ..Default::default()
+                    },
+                    &self.project,
+                    self.release_id,
+                    environment_id,
+                )
+                .await?;
+        }
+
+        let mut waited = Duration::ZERO;
+        let mut handled = HashSet::new();
+        loop {
+            let environment = self
+                .client
+                .releases_client()
+                .get_release_environment(
+                    &self.organization,
+                    &self.project,
+                    self.release_id,
+                    environment_id,
+                )
+                .await?;
+            let started = baseline_attempt.map_or(true, |baseline| {
+                latest_attempt(&environment)
+                    .and_then(|attempt| attempt.attempt)
+                    .map_or(false, |attempt| attempt > baseline)
+            });
+            if started {
+                if let Some(outcome) = deployment_outcome(&environment) {
+                    return Ok(EnvironmentDeployment {
+                        environment_id,
+                        name,
+                        outcome,
+                        environment,
+                    });
+                }
+            }
+            let progress = DeploymentProgress {
+                name: name.clone(),
+                pending: pending_actions(&environment),
+                environment,
+            };
+            if let Some(on_progress) = self.on_progress.as_mut() {
+                on_progress(&progress);
+            }
+            for action in &progress.pending {
+                self.handle(action, &mut handled).await?;
+            }
+            if self.timeout.map_or(false, |timeout| waited >= timeout) {
+                return Ok(EnvironmentDeployment {
+                    environment_id,
+                    name,
+                    outcome: DeploymentOutcome::TimedOut,
+                    environment: progress.environment,
+                });
+            }
+            azure_core::sleep::sleep(self.poll_interval).await;
+            waited += self.poll_interval;
+        }
+    }
+
+    /// Approves, ignores or resumes a pending action if configured to, at most once per action.
+    // Takes `&mut self` so that the future is `Send` although the progress callback is not `Sync`.
+    async fn handle(
+        &mut self,
+        action: &PendingAction,
+        handled: &mut HashSet<HandledAction>,
+    ) -> azure_core::Result<()> {
+        match action {
+            PendingAction::Approval(approval) if self.approve => {
+                let id = approval.id.unwrap_or_default();
+                if handled.insert(HandledAction::Approval(id)) {
+                    self.client
+                        .approvals_client()
+                        .update(
+                            &self.organization,
+                            ReleaseApproval {
+                                status: Some(release_approval::Status::Approved),
+                                comments: self.comment.clone(),
+                                ..Default::default()
+                            },
+                            &self.project,
+                            id,
+                        )
+                        .await?;
+                }
+            }
+            PendingAction::Gates { gates, .. } if self.ignore_gates => {
+                let id = gates.id.unwrap_or_default();
+                let gates_to_ignore = gate_names(gates);
+                if !gates_to_ignore.is_empty() && handled.insert(HandledAction::Gates(id)) {
+                    self.client
+                        .gates_client()
+                        .update(
+                            &self.organization,
+                            GateUpdateMetadata {
+                                comment: self.comment.clone(),
+                                gates_to_ignore,
+                            },
+                            &self.project,
+                            id,
+                        )
+                        .await?;
+                }
+            }
+            PendingAction::ManualIntervention(intervention) if self.resume_manual_interventions => {
+                let id = intervention.id.unwrap_or_default();
+                if handled.insert(HandledAction::ManualIntervention(id)) {
+                    self.client
+                        .manual_interventions_client()
+                        .update(
+                            &self.organization,
+                            ManualInterventionUpdateMetadata {
+                                comment: self.comment.clone(),
+                                status: Some(manual_intervention_update_metadata::Status::Approved),
+                            },
+                            &self.project,
+                            self.release_id,
+                            id,
+                        )
+                        .await?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+impl std::future::IntoFuture for DeployReleaseBuilder {
+    type Output = azure_core::Result<Vec<EnvironmentDeployment>>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<Vec<EnvironmentDeployment>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.deploy())
+    }
+}
+
+/// Whether a deployment to the environment is queued or running.
+fn is_deploying(environment: &ReleaseEnvironment) -> bool {
+    matches!(
+        environment.status,
+        Some(
+            release_environment::Status::InProgress
+                | release_environment::Status::Queued
+                | release_environment::Status::Scheduled
+        )
+    )
+}
+
+/// The most recent deployment attempt to the environment.
+fn latest_attempt(environment: &ReleaseEnvironment) -> Option<&DeploymentAttempt> {
+    environment
+        .deploy_steps
+        .iter()
+        .max_by_key(|attempt| attempt.attempt)
+}
+
+/// The outcome of the environment's deployment, or `None` if it has not finished.
+fn deployment_outcome(environment: &ReleaseEnvironment) -> Option<DeploymentOutcome> {
+    match environment.status.as_ref()? {
+        release_environment::Status::Succeeded => Some(DeploymentOutcome::Succeeded),
+        release_environment::Status::PartiallySucceeded => {
+            Some(DeploymentOutcome::PartiallySucceeded)
+        }
+        release_environment::Status::Canceled => Some(DeploymentOutcome::Canceled),
+        // A failed deployment also leaves the environment rejected.
+        release_environment::Status::Rejected => {
+            let failed = latest_attempt(environment).and_then(|attempt| attempt.status.as_ref())
+                == Some(&deployment_attempt::Status::Failed);
+            Some(if failed {
+                DeploymentOutcome::Failed
+            } else {
+                DeploymentOutcome::Rejected
+            })
+        }
+        _ => None,
+    }
+}
+
+/// The approvals, gates and manual interventions the environment's latest deployment is
+/// waiting for.
+fn pending_actions(environment: &ReleaseEnvironment) -> Vec<PendingAction> {
+    let mut pending: Vec<PendingAction> = environment
+        .pre_deploy_approvals
+        .iter()
+        .chain(&environment.post_deploy_approvals)
+        .filter(|approval| {
+            approval.status == Some(release_approval::Status::Pending)
+                && approval.is_automated != Some(true)
+        })
+        .cloned()
+        .map(|approval| PendingAction::Approval(Box::new(approval)))
+        .collect();
+    if let Some(attempt) = latest_attempt(environment) {
+        let gates = [
+            (GateStage::PreDeployment, &attempt.pre_deployment_gates),
+            (GateStage::PostDeployment, &attempt.post_deployment_gates),
+        ];
+        for (stage, gates) in gates {
+            if let Some(gates) = gates {
+                if matches!(
+                    gates.status,
+                    Some(release_gates::Status::Pending | release_gates::Status::InProgress)
+                ) {
+                    pending.push(PendingAction::Gates {
+                        stage,
+                        gates: gates.clone(),
+                    });
+                }
+            }
+        }
+        pending.extend(
+            attempt
+                .release_deploy_phases
+                .iter()
+                .flat_map(|phase| &phase.manual_interventions)
+                .filter(|intervention| {
+                    intervention.status == Some(manual_intervention::Status::Pending)
+                })
+                .cloned()
+                .map(|intervention| PendingAction::ManualIntervention(Box::new(intervention))),
+        );
+    }
+    pending
+}
+
+/// The names of the gates evaluated by a gate step.
+fn gate_names(gates: &ReleaseGates) -> Vec<String> {
+    let mut names: Vec<String> = gates
+        .deployment_jobs
+        .iter()
+        .flat_map(|job| &job.tasks)
+        .filter_map(|task| task.name.clone())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn environment(value: serde_json::Value) -> ReleaseEnvironment {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_deployment_outcome() {
+        let failed = environment(json!({
+            "status": "rejected",
+            "deploySteps": [
+                { "attempt": 1, "status": "succeeded" },
+                { "attempt": 2, "status": "failed" }
+            ]
+        }));
+        assert_eq!(deployment_outcome(&failed), Some(DeploymentOutcome::Failed));
+        let rejected = environment(json!({
+            "status": "rejected",
+            "deploySteps": [{ "attempt": 1, "status": "notDeployed" }]
+        }));
+        assert_eq!(
+            deployment_outcome(&rejected),
+            Some(DeploymentOutcome::Rejected)
+        );
+        let running = environment(json!({ "status": "inProgress" }));
+        assert_eq!(deployment_outcome(&running), None);
+        assert!(is_deploying(&running));
+    }
+
+    #[test]
+    fn test_pending_actions() {
+        let environment = environment(json!({
+            "status": "inProgress",
+            "preDeployApprovals": [
+                { "id": 1, "status": "approved" },
+                { "id": 2, "status": "pending", "isAutomated": false },
+                { "id": 3, "status": "pending", "isAutomated": true }
+            ],
+            "deploySteps": [{
+                "attempt": 1,
+                "preDeploymentGates": {
+                    "id": 7,
+                    "status": "inProgress",
+                    "deploymentJobs": [{
+                        "tasks": [{ "name": "Query work items" }, { "name": "Invoke REST API" }]
+                    }]
+                },
+                "releaseDeployPhases": [{
+                    "manualInterventions": [
+                        { "id": 4, "status": "pending" },
+                        { "id": 5, "status": "approved" }
+                    ]
+                }]
+            }]
+        }));
+        let pending = pending_actions(&environment);
+        assert_eq!(pending.len(), 3);
+        assert!(matches!(&pending[0], PendingAction::Approval(a) if a.id == Some(2)));
+        match &pending[1] {
+            PendingAction::Gates { stage, gates } => {
+                assert_eq!(*stage, GateStage::PreDeployment);
+                assert_eq!(
+                    gate_names(gates),
+                    vec![
+                        "Invoke REST API".to_string(),
+                        "Query work items".to_string()
+                    ]
+                );
+            }
+            other => panic!("unexpected action: {other:?}"),
+        }
+        assert!(matches!(&pending[2], PendingAction::ManualIntervention(m) if m.id == Some(4)));
+    }
+}
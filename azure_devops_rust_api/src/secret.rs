@@ -0,0 +1,123 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Secret values.
+//!
+//! Model fields that hold credentials (PAT tokens, service endpoint authorization
+//! parameters, variable values) are wrapped in [`Secret`], which serializes as the wrapped
+//! value but is redacted in `Debug` and `Display` output, so that secrets don't end up in
+//! logs when a model is printed. The wrapped value is zeroized when the `Secret` is dropped.
+//!
+//! The fields are selected by the `secret` list in `autorust.toml`.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A value that is redacted in `Debug` and `Display` output and zeroized on drop.
+///
+/// Use [`secret()`](Secret::secret) to access the value.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Secret<T: SecretValue = String>(T);
+
+impl<T: SecretValue> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// Returns the secret value.
+    pub fn secret(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: SecretValue> From<T> for Secret<T> {
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl<T: SecretValue> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+impl<T: SecretValue> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl<T: SecretValue> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.erase();
+    }
+}
+
+/// A type that can be held by a [`Secret`].
+pub trait SecretValue {
+    /// Overwrites the value's memory, so that the secret doesn't linger after it is dropped.
+    fn erase(&mut self);
+}
+
+impl SecretValue for String {
+    fn erase(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// Erases the strings of a JSON value, such as the parameters of a service endpoint
+/// authorization. Object keys are not secret and are left as they are.
+impl SecretValue for serde_json::Value {
+    fn erase(&mut self) {
+        match self {
+            serde_json::Value::String(value) => value.zeroize(),
+            serde_json::Value::Array(values) => values.iter_mut().for_each(SecretValue::erase),
+            serde_json::Value::Object(values) => values.values_mut().for_each(SecretValue::erase),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_secret_serde() {
+        let secret: Secret = serde_json::from_value(json!("hunter2")).unwrap();
+        assert_eq!(secret.secret(), "hunter2");
+        assert_eq!(serde_json::to_value(&secret).unwrap(), json!("hunter2"));
+
+        let parameters: Secret<serde_json::Value> =
+            serde_json::from_value(json!({ "password": "hunter2" })).unwrap();
+        assert_eq!(parameters.secret()["password"], "hunter2");
+    }
+
+    #[test]
+    fn test_secret_redacted() {
+        let secret = Secret::from("hunter2");
+        assert_eq!(format!("{secret:?}"), "Secret(***)");
+        assert_eq!(format!("{secret}"), "***");
+        let value = Some(Secret::new(json!({ "password": "hunter2" })));
+        assert!(!format!("{value:?}").contains("hunter2"));
+    }
+
+    #[test]
+    fn test_erase_json() {
+        let mut value = json!({ "password": "hunter2", "list": ["a"], "port": 1 });
+        value.erase();
+        assert_eq!(value["password"].as_str().map(str::len), Some(0));
+        assert_eq!(value["list"][0], "");
+        assert_eq!(value["port"], 1);
+    }
+}
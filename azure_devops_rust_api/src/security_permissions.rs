@@ -0,0 +1,614 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Effective permission evaluation.
+//!
+//! Access control entries hold raw `allow`/`deny` bitmasks whose bits are defined by the
+//! actions of their security namespace. [`SecurityNamespace`] decodes bitmasks into named
+//! actions, and [`evaluate()`] computes the effective permissions of a set of identity
+//! descriptors (typically a user and the groups it is a member of) on a token, walking up the
+//! token hierarchy for inherited permissions and falling back to the extended information of
+//! the entries. Each [`PermissionDecision`] records why it was made.
+//!
+//! `security::Client::explain_permissions()` loads the namespace and ACLs and evaluates them,
+//! and `security::Client::has_permission()` asks the service to evaluate a permission for the
+//! caller.
+//!
+//! ```rust
+//!     let permissions = security_client
+//!         .explain_permissions(&organization, GIT_REPOSITORIES, &token, [&user, &contributors])
+//!         .await?;
+//!     for decision in &permissions.decisions {
+//!         println!("{decision}");
+//!     }
+//!     let can_push = permissions.is_allowed("GenericContribute");
+//! ```
+
+use crate::security::models::{
+    AccessControlEntry, AccessControlList, ActionDefinition, PermissionEvaluation,
+    PermissionEvaluationBatch, SecurityNamespaceDescription,
+};
+use azure_core::error::{Error, ErrorKind};
+use std::fmt;
+
+/// A security namespace and the actions it defines.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecurityNamespace {
+    description: SecurityNamespaceDescription,
+}
+
+impl SecurityNamespace {
+    pub fn new(description: SecurityNamespaceDescription) -> Self {
+        Self { description }
+    }
+
+    pub fn description(&self) -> &SecurityNamespaceDescription {
+        &self.description
+    }
+
+    pub fn id(&self) -> &str {
+        self.description.namespace_id.as_deref().unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &str {
+        self.description.name.as_deref().unwrap_or_default()
+    }
+
+    pub fn actions(&self) -> &[ActionDefinition] {
+        &self.description.actions
+    }
+
+    /// Finds an action by name or display name.
+    pub fn action(&self, name: &str) -> Option<&ActionDefinition> {
+        self.description.actions.iter().find(|action| {
+            action.name.as_deref() == Some(name) || action.display_name.as_deref() == Some(name)
+        })
+    }
+
+    /// The actions whose bits are set in a bitmask.
+    pub fn decode(&self, mask: i32) -> Vec<&ActionDefinition> {
+        self.description
+            .actions
+            .iter()
+            .filter(|action| {
+                action
+                    .bit
+                    .map_or(false, |bit| bit != 0 && mask & bit == bit)
+            })
+            .collect()
+    }
+
+    /// The names of the actions whose bits are set in a bitmask.
+    pub fn decode_names(&self, mask: i32) -> Vec<&str> {
+        self.decode(mask)
+            .into_iter()
+            .filter_map(|action| action.name.as_deref())
+            .collect()
+    }
+
+    /// The bits of a bitmask that are not defined by any action.
+    pub fn unknown_bits(&self, mask: i32) -> i32 {
+        let known = self
+            .description
+            .actions
+            .iter()
+            .filter_map(|action| action.bit)
+            .fold(0, |known, bit| known | bit);
+        mask & !known
+    }
+
+    /// Encodes action names (or display names) into a bitmask.
+    pub fn mask<'a>(&self, names: impl IntoIterator<Item = &'a str>) -> azure_core::Result<i32> {
+        names.into_iter().try_fold(0, |mask, name| {
+            match self.action(name).and_then(|action| action.bit) {
+                Some(bit) => Ok(mask | bit),
+                None => Err(Error::with_message(ErrorKind::DataConversion, || {
+                    format!("security namespace {} has no action {name}", self.name())
+                })),
+            }
+        })
+    }
+
+    /// The parent of a token in the namespace's token hierarchy, if it is hierarchical.
+    pub fn parent_token(&self, token: &str) -> Option<String> {
+        let separator = self
+            .description
+            .separator_value
+            .as_deref()
+            .and_then(|separator| separator.chars().next())
+            .filter(|separator| *separator != '\0');
+        if let Some(separator) = separator {
+            let token = token.trim_end_matches(separator);
+            return token
+                .rfind(separator)
+                .map(|index| token[..index].to_string())
+                .filter(|parent| !parent.is_empty());
+        }
+        match self.description.element_length {
+            Some(length) if length > 0 && token.len() > length as usize => token
+                .get(..token.len() - length as usize)
+                .map(str::to_string),
+            _ => None,
+        }
+    }
+}
+
+/// Whether an action is allowed or denied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PermissionValue {
+    /// Neither allowed nor denied, which denies the action.
+    NotSet,
+    Allow,
+    Deny,
+}
+
+/// Where a [`PermissionDecision`] comes from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PermissionSource {
+    /// An entry for the descriptor on the evaluated token.
+    Explicit { descriptor: String },
+    /// An entry for the descriptor on an ancestor of the evaluated token.
+    Inherited { token: String, descriptor: String },
+    /// The service-computed inherited or effective permissions in the extended information of
+    /// the descriptor's entry.
+    ExtendedInfo { descriptor: String },
+}
+
+/// The effective permission for one action.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PermissionDecision {
+    /// The non-localized name of the action.
+    pub action: String,
+    pub display_name: Option<String>,
+    pub bit: i32,
+    pub value: PermissionValue,
+    /// `None` if no entry sets the action.
+    pub source: Option<PermissionSource>,
+}
+
+impl fmt::Display for PermissionDecision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = match self.value {
+            PermissionValue::NotSet => "not set",
+            PermissionValue::Allow => "allowed",
+            PermissionValue::Deny => "denied",
+        };
+        write!(f, "{}: {value}", self.action)?;
+        match &self.source {
+            None => Ok(()),
+            Some(PermissionSource::Explicit { descriptor }) => {
+                write!(f, " explicitly for {descriptor}")
+            }
+            Some(PermissionSource::Inherited { token, descriptor }) => {
+                write!(f, " for {descriptor}, inherited from {token}")
+            }
+            Some(PermissionSource::ExtendedInfo { descriptor }) => {
+                write!(f, " for {descriptor}, by the service-computed permissions")
+            }
+        }
+    }
+}
+
+/// The effective permissions of a set of descriptors on a token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EffectivePermissions {
+    pub namespace_id: String,
+    pub token: String,
+    /// A decision for each action of the namespace.
+    pub decisions: Vec<PermissionDecision>,
+}
+
+impl EffectivePermissions {
+    /// The decision for an action, by name or display name.
+    pub fn decision(&self, action: &str) -> Option<&PermissionDecision> {
+        self.decisions.iter().find(|decision| {
+            decision.action == action || decision.display_name.as_deref() == Some(action)
+        })
+    }
+
+    pub fn is_allowed(&self, action: &str) -> bool {
+        self.decision(action)
+            .map_or(false, |decision| decision.value == PermissionValue::Allow)
+    }
+
+    /// The bitmask of the allowed actions.
+    pub fn effective_allow(&self) -> i32 {
+        self.mask(PermissionValue::Allow)
+    }
+
+    /// The bitmask of the denied actions.
+    pub fn effective_deny(&self) -> i32 {
+        self.mask(PermissionValue::Deny)
+    }
+
+    fn mask(&self, value: PermissionValue) -> i32 {
+        self.decisions
+            .iter()
+            .filter(|decision| decision.value == value)
+            .fold(0, |mask, decision| mask | decision.bit)
+    }
+}
+
+/// The entries of an ACL, keyed by descriptor.
+fn entries(acl: &AccessControlList) -> Vec<(String, AccessControlEntry)> {
+    let aces = match &acl.aces_dictionary {
+        Some(serde_json::Value::Object(aces)) => aces,
+        _ => return Vec::new(),
+    };
+    aces.iter()
+        .filter_map(|(descriptor, ace)| {
+            let ace: AccessControlEntry = serde_json::from_value(ace.clone()).ok()?;
+            let descriptor = ace.descriptor.clone().unwrap_or_else(|| descriptor.clone());
+            Some((descriptor, ace))
+        })
+        .collect()
+}
+
+/// Computes the effective permissions of a set of identity descriptors on a token.
+///
+/// `acls` holds the ACLs of the token and its ancestors (others are ignored). For each action,
+/// deny takes precedence over allow, and an entry on a token takes precedence over entries on
+/// its ancestors. Ancestors are not considered past a token whose ACL doesn't inherit
+/// permissions. If no entry sets an action, the inherited and effective permissions in the
+/// extended information of the token's entries are used.
+pub fn evaluate<'a>(
+    namespace: &SecurityNamespace,
+    acls: &[AccessControlList],
+    token: &str,
+    descriptors: impl IntoIterator<Item = &'a str>,
+) -> EffectivePermissions {
+    let descriptors: Vec<&str> = descriptors.into_iter().collect();
+    let holds = |descriptor: &str| {
+        descriptors
+            .iter()
+            .any(|held| held.eq_ignore_ascii_case(descriptor))
+    };
+
+    // The token and its ancestors, with the entries for the descriptors.
+    let mut chain: Vec<(String, Vec<(String, AccessControlEntry)>)> = Vec::new();
+    let mut current = Some(token.to_string());
+    while let Some(level) = current {
+        let acl = acls
+            .iter()
+            .find(|acl| acl.token.as_deref() == Some(level.as_str()));
+        let level_entries = acl
+            .map(entries)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(descriptor, _)| holds(descriptor))
+            .collect();
+        let inherits = acl.map_or(true, |acl| acl.inherit_permissions != Some(false));
+        current = if inherits {
+            namespace.parent_token(&level)
+        } else {
+            None
+        };
+        chain.push((level, level_entries));
+    }
+
+    let decisions = namespace
+        .actions()
+        .iter()
+        .filter_map(|action| {
+            let bit = action.bit.filter(|bit| *bit != 0)?;
+            let (value, source) = decide(bit, token, &chain);
+            Some(PermissionDecision {
+                action: action.name.clone().unwrap_or_default(),
+                display_name: action.display_name.clone(),
+                bit,
+                value,
+                source,
+            })
+        })
+        .collect();
+    EffectivePermissions {
+        namespace_id: namespace.id().to_string(),
+        token: token.to_string(),
+        decisions,
+    }
+}
+
+fn decide(
+    bit: i32,
+    token: &str,
+    chain: &[(String, Vec<(String, AccessControlEntry)>)],
+) -> (PermissionValue, Option<PermissionSource>) {
+    let source = |level: &str, descriptor: &str| {
+        if level == token {
+            PermissionSource::Explicit {
+                descriptor: descriptor.to_string(),
+            }
+        } else {
+            PermissionSource::Inherited {
+                token: level.to_string(),
+                descriptor: descriptor.to_string(),
+            }
+        }
+    };
+    for (level, level_entries) in chain {
+        let set = |mask: fn(&AccessControlEntry) -> Option<i32>| {
+            level_entries
+                .iter()
+                .find(|(_, ace)| mask(ace).unwrap_or_default() & bit != 0)
+        };
+        if let Some((descriptor, _)) = set(|ace| ace.deny) {
+            return (PermissionValue::Deny, Some(source(level, descriptor)));
+        }
+        if let Some((descriptor, _)) = set(|ace| ace.allow) {
+            return (PermissionValue::Allow, Some(source(level, descriptor)));
+        }
+    }
+
+    // Fall back to the service-computed permissions of the token's entries.
+    let token_entries = chain.first().map(|(_, entries)| entries.as_slice());
+    for (descriptor, ace) in token_entries.unwrap_or_default() {
+        let info = match &ace.extended_info {
+            Some(info) => info,
+            None => continue,
+        };
+        let denied =
+            info.effective_deny.unwrap_or_default() | info.inherited_deny.unwrap_or_default();
+        let allowed =
+            info.effective_allow.unwrap_or_default() | info.inherited_allow.unwrap_or_default();
+        let value = if denied & bit != 0 {
+            PermissionValue::Deny
+        } else if allowed & bit != 0 {
+            PermissionValue::Allow
+        } else {
+            continue;
+        };
+        return (
+            value,
+            Some(PermissionSource::ExtendedInfo {
+                descriptor: descriptor.clone(),
+            }),
+        );
+    }
+    (PermissionValue::NotSet, None)
+}
+
+impl crate::security::Client {
+    /// Loads a security namespace, by ID.
+    pub async fn security_namespace(
+        &self,
+        organization: impl Into<String>,
+        namespace_id: impl Into<String>,
+    ) -> azure_core::Result<SecurityNamespace> {
+        let namespace_id = namespace_id.into();
+        let description = self
+            .security_namespaces_client()
+            .query(organization, &namespace_id)
+            .await?
+            .value
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                Error::with_message(ErrorKind::DataConversion, || {
+                    format!("security namespace {namespace_id} not found")
+                })
+            })?;
+        Ok(SecurityNamespace::new(description))
+    }
+
+    /// Computes the effective permissions of a set of identity descriptors (e.g. a user and
+    /// its groups) on a token, with an explanation for each action.
+    ///
+    /// Loads the namespace and the ACLs (with extended information) of the token and its
+    /// ancestors, and evaluates them with [`evaluate()`].
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `namespace_id`: Security namespace identifier.
+    /// * `token`: Security token.
+    /// * `descriptors`: Identity descriptors.
+    pub async fn explain_permissions(
+        &self,
+        organization: impl Into<String>,
+        namespace_id: impl Into<String>,
+        token: impl Into<String>,
+        descriptors: impl IntoIterator<Item = impl Into<String>>,
+    ) -> azure_core::Result<EffectivePermissions> {
+        let organization = organization.into();
+        let namespace_id = namespace_id.into();
+        let token = token.into();
+        let descriptors: Vec<String> = descriptors.into_iter().map(Into::into).collect();
+        let namespace = self
+            .security_namespace(&organization, &namespace_id)
+            .await?;
+
+        let mut acls = Vec::new();
+        let mut current = Some(token.clone());
+        while let Some(level) = current {
+            let level_acls = self
+                .access_control_lists_client()
+                .query(&namespace_id, &organization)
+                .token(&level)
+                .descriptors(descriptors.join(","))
+                .include_extended_info(true)
+                .await?
+                .value;
+            let inherits = level_acls
+                .iter()
+                .find(|acl| acl.token.as_deref() == Some(level.as_str()))
+                .map_or(true, |acl| acl.inherit_permissions != Some(false));
+            acls.extend(level_acls);
+            current = if inherits {
+                namespace.parent_token(&level)
+            } else {
+                None
+            };
+        }
+        Ok(evaluate(
+            &namespace,
+            &acls,
+            &token,
+            descriptors.iter().map(String::as_str),
+        ))
+    }
+
+    /// Asks the service whether the caller has permissions on a token.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `namespace_id`: Security namespace identifier.
+    /// * `token`: Security token.
+    /// * `permissions`: Permission bits to check; all must be allowed.
+    pub async fn has_permission(
+        &self,
+        organization: impl Into<String>,
+        namespace_id: impl Into<String>,
+        token: impl Into<String>,
+        permissions: i32,
+    ) -> azure_core::Result<bool> {
+        let batch = PermissionEvaluationBatch {
+            always_allow_administrators: Some(false),
+            evaluations: vec![PermissionEvaluation {
+                permissions: Some(permissions),
+                security_namespace_id: Some(namespace_id.into()),
+                token: Some(token.into()),
+                value: None,
+            }],
+        };
+        let batch = self
+            .permissions_client()
+            .has_permissions_batch(batch, organization)
+            .await?;
+        Ok(batch
+            .evaluations
+            .first()
+            .and_then(|evaluation| evaluation.value)
+            .unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const USER: &str = "Microsoft.IdentityModel.Claims.ClaimsIdentity;user";
+    const GROUP: &str = "Microsoft.TeamFoundation.Identity;S-1-9-group";
+
+    fn namespace() -> SecurityNamespace {
+        SecurityNamespace::new(
+            serde_json::from_value(json!({
+                "namespaceId": "2e9eb7ed-3c0a-47d4-87c1-0ffdd275fd87",
+                "name": "Git Repositories",
+                "separatorValue": "/",
+                "elementLength": -1,
+                "actions": [
+                    { "bit": 1, "name": "Administer" },
+                    { "bit": 2, "name": "GenericRead", "displayName": "Read" },
+                    { "bit": 4, "name": "GenericContribute", "displayName": "Contribute" },
+                    { "bit": 8, "name": "ForcePush" }
+                ]
+            }))
+            .unwrap(),
+        )
+    }
+
+    fn acl(token: &str, inherit: bool, aces: serde_json::Value) -> AccessControlList {
+        serde_json::from_value(json!({
+            "token": token,
+            "inheritPermissions": inherit,
+            "acesDictionary": aces
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_decode() {
+        let namespace = namespace();
+        assert_eq!(
+            namespace.decode_names(2 | 4 | 64),
+            ["GenericRead", "GenericContribute"]
+        );
+        assert_eq!(namespace.unknown_bits(2 | 4 | 64), 64);
+        assert_eq!(namespace.mask(["Read", "ForcePush"]).unwrap(), 10);
+        assert!(namespace.mask(["Delete"]).is_err());
+        assert_eq!(
+            namespace.parent_token("repoV2/project/repo").as_deref(),
+            Some("repoV2/project")
+        );
+        assert_eq!(namespace.parent_token("repoV2"), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let acls = [
+            acl(
+                "repoV2",
+                true,
+                json!({ GROUP: { "descriptor": GROUP, "allow": 2 | 4 | 8 } }),
+            ),
+            acl(
+                "repoV2/project",
+                true,
+                json!({ USER: { "descriptor": USER, "allow": 0, "deny": 8 } }),
+            ),
+            acl(
+                "repoV2/project/repo",
+                true,
+                json!({
+                    USER: { "descriptor": USER, "allow": 8 },
+                    GROUP: { "descriptor": GROUP, "deny": 4 }
+                }),
+            ),
+        ];
+        let permissions = evaluate(&namespace(), &acls, "repoV2/project/repo", [USER, GROUP]);
+        assert!(permissions.is_allowed("Read"));
+        assert!(!permissions.is_allowed("GenericContribute"));
+        assert_eq!(permissions.effective_allow(), 2 | 8);
+        assert_eq!(permissions.effective_deny(), 4);
+        let force_push = permissions.decision("ForcePush").unwrap();
+        assert_eq!(
+            force_push.source,
+            Some(PermissionSource::Explicit {
+                descriptor: USER.to_string()
+            })
+        );
+        assert_eq!(
+            permissions.decision("Read").unwrap().to_string(),
+            format!("GenericRead: allowed for {GROUP}, inherited from repoV2")
+        );
+        assert_eq!(
+            permissions.decision("Administer").unwrap().value,
+            PermissionValue::NotSet
+        );
+
+        // Only the user's own entries count without the group.
+        let permissions = evaluate(&namespace(), &acls, "repoV2/project/repo", [USER]);
+        assert!(!permissions.is_allowed("Read"));
+    }
+
+    #[test]
+    fn test_evaluate_without_inheritance_uses_extended_info() {
+        let acls = [
+            acl(
+                "repoV2",
+                true,
+                json!({ GROUP: { "descriptor": GROUP, "allow": 2 } }),
+            ),
+            acl(
+                "repoV2/project",
+                false,
+                json!({
+                    USER: {
+                        "descriptor": USER,
+                        "allow": 4,
+                        "extendedInfo": { "effectiveAllow": 1 | 4 }
+                    }
+                }),
+            ),
+        ];
+        let permissions = evaluate(&namespace(), &acls, "repoV2/project", [USER, GROUP]);
+        assert!(!permissions.is_allowed("Read"));
+        assert!(permissions.is_allowed("Contribute"));
+        assert_eq!(
+            permissions.decision("Administer").unwrap().source,
+            Some(PermissionSource::ExtendedInfo {
+                descriptor: USER.to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,687 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Security token builders.
+//!
+//! Access control lists are keyed by namespace-specific security tokens. The types in this
+//! module format (with `Display`/`to_string()`) and parse (with `FromStr`/`parse()`) the tokens
+//! of the Git Repositories, Build, ReleaseManagement, CSS (areas), Iteration and Project
+//! namespaces, and [`SecurityToken::namespace_id()`] gives the namespace to use them with.
+//!
+//! ```rust
+//!     let token = GitRepositoriesToken::branch(&project_id, &repository_id, "main");
+//!     let acl = security_client
+//!         .access_control_lists_client()
+//!         .query(token.namespace_id(), &organization)
+//!         .token(token.to_string())
+//!         .await?;
+//!
+//!     let area = wit_client
+//!         .classification_node_token(&organization, &project, ClassificationStructure::Area, r"Project\Team")
+//!         .await?;
+//! ```
+
+use azure_core::error::{Error, ErrorKind};
+use std::fmt;
+use std::str::FromStr;
+
+/// Security namespace IDs.
+pub mod namespace {
+    pub const GIT_REPOSITORIES: &str = "2e9eb7ed-3c0a-47d4-87c1-0ffdd275fd87";
+    pub const BUILD: &str = "33344d9c-fc72-4d6f-aba5-fa317101a7e9";
+    pub const RELEASE_MANAGEMENT: &str = "c788c23e-1b46-4162-8f5e-d7585343b5de";
+    /// Area paths.
+    pub const CSS: &str = "83e28ad4-2d72-4ceb-97b0-c7726d5502c3";
+    pub const ITERATION: &str = "bf7bfa03-b2b7-47db-8113-fa2e002cc5b1";
+    pub const PROJECT: &str = "52d39943-cb85-4d7f-8fa8-c6baac873819";
+}
+
+/// A security token of a known namespace.
+pub trait SecurityToken: fmt::Display {
+    /// The ID of the security namespace the token belongs to.
+    fn namespace_id(&self) -> &'static str;
+}
+
+fn invalid(kind: &str, token: &str) -> Error {
+    Error::with_message(ErrorKind::DataConversion, || {
+        format!("invalid {kind} security token: {token}")
+    })
+}
+
+/// A Git Repositories namespace token: all repositories, the repositories of a project, a
+/// repository, or a branch or tag of a repository.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum GitRepositoriesToken {
+    /// `repoV2`
+    All,
+    /// `repoV2/{project_id}`
+    Project { project_id: String },
+    /// `repoV2/{project_id}/{repository_id}`
+    Repository {
+        project_id: String,
+        repository_id: String,
+    },
+    /// `repoV2/{project_id}/{repository_id}/refs/heads/{encoded name}`, where each segment of
+    /// the branch (or tag) name is hex encoded UTF-16.
+    Ref {
+        project_id: String,
+        repository_id: String,
+        /// The full ref name, e.g. `refs/heads/main`.
+        ref_name: String,
+    },
+}
+
+const REPO_V2: &str = "repoV2";
+
+impl GitRepositoriesToken {
+    pub fn project(project_id: impl Into<String>) -> Self {
+        Self::Project {
+            project_id: project_id.into(),
+        }
+    }
+
+    pub fn repository(project_id: impl Into<String>, repository_id: impl Into<String>) -> Self {
+        Self::Repository {
+            project_id: project_id.into(),
+            repository_id: repository_id.into(),
+        }
+    }
+
+    /// A branch, by name (e.g. `main`) or ref name (e.g. `refs/heads/main`).
+    pub fn branch(
+        project_id: impl Into<String>,
+        repository_id: impl Into<String>,
+        branch: &str,
+    ) -> Self {
+        let ref_name = if branch.starts_with("refs/") {
+            branch.to_string()
+        } else {
+            format!("refs/heads/{branch}")
+        };
+        Self::Ref {
+            project_id: project_id.into(),
+            repository_id: repository_id.into(),
+            ref_name,
+        }
+    }
+
+    /// A tag, by name (e.g. `v1.0`).
+    pub fn tag(project_id: impl Into<String>, repository_id: impl Into<String>, tag: &str) -> Self {
+        Self::Ref {
+            project_id: project_id.into(),
+            repository_id: repository_id.into(),
+            ref_name: format!("refs/tags/{tag}"),
+        }
+    }
+}
+
+/// Encodes a ref name segment as hex encoded UTF-16 (little endian).
+fn encode_ref_segment(segment: &str) -> String {
+    segment
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn decode_ref_segment(segment: &str) -> Option<String> {
+    if segment.len() % 4 != 0 || !segment.is_ascii() {
+        return None;
+    }
+    let units = (0..segment.len())
+        .step_by(4)
+        .map(|index| {
+            let low = u8::from_str_radix(&segment[index..index + 2], 16).ok()?;
+            let high = u8::from_str_radix(&segment[index + 2..index + 4], 16).ok()?;
+            Some(u16::from_le_bytes([low, high]))
+        })
+        .collect::<Option<Vec<u16>>>()?;
+    String::from_utf16(&units).ok()
+}
+
+impl SecurityToken for GitRepositoriesToken {
+    fn namespace_id(&self) -> &'static str {
+        namespace::GIT_REPOSITORIES
+    }
+}
+
+impl fmt::Display for GitRepositoriesToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => f.write_str(REPO_V2),
+            Self::Project { project_id } => write!(f, "{REPO_V2}/{project_id}"),
+            Self::Repository {
+                project_id,
+                repository_id,
+            } => write!(f, "{REPO_V2}/{project_id}/{repository_id}"),
+            Self::Ref {
+                project_id,
+                repository_id,
+                ref_name,
+            } => {
+                write!(f, "{REPO_V2}/{project_id}/{repository_id}")?;
+                // `refs/heads` (or `refs/tags`) are kept as they are.
+                for (index, segment) in ref_name.split('/').enumerate() {
+                    if index < 2 {
+                        write!(f, "/{segment}")?;
+                    } else {
+                        write!(f, "/{}", encode_ref_segment(segment))?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl FromStr for GitRepositoriesToken {
+    type Err = Error;
+
+    fn from_str(token: &str) -> azure_core::Result<Self> {
+        let segments: Vec<&str> = token.trim_end_matches('/').split('/').collect();
+        match segments.as_slice() {
+            [REPO_V2] => Ok(Self::All),
+            [REPO_V2, project_id] => Ok(Self::project(*project_id)),
+            [REPO_V2, project_id, repository_id] => {
+                Ok(Self::repository(*project_id, *repository_id))
+            }
+            [REPO_V2, project_id, repository_id, "refs", kind, name @ ..] if !name.is_empty() => {
+                let name = name
+                    .iter()
+                    .map(|segment| decode_ref_segment(segment))
+                    .collect::<Option<Vec<String>>>()
+                    .ok_or_else(|| invalid("Git Repositories", token))?;
+                Ok(Self::Ref {
+                    project_id: project_id.to_string(),
+                    repository_id: repository_id.to_string(),
+                    ref_name: format!("refs/{kind}/{}", name.join("/")),
+                })
+            }
+            _ => Err(invalid("Git Repositories", token)),
+        }
+    }
+}
+
+/// Splits a folder path such as `\Folder\Sub` into its segments.
+fn folder_segments(folder: &str) -> Vec<String> {
+    folder
+        .split(['\\', '/'])
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// A Build namespace token: `{project_id}`, optionally followed by folder segments and a
+/// pipeline (build definition) ID.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BuildToken {
+    pub project_id: String,
+    pub folder: Vec<String>,
+    pub definition_id: Option<i32>,
+}
+
+impl BuildToken {
+    pub fn project(project_id: impl Into<String>) -> Self {
+        Self {
+            project_id: project_id.into(),
+            folder: Vec::new(),
+            definition_id: None,
+        }
+    }
+
+    /// A folder, e.g. `\Folder\Sub`.
+    pub fn folder(project_id: impl Into<String>, folder: &str) -> Self {
+        Self {
+            folder: folder_segments(folder),
+            ..Self::project(project_id)
+        }
+    }
+
+    pub fn definition(project_id: impl Into<String>, definition_id: i32) -> Self {
+        Self {
+            definition_id: Some(definition_id),
+            ..Self::project(project_id)
+        }
+    }
+
+    /// Sets the folder of a definition token, e.g. `\Folder\Sub`.
+    #[must_use]
+    pub fn in_folder(mut self, folder: &str) -> Self {
+        self.folder = folder_segments(folder);
+        self
+    }
+}
+
+impl SecurityToken for BuildToken {
+    fn namespace_id(&self) -> &'static str {
+        namespace::BUILD
+    }
+}
+
+/// Formats `{project_id}/{folder...}/{definition_id}`.
+fn fmt_definition_path(
+    f: &mut fmt::Formatter<'_>,
+    project_id: &str,
+    folder: &[String],
+    definition_id: Option<i32>,
+) -> fmt::Result {
+    f.write_str(project_id)?;
+    for segment in folder {
+        write!(f, "/{segment}")?;
+    }
+    if let Some(definition_id) = definition_id {
+        write!(f, "/{definition_id}")?;
+    }
+    Ok(())
+}
+
+/// Parses `{project_id}/{folder...}/{definition_id}`; a numeric last segment is taken to be
+/// the definition ID.
+fn parse_definition_path(segments: &[&str]) -> Option<(String, Vec<String>, Option<i32>)> {
+    let (project_id, rest) = segments.split_first()?;
+    if project_id.is_empty() {
+        return None;
+    }
+    let (definition_id, folder) = match rest.split_last() {
+        Some((last, folder)) => match last.parse::<i32>() {
+            Ok(definition_id) => (Some(definition_id), folder),
+            Err(_) => (None, rest),
+        },
+        None => (None, rest),
+    };
+    let folder = folder.iter().map(|segment| segment.to_string()).collect();
+    Some((project_id.to_string(), folder, definition_id))
+}
+
+impl fmt::Display for BuildToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_definition_path(f, &self.project_id, &self.folder, self.definition_id)
+    }
+}
+
+impl FromStr for BuildToken {
+    type Err = Error;
+
+    fn from_str(token: &str) -> azure_core::Result<Self> {
+        let segments: Vec<&str> = token.trim_end_matches('/').split('/').collect();
+        let (project_id, folder, definition_id) =
+            parse_definition_path(&segments).ok_or_else(|| invalid("Build", token))?;
+        Ok(Self {
+            project_id,
+            folder,
+            definition_id,
+        })
+    }
+}
+
+/// A ReleaseManagement namespace token: `{project_id}`, optionally followed by folder segments,
+/// a release definition ID and `Environment/{environment_id}`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ReleaseManagementToken {
+    pub project_id: String,
+    pub folder: Vec<String>,
+    pub definition_id: Option<i32>,
+    /// Only set with a definition ID.
+    pub environment_id: Option<i32>,
+}
+
+impl ReleaseManagementToken {
+    pub fn project(project_id: impl Into<String>) -> Self {
+        Self {
+            project_id: project_id.into(),
+            folder: Vec::new(),
+            definition_id: None,
+            environment_id: None,
+        }
+    }
+
+    /// A folder, e.g. `\Folder\Sub`.
+    pub fn folder(project_id: impl Into<String>, folder: &str) -> Self {
+        Self {
+            folder: folder_segments(folder),
+            ..Self::project(project_id)
+        }
+    }
+
+    pub fn definition(project_id: impl Into<String>, definition_id: i32) -> Self {
+        Self {
+            definition_id: Some(definition_id),
+            ..Self::project(project_id)
+        }
+    }
+
+    /// A stage (environment) of a release definition.
+    pub fn environment(
+        project_id: impl Into<String>,
+        definition_id: i32,
+        environment_id: i32,
+    ) -> Self {
+        Self {
+            environment_id: Some(environment_id),
+            ..Self::definition(project_id, definition_id)
+        }
+    }
+
+    /// Sets the folder of a definition or environment token, e.g. `\Folder\Sub`.
+    #[must_use]
+    pub fn in_folder(mut self, folder: &str) -> Self {
+        self.folder = folder_segments(folder);
+        self
+    }
+}
+
+impl SecurityToken for ReleaseManagementToken {
+    fn namespace_id(&self) -> &'static str {
+        namespace::RELEASE_MANAGEMENT
+    }
+}
+
+impl fmt::Display for ReleaseManagementToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_definition_path(f, &self.project_id, &self.folder, self.definition_id)?;
+        match (self.definition_id, self.environment_id) {
+            (Some(_), Some(environment_id)) => write!(f, "/Environment/{environment_id}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl FromStr for ReleaseManagementToken {
+    type Err = Error;
+
+    fn from_str(token: &str) -> azure_core::Result<Self> {
+        let mut segments: Vec<&str> = token.trim_end_matches('/').split('/').collect();
+        let mut environment_id = None;
+        if let [.., "Environment", environment] = segments.as_slice() {
+            environment_id = Some(
+                environment
+                    .parse::<i32>()
+                    .map_err(|_| invalid("ReleaseManagement", token))?,
+            );
+            segments.truncate(segments.len() - 2);
+        }
+        let (project_id, folder, definition_id) = parse_definition_path(&segments)
+            .filter(|(_, _, definition_id)| environment_id.is_none() || definition_id.is_some())
+            .ok_or_else(|| invalid("ReleaseManagement", token))?;
+        Ok(Self {
+            project_id,
+            folder,
+            definition_id,
+            environment_id,
+        })
+    }
+}
+
+/// The classification structure of a [`ClassificationNodeToken`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ClassificationStructure {
+    /// Area paths, secured by the CSS namespace.
+    Area,
+    /// Iteration paths, secured by the Iteration namespace.
+    Iteration,
+}
+
+const CLASSIFICATION_NODE: &str = "vstfs:///Classification/Node/";
+
+/// A CSS (area) or Iteration namespace token: the identifiers (GUIDs) of a classification node
+/// and its ancestors, from the root node, as
+/// `vstfs:///Classification/Node/{root}:vstfs:///Classification/Node/{child}...`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClassificationNodeToken {
+    pub structure: ClassificationStructure,
+    pub node_ids: Vec<String>,
+}
+
+impl ClassificationNodeToken {
+    /// A token from the node identifiers, starting at the root node.
+    pub fn new(
+        structure: ClassificationStructure,
+        node_ids: impl IntoIterator<Item = impl Into<String>>,
+    ) -> Self {
+        Self {
+            structure,
+            node_ids: node_ids.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// The token of a child node.
+    #[must_use]
+    pub fn child(mut self, node_id: impl Into<String>) -> Self {
+        self.node_ids.push(node_id.into());
+        self
+    }
+
+    /// The token of the parent node, if this isn't the root node.
+    pub fn parent(&self) -> Option<Self> {
+        (self.node_ids.len() > 1).then(|| Self {
+            structure: self.structure,
+            node_ids: self.node_ids[..self.node_ids.len() - 1].to_vec(),
+        })
+    }
+
+    /// Parses a token of the given structure (which the token itself doesn't record).
+    pub fn parse(structure: ClassificationStructure, token: &str) -> azure_core::Result<Self> {
+        let node_ids = token
+            .split(':')
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            // `vstfs:///...` contains a colon, so the parts alternate `vstfs` and `///...`.
+            .chunks(2)
+            .map(|chunk| match chunk {
+                ["vstfs", node] => node
+                    .strip_prefix("///Classification/Node/")
+                    .filter(|id| !id.is_empty())
+                    .map(str::to_string),
+                _ => None,
+            })
+            .collect::<Option<Vec<String>>>()
+            .filter(|node_ids| !node_ids.is_empty())
+            .ok_or_else(|| invalid("classification node", token))?;
+        Ok(Self {
+            structure,
+            node_ids,
+        })
+    }
+}
+
+impl SecurityToken for ClassificationNodeToken {
+    fn namespace_id(&self) -> &'static str {
+        match self.structure {
+            ClassificationStructure::Area => namespace::CSS,
+            ClassificationStructure::Iteration => namespace::ITERATION,
+        }
+    }
+}
+
+impl fmt::Display for ClassificationNodeToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, node_id) in self.node_ids.iter().enumerate() {
+            if index > 0 {
+                f.write_str(":")?;
+            }
+            write!(f, "{CLASSIFICATION_NODE}{node_id}")?;
+        }
+        Ok(())
+    }
+}
+
+const PROJECT_PREFIX: &str = "$PROJECT:vstfs:///Classification/TeamProject/";
+
+/// A Project namespace token: `$PROJECT:vstfs:///Classification/TeamProject/{project_id}`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ProjectToken {
+    pub project_id: String,
+}
+
+impl ProjectToken {
+    pub fn new(project_id: impl Into<String>) -> Self {
+        Self {
+            project_id: project_id.into(),
+        }
+    }
+}
+
+impl SecurityToken for ProjectToken {
+    fn namespace_id(&self) -> &'static str {
+        namespace::PROJECT
+    }
+}
+
+impl fmt::Display for ProjectToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{PROJECT_PREFIX}{}", self.project_id)
+    }
+}
+
+impl FromStr for ProjectToken {
+    type Err = Error;
+
+    fn from_str(token: &str) -> azure_core::Result<Self> {
+        token
+            .strip_prefix(PROJECT_PREFIX)
+            .filter(|project_id| !project_id.is_empty() && !project_id.contains([':', '/']))
+            .map(ProjectToken::new)
+            .ok_or_else(|| invalid("Project", token))
+    }
+}
+
+#[cfg(feature = "wit")]
+impl crate::wit::Client {
+    /// Looks up the security token of an area or iteration path, such as `Project\Team`.
+    ///
+    /// Fetches the root node and each ancestor of the node to collect their identifiers.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `structure`: Areas or iterations.
+    /// * `path`: The area or iteration path, starting with the root node (the project name).
+    pub async fn classification_node_token(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        structure: ClassificationStructure,
+        path: &str,
+    ) -> azure_core::Result<ClassificationNodeToken> {
+        let organization = organization.into();
+        let project = project.into();
+        let structure_group = match structure {
+            ClassificationStructure::Area => "areas",
+            ClassificationStructure::Iteration => "iterations",
+        };
+        // The path of a node below the root node excludes the root node's name.
+        let segments: Vec<String> = folder_segments(path).into_iter().skip(1).collect();
+        let mut node_ids = Vec::with_capacity(segments.len() + 1);
+        for depth in 0..=segments.len() {
+            let node = self
+                .classification_nodes_client()
+                .get(
+                    &organization,
+                    &project,
+                    structure_group,
+                    segments[..depth].join("/"),
+                )
+                .await?;
+            let identifier = node.identifier.ok_or_else(|| {
+                Error::with_message(ErrorKind::DataConversion, || {
+                    format!("classification node {path} has no identifier")
+                })
+            })?;
+            node_ids.push(identifier);
+        }
+        Ok(ClassificationNodeToken::new(structure, node_ids))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROJECT: &str = "6ce954b1-ce1f-45d1-b94d-e6bf2464ba2c";
+    const REPOSITORY: &str = "2b6a3a7f-14c0-4e4a-9a53-2c0b4e3e2c3a";
+
+    #[test]
+    fn test_git_repositories_token() {
+        let token = GitRepositoriesToken::branch(PROJECT, REPOSITORY, "feature/ü");
+        assert_eq!(
+            token.to_string(),
+            format!("repoV2/{PROJECT}/{REPOSITORY}/refs/heads/6600650061007400750072006500/fc00")
+        );
+        assert_eq!(
+            token.to_string().parse::<GitRepositoriesToken>().unwrap(),
+            token
+        );
+        assert_eq!(token.namespace_id(), namespace::GIT_REPOSITORIES);
+        assert_eq!(
+            format!("repoV2/{PROJECT}")
+                .parse::<GitRepositoriesToken>()
+                .unwrap(),
+            GitRepositoriesToken::project(PROJECT)
+        );
+        assert_eq!(
+            "repoV2".parse::<GitRepositoriesToken>().unwrap(),
+            GitRepositoriesToken::All
+        );
+        assert!(format!("repoV2/{PROJECT}/{REPOSITORY}/refs/heads/zz")
+            .parse::<GitRepositoriesToken>()
+            .is_err());
+        assert!("repo".parse::<GitRepositoriesToken>().is_err());
+    }
+
+    #[test]
+    fn test_build_and_release_tokens() {
+        let build = BuildToken::definition(PROJECT, 12).in_folder(r"\Team\CI");
+        assert_eq!(build.to_string(), format!("{PROJECT}/Team/CI/12"));
+        assert_eq!(build.to_string().parse::<BuildToken>().unwrap(), build);
+        assert_eq!(
+            format!("{PROJECT}/Team").parse::<BuildToken>().unwrap(),
+            BuildToken::folder(PROJECT, "Team")
+        );
+
+        let release = ReleaseManagementToken::environment(PROJECT, 3, 7).in_folder(r"\Prod");
+        assert_eq!(
+            release.to_string(),
+            format!("{PROJECT}/Prod/3/Environment/7")
+        );
+        assert_eq!(
+            release
+                .to_string()
+                .parse::<ReleaseManagementToken>()
+                .unwrap(),
+            release
+        );
+        assert!(format!("{PROJECT}/Environment/7")
+            .parse::<ReleaseManagementToken>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_classification_and_project_tokens() {
+        let area =
+            ClassificationNodeToken::new(ClassificationStructure::Area, ["root"]).child("team");
+        assert_eq!(
+            area.to_string(),
+            "vstfs:///Classification/Node/root:vstfs:///Classification/Node/team"
+        );
+        assert_eq!(
+            ClassificationNodeToken::parse(ClassificationStructure::Area, &area.to_string())
+                .unwrap(),
+            area
+        );
+        assert_eq!(area.parent().unwrap().node_ids, ["root"]);
+        assert_eq!(area.namespace_id(), namespace::CSS);
+        assert!(
+            ClassificationNodeToken::parse(ClassificationStructure::Iteration, "root").is_err()
+        );
+
+        let project = ProjectToken::new(PROJECT);
+        assert_eq!(
+            project.to_string(),
+            format!("$PROJECT:vstfs:///Classification/TeamProject/{PROJECT}")
+        );
+        assert_eq!(
+            project.to_string().parse::<ProjectToken>().unwrap(),
+            project
+        );
+    }
+}
@@ -611,7 +611,7 @@ impl DependsOn {
 pub struct EndpointAuthorization {
     #[doc = "Gets or sets the parameters for the selected authorization scheme."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub parameters: Option<serde_json::Value>,
+    pub parameters: Option<crate::secret::Secret<serde_json::Value>>,
     #[doc = "Gets or sets the scheme used for service endpoint authentication."]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub scheme: Option<String>,
@@ -1308,8 +1308,8 @@ pub struct ServiceEndpoint {
     pub administrators_group: Option<IdentityRef>,
     #[doc = "Represents the authorization used for service endpoint."]
     pub authorization: EndpointAuthorization,
-    #[serde(rename = "createdBy")]
-    pub created_by: IdentityRef,
+    #[serde(rename = "createdBy", default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<IdentityRef>,
     pub data: serde_json::Value,
     #[doc = "Gets or sets the description of endpoint."]
     pub description: String,
@@ -1321,7 +1321,8 @@ pub struct ServiceEndpoint {
     )]
     pub group_scope_id: Option<String>,
     #[doc = "Gets or sets the identifier of this endpoint."]
-    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
     #[doc = "EndPoint state indicator"]
     #[serde(rename = "isReady")]
     pub is_ready: bool,
@@ -1362,10 +1363,8 @@ pub struct ServiceEndpoint {
 impl ServiceEndpoint {
     pub fn new(
         authorization: EndpointAuthorization,
-        created_by: IdentityRef,
         data: serde_json::Value,
         description: String,
-        id: String,
         is_ready: bool,
         is_shared: bool,
         name: String,
@@ -1376,11 +1375,11 @@ impl ServiceEndpoint {
         Self {
             administrators_group: None,
             authorization,
-            created_by,
+            created_by: None,
             data,
             description,
             group_scope_id: None,
-            id,
+            id: None,
             is_ready,
             is_shared,
             name,
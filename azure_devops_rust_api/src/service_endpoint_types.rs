@@ -0,0 +1,658 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Typed service endpoint authorization schemes and endpoint data.
+//!
+//! [`AuthorizationScheme`] converts to and from the `scheme` and free-form `parameters` of an
+//! [`EndpointAuthorization`], and [`NewServiceEndpoint`] builds the [`ServiceEndpoint`] to
+//! create for common endpoint types. `service_endpoint::Client::share_endpoint()` shares an
+//! existing endpoint with other projects.
+//!
+//! ```rust
+//!     let endpoint = NewServiceEndpoint::azure_rm(
+//!         "production",
+//!         AzureRmData::subscription(subscription_id, "Production"),
+//!         AuthorizationScheme::WorkloadIdentityFederation {
+//!             tenant_id: tenant_id.clone(),
+//!             service_principal_id: client_id.clone(),
+//!         },
+//!     )
+//!     .project(&project_id, &project_name);
+//!     let endpoint = service_endpoint_client
+//!         .endpoints_client()
+//!         .create(&organization, endpoint)
+//!         .await?;
+//! ```
+
+use crate::secret::Secret;
+use crate::service_endpoint::models::{
+    EndpointAuthorization, ProjectReference, ServiceEndpoint, ServiceEndpointProjectReference,
+};
+use azure_core::error::{Error, ErrorKind};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// The authorization scheme of a service endpoint, with its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AuthorizationScheme {
+    /// An Azure AD service principal authenticating with a secret or a certificate.
+    ServicePrincipal {
+        tenant_id: String,
+        service_principal_id: String,
+        credential: ServicePrincipalCredential,
+    },
+    /// An Azure AD service principal (or managed identity) trusting tokens issued by
+    /// Azure DevOps, without a stored secret.
+    WorkloadIdentityFederation {
+        tenant_id: String,
+        service_principal_id: String,
+    },
+    /// The managed identity of the agent's machine.
+    ManagedServiceIdentity { tenant_id: String },
+    /// Basic authentication, e.g. for Docker registries.
+    UsernamePassword { username: String, password: Secret },
+    /// An API token, e.g. for Kubernetes service accounts or generic endpoints.
+    Token { token: Secret },
+    /// A personal access token, e.g. for GitHub.
+    PersonalAccessToken { token: Secret },
+    /// No authorization.
+    None,
+}
+
+/// The credential of a [`AuthorizationScheme::ServicePrincipal`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum ServicePrincipalCredential {
+    /// A client secret.
+    Key(Secret),
+    /// A PEM encoded certificate, including its private key.
+    Certificate(Secret),
+}
+
+impl AuthorizationScheme {
+    /// The name of the scheme, as used in [`EndpointAuthorization::scheme`].
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            AuthorizationScheme::ServicePrincipal { .. } => "ServicePrincipal",
+            AuthorizationScheme::WorkloadIdentityFederation { .. } => "WorkloadIdentityFederation",
+            AuthorizationScheme::ManagedServiceIdentity { .. } => "ManagedServiceIdentity",
+            AuthorizationScheme::UsernamePassword { .. } => "UsernamePassword",
+            AuthorizationScheme::Token { .. } => "Token",
+            AuthorizationScheme::PersonalAccessToken { .. } => "PersonalAccessToken",
+            AuthorizationScheme::None => "None",
+        }
+    }
+
+    fn parameters(&self) -> Value {
+        match self {
+            AuthorizationScheme::ServicePrincipal {
+                tenant_id,
+                service_principal_id,
+                credential,
+            } => {
+                let (authentication_type, key, value) = match credential {
+                    ServicePrincipalCredential::Key(key) => {
+                        ("spnKey", "serviceprincipalkey", key.secret())
+                    }
+                    ServicePrincipalCredential::Certificate(certificate) => (
+                        "spnCertificate",
+                        "servicePrincipalCertificate",
+                        certificate.secret(),
+                    ),
+                };
+                json!({
+                    "tenantid": tenant_id,
+                    "serviceprincipalid": service_principal_id,
+                    "authenticationType": authentication_type,
+                    (key): value,
+                })
+            }
+            AuthorizationScheme::WorkloadIdentityFederation {
+                tenant_id,
+                service_principal_id,
+            } => json!({
+                "tenantid": tenant_id,
+                "serviceprincipalid": service_principal_id,
+            }),
+            AuthorizationScheme::ManagedServiceIdentity { tenant_id } => {
+                json!({ "tenantid": tenant_id })
+            }
+            AuthorizationScheme::UsernamePassword { username, password } => json!({
+                "username": username,
+                "password": password.secret(),
+            }),
+            AuthorizationScheme::Token { token } => json!({ "apitoken": token.secret() }),
+            AuthorizationScheme::PersonalAccessToken { token } => {
+                json!({ "accessToken": token.secret() })
+            }
+            AuthorizationScheme::None => json!({}),
+        }
+    }
+}
+
+impl From<AuthorizationScheme> for EndpointAuthorization {
+    fn from(scheme: AuthorizationScheme) -> Self {
+        Self {
+            parameters: Some(Secret::new(scheme.parameters())),
+            scheme: Some(scheme.scheme().to_string()),
+        }
+    }
+}
+
+/// Reads the scheme and parameters of an endpoint authorization. Secret parameters are
+/// usually not returned by the service and are then empty.
+impl TryFrom<&EndpointAuthorization> for AuthorizationScheme {
+    type Error = Error;
+
+    fn try_from(authorization: &EndpointAuthorization) -> azure_core::Result<Self> {
+        let empty = Value::Object(Map::new());
+        let parameters = authorization
+            .parameters
+            .as_ref()
+            .map_or(&empty, |parameters| parameters.secret());
+        let parameter = |name: &str| -> String {
+            parameters
+                .as_object()
+                .and_then(|parameters| {
+                    parameters
+                        .iter()
+                        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                })
+                .and_then(|(_, value)| value.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let scheme = authorization.scheme.as_deref().unwrap_or("None");
+        let scheme = match scheme {
+            "ServicePrincipal" => AuthorizationScheme::ServicePrincipal {
+                tenant_id: parameter("tenantid"),
+                service_principal_id: parameter("serviceprincipalid"),
+                credential: if parameter("authenticationType") == "spnCertificate" {
+                    ServicePrincipalCredential::Certificate(
+                        parameter("servicePrincipalCertificate").into(),
+                    )
+                } else {
+                    ServicePrincipalCredential::Key(parameter("serviceprincipalkey").into())
+                },
+            },
+            "WorkloadIdentityFederation" => AuthorizationScheme::WorkloadIdentityFederation {
+                tenant_id: parameter("tenantid"),
+                service_principal_id: parameter("serviceprincipalid"),
+            },
+            "ManagedServiceIdentity" => AuthorizationScheme::ManagedServiceIdentity {
+                tenant_id: parameter("tenantid"),
+            },
+            "UsernamePassword" => AuthorizationScheme::UsernamePassword {
+                username: parameter("username"),
+                password: parameter("password").into(),
+            },
+            "Token" => AuthorizationScheme::Token {
+                token: parameter("apitoken").into(),
+            },
+            "PersonalAccessToken" => AuthorizationScheme::PersonalAccessToken {
+                token: parameter("accessToken").into(),
+            },
+            "None" => AuthorizationScheme::None,
+            scheme => {
+                return Err(Error::with_message(ErrorKind::DataConversion, || {
+                    format!("unsupported service endpoint authorization scheme: {scheme}")
+                }))
+            }
+        };
+        Ok(scheme)
+    }
+}
+
+/// The data of an Azure Resource Manager (`azurerm`) service endpoint.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AzureRmData {
+    /// `Subscription` or `ManagementGroup`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope_level: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub management_group_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub management_group_name: Option<String>,
+    /// The Azure cloud, e.g. `AzureCloud` or `AzureUSGovernment`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// `Manual` for an existing service principal, `Automatic` to let Azure DevOps create one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creation_mode: Option<String>,
+}
+
+impl AzureRmData {
+    /// Data for a manually created connection to a subscription in the Azure public cloud.
+    pub fn subscription(
+        subscription_id: impl Into<String>,
+        subscription_name: impl Into<String>,
+    ) -> Self {
+        Self {
+            scope_level: Some("Subscription".to_string()),
+            subscription_id: Some(subscription_id.into()),
+            subscription_name: Some(subscription_name.into()),
+            environment: Some("AzureCloud".to_string()),
+            creation_mode: Some("Manual".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Reads the data of an `azurerm` endpoint; `None` for other endpoint types.
+    pub fn from_endpoint(endpoint: &ServiceEndpoint) -> Option<Self> {
+        if !endpoint.type_.eq_ignore_ascii_case(AZURE_RM) {
+            return None;
+        }
+        serde_json::from_value(endpoint.data.clone()).ok()
+    }
+}
+
+/// The data of a Docker registry (`dockerregistry`) service endpoint.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DockerRegistryData {
+    /// `DockerHub`, `ACR` or `Others`.
+    #[serde(
+        rename = "registrytype",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub registry_type: Option<String>,
+}
+
+impl DockerRegistryData {
+    /// Reads the data of a `dockerregistry` endpoint; `None` for other endpoint types.
+    pub fn from_endpoint(endpoint: &ServiceEndpoint) -> Option<Self> {
+        if !endpoint.type_.eq_ignore_ascii_case(DOCKER_REGISTRY) {
+            return None;
+        }
+        serde_json::from_value(endpoint.data.clone()).ok()
+    }
+}
+
+const AZURE_RM: &str = "azurerm";
+const DOCKER_REGISTRY: &str = "dockerregistry";
+
+/// A service endpoint to create, converted into a [`ServiceEndpoint`] for
+/// `service_endpoint::endpoints::Client::create()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NewServiceEndpoint {
+    name: String,
+    description: String,
+    type_: String,
+    url: String,
+    data: Value,
+    authorization: AuthorizationScheme,
+    /// IDs and names of the projects to create the endpoint in.
+    projects: Vec<(String, String)>,
+}
+
+impl NewServiceEndpoint {
+    /// An endpoint of any type; `data` is the type specific endpoint data.
+    pub fn new(
+        name: impl Into<String>,
+        type_: impl Into<String>,
+        url: impl Into<String>,
+        authorization: AuthorizationScheme,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            description: String::new(),
+            type_: type_.into(),
+            url: url.into(),
+            data: Value::Object(Map::new()),
+            authorization,
+            projects: Vec::new(),
+        }
+    }
+
+    /// An Azure Resource Manager connection.
+    pub fn azure_rm(
+        name: impl Into<String>,
+        data: AzureRmData,
+        authorization: AuthorizationScheme,
+    ) -> Self {
+        let url = match data.environment.as_deref() {
+            Some("AzureUSGovernment") => "https://management.usgovcloudapi.net/",
+            Some("AzureChinaCloud") => "https://management.chinacloudapi.cn/",
+            _ => "https://management.azure.com/",
+        };
+        Self::new(name, AZURE_RM, url, authorization).data(json!(data))
+    }
+
+    /// A GitHub connection, usually authorized with a personal access token.
+    pub fn github(name: impl Into<String>, authorization: AuthorizationScheme) -> Self {
+        Self::new(name, "github", "https://github.com", authorization)
+    }
+
+    /// A Docker registry connection authorized with a username and password.
+    pub fn docker_registry(
+        name: impl Into<String>,
+        registry: impl Into<String>,
+        username: impl Into<String>,
+        password: impl Into<Secret>,
+    ) -> Self {
+        let registry = registry.into();
+        let registry_type = if registry.contains("index.docker.io") {
+            "DockerHub"
+        } else {
+            "Others"
+        };
+        let authorization = AuthorizationScheme::UsernamePassword {
+            username: username.into(),
+            password: password.into(),
+        };
+        Self::new(name, DOCKER_REGISTRY, registry, authorization).data(json!(DockerRegistryData {
+            registry_type: Some(registry_type.to_string()),
+        }))
+    }
+
+    /// A generic connection to a server.
+    pub fn generic(
+        name: impl Into<String>,
+        url: impl Into<String>,
+        authorization: AuthorizationScheme,
+    ) -> Self {
+        Self::new(name, "generic", url, authorization)
+    }
+
+    #[must_use]
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the type specific endpoint data.
+    #[must_use]
+    pub fn data(mut self, data: Value) -> Self {
+        self.data = data;
+        self
+    }
+
+    /// Adds a project the endpoint is created in. At least one project is required;
+    /// the endpoint is shared if more than one is added.
+    #[must_use]
+    pub fn project(
+        mut self,
+        project_id: impl Into<String>,
+        project_name: impl Into<String>,
+    ) -> Self {
+        self.projects.push((project_id.into(), project_name.into()));
+        self
+    }
+}
+
+impl From<NewServiceEndpoint> for ServiceEndpoint {
+    fn from(endpoint: NewServiceEndpoint) -> Self {
+        let mut service_endpoint = ServiceEndpoint::new(
+            endpoint.authorization.into(),
+            endpoint.data,
+            endpoint.description,
+            false,
+            endpoint.projects.len() > 1,
+            endpoint.name,
+            "library".to_string(),
+            endpoint.type_,
+            endpoint.url,
+        );
+        service_endpoint.service_endpoint_project_references = endpoint
+            .projects
+            .into_iter()
+            .map(|(project_id, project_name)| {
+                project_reference(
+                    project_id,
+                    project_name,
+                    service_endpoint.name.clone(),
+                    service_endpoint.description.clone(),
+                )
+            })
+            .collect();
+        service_endpoint
+    }
+}
+
+fn project_reference(
+    project_id: String,
+    project_name: String,
+    name: String,
+    description: String,
+) -> ServiceEndpointProjectReference {
+    ServiceEndpointProjectReference::new(
+        description,
+        name,
+        ProjectReference::new(project_id, project_name),
+    )
+}
+
+impl crate::service_endpoint::Client {
+    /// Creates a builder that shares a service endpoint with other projects.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name of a project the endpoint is already in.
+    /// * `endpoint_id`: Id of the service endpoint.
+    pub fn share_endpoint(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        endpoint_id: impl Into<String>,
+    ) -> ShareEndpointBuilder {
+        ShareEndpointBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            endpoint_id: endpoint_id.into(),
+            projects: Vec::new(),
+            name: None,
+        }
+    }
+}
+
+/// `ShareEndpointBuilder` provides a mechanism for setting the projects to share an
+/// endpoint with.
+///
+/// To share the endpoint, call `.await`, which returns the IDs of the projects the endpoint
+/// was newly shared with; projects it is already in are skipped.
+#[derive(Clone)]
+pub struct ShareEndpointBuilder {
+    client: crate::service_endpoint::Client,
+    organization: String,
+    project: String,
+    endpoint_id: String,
+    projects: Vec<(String, String)>,
+    name: Option<String>,
+}
+
+impl ShareEndpointBuilder {
+    /// Adds a project to share the endpoint with.
+    #[must_use]
+    pub fn project(
+        mut self,
+        project_id: impl Into<String>,
+        project_name: impl Into<String>,
+    ) -> Self {
+        self.projects.push((project_id.into(), project_name.into()));
+        self
+    }
+
+    /// The name of the endpoint in the projects it is shared with (default: its current name).
+    #[must_use]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+}
+
+impl std::future::IntoFuture for ShareEndpointBuilder {
+    type Output = azure_core::Result<Vec<String>>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<Vec<String>>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            let endpoints = self.client.endpoints_client();
+            let endpoint = endpoints
+                .get(&self.organization, &self.project, &self.endpoint_id)
+                .await?;
+            let references = share_references(&endpoint, &self.projects, self.name.as_deref());
+            let shared = references
+                .iter()
+                .map(|reference| reference.project_reference.id.clone())
+                .collect();
+            if !references.is_empty() {
+                endpoints
+                    .share_service_endpoint(&self.organization, references, &self.endpoint_id)
+                    .await?;
+            }
+            Ok(shared)
+        })
+    }
+}
+
+/// The project references to share `endpoint` with the given projects, skipping the
+/// projects it is already in.
+fn share_references(
+    endpoint: &ServiceEndpoint,
+    projects: &[(String, String)],
+    name: Option<&str>,
+) -> Vec<ServiceEndpointProjectReference> {
+    let mut references: Vec<ServiceEndpointProjectReference> = Vec::new();
+    for (project_id, project_name) in projects {
+        let already_shared = endpoint
+            .service_endpoint_project_references
+            .iter()
+            .chain(references.iter())
+            .any(|reference| {
+                reference
+                    .project_reference
+                    .id
+                    .eq_ignore_ascii_case(project_id)
+            });
+        if !already_shared {
+            references.push(project_reference(
+                project_id.clone(),
+                project_name.clone(),
+                name.unwrap_or(&endpoint.name).to_string(),
+                endpoint.description.clone(),
+            ));
+        }
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorization_round_trip() {
+        let scheme = AuthorizationScheme::ServicePrincipal {
+            tenant_id: "tenant".to_string(),
+            service_principal_id: "client".to_string(),
+            credential: ServicePrincipalCredential::Key("s3cret".into()),
+        };
+        let authorization = EndpointAuthorization::from(scheme.clone());
+        assert_eq!(
+            serde_json::to_value(&authorization).unwrap(),
+            json!({
+                "scheme": "ServicePrincipal",
+                "parameters": {
+                    "tenantid": "tenant",
+                    "serviceprincipalid": "client",
+                    "authenticationType": "spnKey",
+                    "serviceprincipalkey": "s3cret"
+                }
+            })
+        );
+        assert!(!format!("{authorization:?}").contains("s3cret"));
+        assert_eq!(
+            AuthorizationScheme::try_from(&authorization).unwrap(),
+            scheme
+        );
+
+        let authorization: EndpointAuthorization = serde_json::from_value(json!({
+            "scheme": "WorkloadIdentityFederation",
+            "parameters": {
+                "tenantid": "tenant",
+                "serviceprincipalid": "client",
+                "workloadIdentityFederationSubject": "sc://org/project/name"
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            AuthorizationScheme::try_from(&authorization).unwrap(),
+            AuthorizationScheme::WorkloadIdentityFederation {
+                tenant_id: "tenant".to_string(),
+                service_principal_id: "client".to_string(),
+            }
+        );
+        let unknown = EndpointAuthorization {
+            scheme: Some("Certificate".to_string()),
+            parameters: None,
+        };
+        assert!(AuthorizationScheme::try_from(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_new_azure_rm_endpoint() {
+        let endpoint: ServiceEndpoint = NewServiceEndpoint::azure_rm(
+            "production",
+            AzureRmData::subscription("sub", "Production"),
+            AuthorizationScheme::ManagedServiceIdentity {
+                tenant_id: "tenant".to_string(),
+            },
+        )
+        .project("p1", "Project 1")
+        .into();
+        assert_eq!(endpoint.type_, "azurerm");
+        let body = serde_json::to_value(&endpoint).unwrap();
+        assert!(body.get("id").is_none() && body.get("createdBy").is_none());
+        assert_eq!(endpoint.url, "https://management.azure.com/");
+        assert!(!endpoint.is_shared);
+        assert_eq!(
+            endpoint.data,
+            json!({
+                "scopeLevel": "Subscription",
+                "subscriptionId": "sub",
+                "subscriptionName": "Production",
+                "environment": "AzureCloud",
+                "creationMode": "Manual"
+            })
+        );
+        assert_eq!(
+            AzureRmData::from_endpoint(&endpoint).and_then(|data| data.subscription_id),
+            Some("sub".to_string())
+        );
+        assert_eq!(
+            endpoint.service_endpoint_project_references[0].name,
+            "production"
+        );
+    }
+
+    #[test]
+    fn test_share_references() {
+        let endpoint: ServiceEndpoint =
+            NewServiceEndpoint::generic("server", "https://example.com", AuthorizationScheme::None)
+                .project("p1", "Project 1")
+                .into();
+        let references = share_references(
+            &endpoint,
+            &[
+                ("P1".to_string(), "Project 1".to_string()),
+                ("p2".to_string(), "Project 2".to_string()),
+                ("p2".to_string(), "Project 2".to_string()),
+            ],
+            Some("shared-server"),
+        );
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].project_reference.id, "p2");
+        assert_eq!(references[0].name, "shared-server");
+    }
+}
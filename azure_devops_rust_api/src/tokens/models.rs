@@ -244,7 +244,7 @@ pub struct PatToken {
     pub target_accounts: Vec<String>,
     #[doc = "The unique token string generated at creation"]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub token: Option<String>,
+    pub token: Option<crate::secret::Secret<String>>,
     #[doc = "The token creation date"]
     #[serde(
         rename = "validFrom",
@@ -0,0 +1,721 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Streaming work item attachment upload and download.
+//!
+//! The generated [`wit::attachments`](crate::wit::attachments) operations take the attachment
+//! content as a single in-memory value. This module instead uploads content from a stream
+//! or reader (using the chunked upload protocol for large attachments), streams downloads to a
+//! writer, and links uploaded attachments to work items. Opening files is left to the caller,
+//! so any async runtime can be used.
+//!
+//! ```rust
+//!     // `file` is any `futures::io::AsyncRead`, e.g. a Tokio file wrapped with `tokio_util::compat`.
+//!     let content = wit_attachments::UploadContent::from_reader(length, file);
+//!     let attachment = wit_client
+//!         .upload_attachment(&organization, &project, "build.log", content)
+//!         .await?;
+//!     wit_client
+//!         .attach_to_work_item(&organization, &project, work_item_id, &attachment)
+//!         .comment("Build log")
+//!         .await?;
+//! ```
+
+use crate::wit::models::{
+    json_patch_operation::Op, AttachmentReference, JsonPatchOperation, WorkItem,
+};
+use azure_core::error::{Error, ErrorKind, ResultExt};
+use azure_core::{headers, Method, Request, Response, ResponseBody, Url};
+use bytes::{Bytes, BytesMut};
+use futures::{Stream, StreamExt};
+use serde_json::json;
+use std::pin::Pin;
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Default size of each chunk sent by a chunked upload.
+///
+/// Content up to this size is sent with a single (simple) upload request.
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Relation type used to link an attachment to a work item.
+pub const ATTACHED_FILE: &str = "AttachedFile";
+
+#[cfg(not(target_arch = "wasm32"))]
+type ByteStream = Pin<Box<dyn Stream<Item = azure_core::Result<Bytes>> + Send>>;
+#[cfg(target_arch = "wasm32")]
+type ByteStream = Pin<Box<dyn Stream<Item = azure_core::Result<Bytes>>>>;
+
+/// Attachment content to upload.
+pub struct UploadContent {
+    length: u64,
+    stream: ByteStream,
+}
+
+impl UploadContent {
+    /// Creates upload content from an in-memory buffer.
+    pub fn from_bytes(bytes: impl Into<Bytes>) -> Self {
+        let bytes = bytes.into();
+        Self {
+            length: bytes.len() as u64,
+            stream: Box::pin(futures::stream::once(futures::future::ready(Ok(bytes)))),
+        }
+    }
+
+    /// Creates upload content from a stream of known total length.
+    ///
+    /// The upload fails if the stream yields more or fewer than `length` bytes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_stream<S>(length: u64, stream: S) -> Self
+    where
+        S: Stream<Item = azure_core::Result<Bytes>> + Send + 'static,
+    {
+        Self {
+            length,
+            stream: Box::pin(stream),
+        }
+    }
+
+    /// Creates upload content from a stream of known total length.
+    ///
+    /// The upload fails if the stream yields more or fewer than `length` bytes.
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_stream<S>(length: u64, stream: S) -> Self
+    where
+        S: Stream<Item = azure_core::Result<Bytes>> + 'static,
+    {
+        Self {
+            length,
+            stream: Box::pin(stream),
+        }
+    }
+
+    /// Creates upload content that streams `length` bytes from `reader`.
+    ///
+    /// The upload fails if the reader yields more or fewer than `length` bytes.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_reader<R>(length: u64, reader: R) -> Self
+    where
+        R: futures::io::AsyncRead + Send + 'static,
+    {
+        Self::from_stream(length, read_chunks(reader))
+    }
+
+    /// Creates upload content that streams `length` bytes from `reader`.
+    ///
+    /// The upload fails if the reader yields more or fewer than `length` bytes.
+    #[cfg(target_arch = "wasm32")]
+    pub fn from_reader<R>(length: u64, reader: R) -> Self
+    where
+        R: futures::io::AsyncRead + 'static,
+    {
+        Self::from_stream(length, read_chunks(reader))
+    }
+
+    /// Total length of the content, in bytes.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns `true` if the content is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+}
+
+impl std::fmt::Debug for UploadContent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UploadContent")
+            .field("length", &self.length)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A downloaded attachment body, which can be consumed as a stream of `Bytes`.
+#[derive(Debug)]
+pub struct AttachmentDownload {
+    /// Content type reported by the service, or detected from the file name.
+    pub content_type: Option<String>,
+    /// Content length reported by the service.
+    pub content_length: Option<u64>,
+    /// The attachment content.
+    pub body: ResponseBody,
+}
+
+impl AttachmentDownload {
+    /// Writes the attachment content to `writer`, returning the number of bytes written.
+    pub async fn write_to<W>(self, writer: &mut W) -> azure_core::Result<u64>
+    where
+        W: futures::io::AsyncWrite + Unpin,
+    {
+        use futures::io::AsyncWriteExt;
+
+        let mut body = self.body;
+        let mut written = 0;
+        while let Some(chunk) = body.next().await {
+            let chunk = chunk?;
+            writer
+                .write_all(&chunk)
+                .await
+                .context(ErrorKind::Io, "Failed to write attachment content")?;
+            written += chunk.len() as u64;
+        }
+        writer
+            .flush()
+            .await
+            .context(ErrorKind::Io, "Failed to write attachment content")?;
+        Ok(written)
+    }
+}
+
+/// Reads `reader` as a stream of chunks.
+fn read_chunks<R>(reader: R) -> impl Stream<Item = azure_core::Result<Bytes>>
+where
+    R: futures::io::AsyncRead + 'static,
+{
+    futures::stream::try_unfold(Box::pin(reader), |mut reader| async move {
+        use futures::io::AsyncReadExt;
+
+        let mut buffer = vec![0; 64 * 1024];
+        let read = reader
+            .read(&mut buffer)
+            .await
+            .context(ErrorKind::Io, "Failed to read attachment content")?;
+        buffer.truncate(read);
+        Ok((read > 0).then(|| (Bytes::from(buffer), reader)))
+    })
+}
+
+impl crate::wit::Client {
+    /// Uploads an attachment, streaming its content.
+    ///
+    /// Content larger than the chunk size (default [`DEFAULT_CHUNK_SIZE`]) is uploaded using the
+    /// chunked upload protocol, so at most one chunk is held in memory at a time.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `file_name`: The name of the attachment file
+    /// * `content`: The attachment content
+    pub fn upload_attachment(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        file_name: impl Into<String>,
+        content: UploadContent,
+    ) -> UploadAttachmentBuilder {
+        UploadAttachmentBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            file_name: file_name.into(),
+            content,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            area_path: None,
+        }
+    }
+
+    /// Downloads an attachment as a stream.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `id`: Attachment ID
+    pub fn download_attachment(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        id: impl Into<String>,
+    ) -> DownloadAttachmentBuilder {
+        DownloadAttachmentBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            id: id.into(),
+            file_name: None,
+        }
+    }
+
+    /// Adds an `AttachedFile` relation from a work item to an uploaded attachment.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `work_item_id`: The id of the work item to attach to
+    /// * `attachment`: The uploaded attachment
+    pub fn attach_to_work_item(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        work_item_id: i32,
+        attachment: &AttachmentReference,
+    ) -> AttachToWorkItemBuilder {
+        AttachToWorkItemBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            work_item_id,
+            url: attachment.url.clone(),
+            comment: None,
+        }
+    }
+}
+
+/// `UploadAttachmentBuilder` provides a mechanism for setting optional upload parameters.
+///
+/// To upload the attachment, invoke `.await`, which returns the [`AttachmentReference`]
+/// of the new attachment.
+pub struct UploadAttachmentBuilder {
+    client: crate::wit::Client,
+    organization: String,
+    project: String,
+    file_name: String,
+    content: UploadContent,
+    chunk_size: usize,
+    area_path: Option<String>,
+}
+
+impl UploadAttachmentBuilder {
+    /// Size of each uploaded chunk.
+    #[must_use]
+    pub fn chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size.max(1);
+        self
+    }
+
+    /// Target project Area Path
+    #[must_use]
+    pub fn area_path(mut self, area_path: impl Into<String>) -> Self {
+        self.area_path = Some(area_path.into());
+        self
+    }
+
+    fn url(&self, id: Option<&str>) -> azure_core::Result<Url> {
+        let mut url = Url::parse(&format!(
+            "{}/{}/{}/_apis/wit/attachments",
+            self.client.endpoint(),
+            &self.organization,
+            &self.project
+        ))?;
+        if let Some(id) = id {
+            url.path_segments_mut()
+                .map_err(|_| Error::message(ErrorKind::Other, "Invalid endpoint URL"))?
+                .push(id);
+        }
+        url.query_pairs_mut()
+            .append_pair(azure_core::query_param::API_VERSION, "7.1-preview")
+            .append_pair("fileName", &self.file_name);
+        Ok(url)
+    }
+
+    async fn upload(mut self) -> azure_core::Result<AttachmentReference> {
+        let length = self.content.length;
+        let mut stream =
+            std::mem::replace(&mut self.content.stream, Box::pin(futures::stream::empty()));
+        let mut buffer = BytesMut::new();
+
+        // Detect the content type from the file name and the first chunk.
+        fill_buffer(&mut stream, &mut buffer, self.chunk_size).await?;
+        let content_type = upload_content_type(&self.file_name, &buffer);
+
+        if length <= self.chunk_size as u64 {
+            let content = next_chunk(&mut stream, &mut buffer, self.chunk_size).await?;
+            check_length(&mut stream, length, content.len() as u64).await?;
+            let mut url = self.url(None)?;
+            if let Some(area_path) = &self.area_path {
+                url.query_pairs_mut().append_pair("areaPath", area_path);
+            }
+            return send(&self.client, Method::Post, url, content_type, content, None)
+                .await?
+                .json()
+                .await;
+        }
+
+        // Start a chunked upload, then upload each chunk to the returned attachment id.
+        let mut url = self.url(None)?;
+        url.query_pairs_mut().append_pair("uploadType", "Chunked");
+        if let Some(area_path) = &self.area_path {
+            url.query_pairs_mut().append_pair("areaPath", area_path);
+        }
+        let attachment: AttachmentReference = send(
+            &self.client,
+            Method::Post,
+            url,
+            content_type,
+            Bytes::new(),
+            None,
+        )
+        .await?
+        .json()
+        .await?;
+        let id = attachment.id.as_deref().ok_or_else(|| {
+            Error::message(
+                ErrorKind::DataConversion,
+                "Chunked upload response has no attachment id",
+            )
+        })?;
+
+        let mut offset = 0;
+        while offset < length {
+            let chunk = next_chunk(&mut stream, &mut buffer, self.chunk_size).await?;
+            if chunk.is_empty() {
+                break;
+            }
+            let end = offset + chunk.len() as u64;
+            if end > length {
+                return Err(length_mismatch(length, end));
+            }
+            let content_range = content_range(offset, end, length);
+            send(
+                &self.client,
+                Method::Put,
+                self.url(Some(id))?,
+                content_type,
+                chunk,
+                Some(content_range),
+            )
+            .await?;
+            offset = end;
+        }
+        check_length(&mut stream, length, offset + buffer.len() as u64).await?;
+
+        Ok(attachment)
+    }
+}
+
+impl std::future::IntoFuture for UploadAttachmentBuilder {
+    type Output = azure_core::Result<AttachmentReference>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<AttachmentReference>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.upload())
+    }
+}
+
+/// `DownloadAttachmentBuilder` provides a mechanism for setting optional download parameters.
+///
+/// Invoke `.await` to start the download, which returns an [`AttachmentDownload`] whose body
+/// can be streamed, or use [`DownloadAttachmentBuilder::write_to`] to stream it straight to a writer.
+#[derive(Clone)]
+pub struct DownloadAttachmentBuilder {
+    client: crate::wit::Client,
+    organization: String,
+    project: String,
+    id: String,
+    file_name: Option<String>,
+}
+
+impl DownloadAttachmentBuilder {
+    /// Name of the file. Also used to detect the content type if the service doesn't report one.
+    #[must_use]
+    pub fn file_name(mut self, file_name: impl Into<String>) -> Self {
+        self.file_name = Some(file_name.into());
+        self
+    }
+
+    async fn download(self) -> azure_core::Result<AttachmentDownload> {
+        let mut url = Url::parse(&format!(
+            "{}/{}/{}/_apis/wit/attachments/{}",
+            self.client.endpoint(),
+            &self.organization,
+            &self.project,
+            &self.id
+        ))?;
+        url.query_pairs_mut()
+            .append_pair(azure_core::query_param::API_VERSION, "7.1-preview")
+            .append_pair("download", "true");
+        if let Some(file_name) = &self.file_name {
+            url.query_pairs_mut().append_pair("fileName", file_name);
+        }
+        let mut request = Request::new(url, Method::Get);
+        authorize(&self.client, &mut request).await?;
+        request.insert_header(headers::ACCEPT, "application/octet-stream");
+        let response = self.client.send(&mut request).await?;
+        let (_status, headers, body) = response.deconstruct();
+
+        let content_type = headers
+            .get_optional_str(&headers::CONTENT_TYPE)
+            .filter(|content_type| *content_type != "application/octet-stream")
+            .map(str::to_owned)
+            .or_else(|| {
+                self.file_name
+                    .as_deref()
+                    .and_then(content_type_from_file_name)
+                    .map(str::to_owned)
+            });
+        let content_length = headers
+            .get_optional_str(&headers::CONTENT_LENGTH)
+            .and_then(|length| length.parse().ok());
+        Ok(AttachmentDownload {
+            content_type,
+            content_length,
+            body,
+        })
+    }
+
+    /// Downloads the attachment to `writer`, returning the number of bytes written.
+    pub async fn write_to<W>(self, writer: &mut W) -> azure_core::Result<u64>
+    where
+        W: futures::io::AsyncWrite + Unpin,
+    {
+        self.download().await?.write_to(writer).await
+    }
+}
+
+impl std::future::IntoFuture for DownloadAttachmentBuilder {
+    type Output = azure_core::Result<AttachmentDownload>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<AttachmentDownload>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.download())
+    }
+}
+
+/// `AttachToWorkItemBuilder` provides a mechanism for setting optional relation parameters.
+///
+/// Invoke `.await` to add the relation, which returns the updated [`WorkItem`].
+#[derive(Clone)]
+pub struct AttachToWorkItemBuilder {
+    client: crate::wit::Client,
+    organization: String,
+    project: String,
+    work_item_id: i32,
+    url: Option<String>,
+    comment: Option<String>,
+}
+
+impl AttachToWorkItemBuilder {
+    /// Comment shown alongside the attachment.
+    #[must_use]
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.comment = Some(comment.into());
+        self
+    }
+}
+
+impl std::future::IntoFuture for AttachToWorkItemBuilder {
+    type Output = azure_core::Result<WorkItem>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<WorkItem>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            let url = self.url.ok_or_else(|| {
+                Error::message(ErrorKind::Other, "Attachment reference has no url")
+            })?;
+            let mut value = json!({ "rel": ATTACHED_FILE, "url": url });
+            if let Some(comment) = self.comment {
+                value["attributes"] = json!({ "comment": comment });
+            }
+            let operation = JsonPatchOperation {
+                op: Some(Op::Add),
+                path: Some("/relations/-".to_string()),
+                value: Some(value),
+                from: None,
+            };
+            self.client
+                .work_items_client()
+                .update(
+                    &self.organization,
+                    vec![operation],
+                    self.work_item_id,
+                    &self.project,
+                )
+                .await
+        })
+    }
+}
+
+async fn authorize(client: &crate::wit::Client, request: &mut Request) -> azure_core::Result<()> {
+    if let Some(auth_header) = client
+        .token_credential()
+        .http_authorization_header(&client.scopes())
+        .await?
+    {
+        request.insert_header(headers::AUTHORIZATION, auth_header);
+    }
+    Ok(())
+}
+
+async fn send(
+    client: &crate::wit::Client,
+    method: Method,
+    url: Url,
+    content_type: &'static str,
+    body: Bytes,
+    content_range: Option<String>,
+) -> azure_core::Result<Response> {
+    let mut request = Request::new(url, method);
+    authorize(client, &mut request).await?;
+    request.insert_header(headers::CONTENT_TYPE, content_type);
+    if let Some(content_range) = content_range {
+        request.insert_header("content-range", content_range);
+    }
+    request.set_body(body);
+    client.send(&mut request).await
+}
+
+/// Reads from `stream` until `buffer` holds at least `size` bytes (or the stream ends).
+async fn fill_buffer(
+    stream: &mut ByteStream,
+    buffer: &mut BytesMut,
+    size: usize,
+) -> azure_core::Result<()> {
+    while buffer.len() < size {
+        match stream.next().await {
+            Some(bytes) => buffer.extend_from_slice(&bytes?),
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+/// Reads from `stream` until `buffer` holds `chunk_size` bytes (or the stream ends),
+/// then splits off and returns up to `chunk_size` bytes.
+async fn next_chunk(
+    stream: &mut ByteStream,
+    buffer: &mut BytesMut,
+    chunk_size: usize,
+) -> azure_core::Result<Bytes> {
+    fill_buffer(stream, buffer, chunk_size).await?;
+    let size = buffer.len().min(chunk_size);
+    Ok(buffer.split_to(size).freeze())
+}
+
+async fn check_length(
+    stream: &mut ByteStream,
+    expected: u64,
+    mut actual: u64,
+) -> azure_core::Result<()> {
+    while let Some(bytes) = stream.next().await {
+        actual += bytes?.len() as u64;
+    }
+    if actual != expected {
+        return Err(length_mismatch(expected, actual));
+    }
+    Ok(())
+}
+
+fn length_mismatch(expected: u64, actual: u64) -> Error {
+    Error::message(
+        ErrorKind::Other,
+        format!("Attachment content length mismatch: expected {expected} bytes, got {actual}"),
+    )
+}
+
+/// Formats a `Content-Range` header value for the bytes `start..end` of a `total` byte upload.
+fn content_range(start: u64, end: u64, total: u64) -> String {
+    format!("bytes {}-{}/{}", start, end - 1, total)
+}
+
+/// Detects the content type of an attachment from its leading bytes, falling back to its file name.
+pub fn detect_content_type(file_name: &str, head: &[u8]) -> Option<&'static str> {
+    content_type_from_magic(head).or_else(|| content_type_from_file_name(file_name))
+}
+
+/// Content type sent with uploaded content: detected, or `application/octet-stream`.
+fn upload_content_type(file_name: &str, head: &[u8]) -> &'static str {
+    detect_content_type(file_name, head).unwrap_or("application/octet-stream")
+}
+
+fn content_type_from_magic(head: &[u8]) -> Option<&'static str> {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"MDMP", "application/x-dmp"),
+    ];
+    SIGNATURES
+        .iter()
+        .find(|(signature, _)| head.starts_with(signature))
+        .map(|(_, content_type)| *content_type)
+}
+
+fn content_type_from_file_name(file_name: &str) -> Option<&'static str> {
+    let (_, extension) = file_name.rsplit_once('.')?;
+    let content_type = match extension.to_ascii_lowercase().as_str() {
+        "txt" | "log" => "text/plain",
+        "csv" => "text/csv",
+        "htm" | "html" => "text/html",
+        "md" => "text/markdown",
+        "xml" => "application/xml",
+        "json" => "application/json",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "7z" => "application/x-7z-compressed",
+        "dmp" => "application/x-dmp",
+        _ => return None,
+    };
+    Some(content_type)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_range() {
+        assert_eq!(content_range(0, 40000, 50000), "bytes 0-39999/50000");
+        assert_eq!(
+            content_range(40000, 50000, 50000),
+            "bytes 40000-49999/50000"
+        );
+    }
+
+    #[test]
+    fn test_detect_content_type() {
+        assert_eq!(
+            detect_content_type("screenshot", b"\x89PNG\r\n\x1a\n...."),
+            Some("image/png")
+        );
+        assert_eq!(
+            detect_content_type("crash.dmp", b"MDMP\x93\xa7"),
+            Some("application/x-dmp")
+        );
+        assert_eq!(
+            detect_content_type("build.LOG", b"2024-01-01"),
+            Some("text/plain")
+        );
+        assert_eq!(detect_content_type("data.bin", b"\x00\x01"), None);
+        assert_eq!(
+            upload_content_type("data.bin", b"\x00\x01"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn test_next_chunk() {
+        futures::executor::block_on(async {
+            let chunks = ["abc", "defgh", "ij"].map(|s| Ok(Bytes::from(s)));
+            let mut stream: ByteStream = Box::pin(futures::stream::iter(chunks));
+            let mut buffer = BytesMut::new();
+            fill_buffer(&mut stream, &mut buffer, 2).await.unwrap();
+            assert_eq!(buffer, "abc");
+            assert_eq!(
+                next_chunk(&mut stream, &mut buffer, 4).await.unwrap(),
+                "abcd"
+            );
+            assert_eq!(
+                next_chunk(&mut stream, &mut buffer, 4).await.unwrap(),
+                "efgh"
+            );
+            assert_eq!(next_chunk(&mut stream, &mut buffer, 4).await.unwrap(), "ij");
+            assert!(next_chunk(&mut stream, &mut buffer, 4)
+                .await
+                .unwrap()
+                .is_empty());
+        });
+    }
+}
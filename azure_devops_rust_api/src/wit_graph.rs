@@ -0,0 +1,619 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT License.
+
+//! Work item link graph traversal.
+//!
+//! Walks the links between work items (e.g. Epic > Feature > User Story > Task) breadth-first,
+//! fetching work items in batches, and returns an in-memory [`WorkItemGraph`].
+//!
+//! ```rust
+//!     let graph = wit_client
+//!         .traverse_work_items(&organization, &project, [epic_id])
+//!         .link_types([wit_graph::HIERARCHY_FORWARD])
+//!         .max_depth(3)
+//!         .await?;
+//!     if let Some(tree) = graph.tree(epic_id) {
+//!         // ...
+//!     }
+//! ```
+
+use crate::wit::models::{
+    work_item_batch_get_request::{ErrorPolicy, Expand},
+    WorkItem, WorkItemBatchGetRequest,
+};
+use azure_core::error::{Error, ErrorKind};
+use futures::{StreamExt, TryStreamExt};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[cfg(not(target_arch = "wasm32"))]
+use futures::future::BoxFuture;
+#[cfg(target_arch = "wasm32")]
+use futures::future::LocalBoxFuture as BoxFuture;
+
+/// Parent to child link type.
+pub const HIERARCHY_FORWARD: &str = "System.LinkTypes.Hierarchy-Forward";
+/// Child to parent link type.
+pub const HIERARCHY_REVERSE: &str = "System.LinkTypes.Hierarchy-Reverse";
+/// Related link type.
+pub const RELATED: &str = "System.LinkTypes.Related";
+/// Successor link type.
+pub const DEPENDENCY_FORWARD: &str = "System.LinkTypes.Dependency-Forward";
+/// Predecessor link type.
+pub const DEPENDENCY_REVERSE: &str = "System.LinkTypes.Dependency-Reverse";
+
+/// Maximum number of work items that can be requested by a single `workitemsbatch` call.
+pub const MAX_BATCH_SIZE: usize = 200;
+
+/// A work item link type, as described by the `work_item_relation_types` API.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LinkType {
+    /// Reference name, e.g. `System.LinkTypes.Hierarchy-Forward`.
+    pub reference_name: String,
+    /// Display name, e.g. `Child`.
+    pub name: Option<String>,
+    /// Relation type attributes (`usage`, `topology`, `directional`, ...).
+    pub attributes: Option<serde_json::Value>,
+}
+
+/// A typed link between two work items in a [`WorkItemGraph`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorkItemLink {
+    /// Id of the work item that holds the relation.
+    pub source: i32,
+    /// Id of the linked work item.
+    pub target: i32,
+    /// The type of the link.
+    pub link_type: LinkType,
+    /// Attributes of this specific link (e.g. `comment`).
+    pub attributes: serde_json::Value,
+}
+
+/// A node of the spanning tree returned by [`WorkItemGraph::tree`].
+#[derive(Clone, Debug)]
+pub struct WorkItemTreeNode<'a> {
+    pub work_item: &'a WorkItem,
+    /// The link that was followed to reach this node (`None` for the root).
+    pub link: Option<&'a WorkItemLink>,
+    pub children: Vec<WorkItemTreeNode<'a>>,
+}
+
+/// In-memory graph of work items and the links between them.
+#[derive(Clone, Debug, Default)]
+pub struct WorkItemGraph {
+    roots: Vec<i32>,
+    work_items: BTreeMap<i32, WorkItem>,
+    depths: HashMap<i32, usize>,
+    links: Vec<WorkItemLink>,
+    missing: Vec<i32>,
+    truncated: Vec<i32>,
+}
+
+impl WorkItemGraph {
+    /// The ids that the traversal started from.
+    pub fn roots(&self) -> &[i32] {
+        &self.roots
+    }
+
+    /// Returns the work item with the given id, if it was fetched.
+    pub fn get(&self, id: i32) -> Option<&WorkItem> {
+        self.work_items.get(&id)
+    }
+
+    /// Iterates over all fetched work items, ordered by id.
+    pub fn work_items(&self) -> impl Iterator<Item = &WorkItem> {
+        self.work_items.values()
+    }
+
+    /// All links found between fetched work items.
+    pub fn links(&self) -> &[WorkItemLink] {
+        &self.links
+    }
+
+    /// Distance (in links) from the nearest root to the given work item.
+    pub fn depth(&self, id: i32) -> Option<usize> {
+        self.depths.get(&id).copied()
+    }
+
+    /// Linked work items that could not be fetched (deleted, or no permission to read).
+    pub fn missing(&self) -> &[i32] {
+        &self.missing
+    }
+
+    /// Work items at the depth limit whose links were not followed.
+    pub fn truncated(&self) -> &[i32] {
+        &self.truncated
+    }
+
+    /// Records the requested work items that were not fetched as missing.
+    fn record_missing(&mut self, requested: &[i32], fetched: &[WorkItem]) {
+        let fetched_ids: HashSet<i32> = fetched.iter().map(|work_item| work_item.id).collect();
+        self.missing
+            .extend(requested.iter().filter(|id| !fetched_ids.contains(id)));
+    }
+
+    /// Outgoing links of the given work item.
+    pub fn links_from(&self, id: i32) -> impl Iterator<Item = &WorkItemLink> {
+        self.links.iter().filter(move |link| link.source == id)
+    }
+
+    /// Incoming links of the given work item.
+    pub fn links_to(&self, id: i32) -> impl Iterator<Item = &WorkItemLink> {
+        self.links.iter().filter(move |link| link.target == id)
+    }
+
+    /// Work items directly linked from the given work item.
+    pub fn children(&self, id: i32) -> impl Iterator<Item = &WorkItem> {
+        self.links_from(id)
+            .filter_map(move |link| self.work_items.get(&link.target))
+    }
+
+    /// Builds a spanning tree from the given root.
+    ///
+    /// Each work item appears at most once, under the first link that reached it,
+    /// so diamonds and cycles in the link graph do not cause duplication.
+    pub fn tree(&self, root: i32) -> Option<WorkItemTreeNode<'_>> {
+        let work_item = self.work_items.get(&root)?;
+        let mut visited = HashSet::from([root]);
+        Some(self.subtree(work_item, None, &mut visited))
+    }
+
+    fn subtree<'a>(
+        &'a self,
+        work_item: &'a WorkItem,
+        link: Option<&'a WorkItemLink>,
+        visited: &mut HashSet<i32>,
+    ) -> WorkItemTreeNode<'a> {
+        // Claim all children before descending so that siblings are preferred over
+        // deeper paths, matching the breadth-first order of the traversal.
+        let children: Vec<_> = self
+            .links_from(work_item.id)
+            .filter(|link| visited.insert(link.target))
+            .filter_map(|link| Some((self.work_items.get(&link.target)?, link)))
+            .collect();
+        let children = children
+            .into_iter()
+            .map(|(child, link)| self.subtree(child, Some(link), visited))
+            .collect();
+        WorkItemTreeNode {
+            work_item,
+            link,
+            children,
+        }
+    }
+
+    /// Returns the ids of the work items forming a cycle, if the graph contains one.
+    ///
+    /// The first id is repeated at the end of the returned path.
+    pub fn find_cycle(&self) -> Option<Vec<i32>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            InProgress,
+            Done,
+        }
+
+        fn visit(
+            graph: &WorkItemGraph,
+            id: i32,
+            states: &mut HashMap<i32, State>,
+            path: &mut Vec<i32>,
+        ) -> Option<Vec<i32>> {
+            states.insert(id, State::InProgress);
+            path.push(id);
+            for link in graph.links_from(id) {
+                match states.get(&link.target) {
+                    Some(State::InProgress) => {
+                        let start = path.iter().position(|&p| p == link.target)?;
+                        let mut cycle = path[start..].to_vec();
+                        cycle.push(link.target);
+                        return Some(cycle);
+                    }
+                    Some(State::Done) => {}
+                    None => {
+                        if let Some(cycle) = visit(graph, link.target, states, path) {
+                            return Some(cycle);
+                        }
+                    }
+                }
+            }
+            path.pop();
+            states.insert(id, State::Done);
+            None
+        }
+
+        let mut states = HashMap::new();
+        for &id in self.work_items.keys() {
+            if !states.contains_key(&id) {
+                if let Some(cycle) = visit(self, id, &mut states, &mut Vec::new()) {
+                    return Some(cycle);
+                }
+            }
+        }
+        None
+    }
+}
+
+impl crate::wit::Client {
+    /// Traverses the work item link graph breadth-first, starting from `root_ids`.
+    ///
+    /// By default only [`HIERARCHY_FORWARD`] (parent to child) links are followed, with no depth limit.
+    ///
+    /// Arguments:
+    /// * `organization`: The name of the Azure DevOps organization.
+    /// * `project`: Project ID or project name
+    /// * `root_ids`: Ids of the work items to start from
+    pub fn traverse_work_items(
+        &self,
+        organization: impl Into<String>,
+        project: impl Into<String>,
+        root_ids: impl IntoIterator<Item = i32>,
+    ) -> TraverseBuilder {
+        TraverseBuilder {
+            client: self.clone(),
+            organization: organization.into(),
+            project: project.into(),
+            root_ids: root_ids.into_iter().collect(),
+            link_types: vec![HIERARCHY_FORWARD.to_string()],
+            max_depth: None,
+            batch_size: MAX_BATCH_SIZE,
+            concurrency: 4,
+        }
+    }
+}
+
+/// `TraverseBuilder` provides a mechanism for setting optional traversal parameters.
+///
+/// To run the traversal, invoke `.await`, which returns the resulting [`WorkItemGraph`].
+#[derive(Clone)]
+pub struct TraverseBuilder {
+    client: crate::wit::Client,
+    organization: String,
+    project: String,
+    root_ids: Vec<i32>,
+    link_types: Vec<String>,
+    max_depth: Option<usize>,
+    batch_size: usize,
+    concurrency: usize,
+}
+
+impl TraverseBuilder {
+    /// Link types to follow, by reference name (e.g. [`HIERARCHY_FORWARD`]) or display name (e.g. `Child`).
+    #[must_use]
+    pub fn link_types(mut self, link_types: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.link_types = link_types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Maximum number of links to follow from the roots.
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Number of work items requested per batch call (at most [`MAX_BATCH_SIZE`]).
+    #[must_use]
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.clamp(1, MAX_BATCH_SIZE);
+        self
+    }
+
+    /// Maximum number of batch requests in flight at once.
+    #[must_use]
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    async fn resolve_link_types(&self) -> azure_core::Result<Vec<LinkType>> {
+        let relation_types = self
+            .client
+            .work_item_relation_types_client()
+            .list(&self.organization)
+            .await?
+            .value;
+
+        self.link_types
+            .iter()
+            .map(|requested| {
+                let relation_type = relation_types
+                    .iter()
+                    .find(|relation_type| {
+                        let reference = &relation_type.work_item_tracking_reference;
+                        reference.reference_name.as_deref() == Some(requested.as_str())
+                            || reference.name.as_deref() == Some(requested.as_str())
+                    })
+                    .ok_or_else(|| {
+                        Error::message(
+                            ErrorKind::Other,
+                            format!("Unknown work item relation type: {requested}"),
+                        )
+                    })?;
+                let usage = relation_type
+                    .attributes
+                    .as_ref()
+                    .and_then(|attributes| attributes.get("usage"))
+                    .and_then(|usage| usage.as_str());
+                if usage.map_or(false, |usage| usage != "workItemLink") {
+                    return Err(Error::message(
+                        ErrorKind::Other,
+                        format!("Relation type {requested} does not link work items"),
+                    ));
+                }
+                let reference = &relation_type.work_item_tracking_reference;
+                Ok(LinkType {
+                    reference_name: reference
+                        .reference_name
+                        .clone()
+                        .unwrap_or_else(|| requested.clone()),
+                    name: reference.name.clone(),
+                    attributes: relation_type.attributes.clone(),
+                })
+            })
+            .collect()
+    }
+
+    async fn fetch_batch(&self, ids: Vec<i32>) -> azure_core::Result<Vec<WorkItem>> {
+        let bytes = self
+            .client
+            .work_items_client()
+            .get_work_items_batch(&self.organization, batch_request(ids), &self.project)
+            .send()
+            .await?
+            .into_raw_response()
+            .into_body()
+            .collect()
+            .await?;
+        parse_batch(&bytes)
+    }
+
+    async fn fetch(&self, ids: Vec<i32>) -> azure_core::Result<Vec<WorkItem>> {
+        let batches: Vec<Vec<WorkItem>> =
+            futures::stream::iter(ids.chunks(self.batch_size).map(<[i32]>::to_vec))
+                .map(|batch| self.fetch_batch(batch))
+                .buffer_unordered(self.concurrency)
+                .try_collect()
+                .await?;
+        Ok(batches.into_iter().flatten().collect())
+    }
+
+    async fn traverse(self) -> azure_core::Result<WorkItemGraph> {
+        let link_types = self.resolve_link_types().await?;
+
+        let mut graph = WorkItemGraph {
+            roots: self.root_ids.clone(),
+            ..Default::default()
+        };
+        let mut seen: HashSet<i32> = HashSet::new();
+        let mut frontier: Vec<i32> = self
+            .root_ids
+            .iter()
+            .copied()
+            .filter(|id| seen.insert(*id))
+            .collect();
+        let mut pending_links = Vec::new();
+        let mut depth = 0;
+
+        while !frontier.is_empty() {
+            let fetched = self.fetch(frontier.clone()).await?;
+            graph.record_missing(&frontier, &fetched);
+
+            let follow_links = self.max_depth.map_or(true, |max_depth| depth < max_depth);
+            let mut next = Vec::new();
+            for work_item in fetched {
+                graph.depths.insert(work_item.id, depth);
+                if follow_links {
+                    for (target, link_type, attributes) in work_item_links(&work_item, &link_types)
+                    {
+                        if seen.insert(target) {
+                            next.push(target);
+                        }
+                        pending_links.push(WorkItemLink {
+                            source: work_item.id,
+                            target,
+                            link_type: link_type.clone(),
+                            attributes,
+                        });
+                    }
+                } else if work_item_links(&work_item, &link_types).next().is_some() {
+                    graph.truncated.push(work_item.id);
+                }
+                graph.work_items.insert(work_item.id, work_item);
+            }
+
+            next.sort_unstable();
+            frontier = next;
+            depth += 1;
+        }
+
+        // Only keep links whose target was actually fetched.
+        graph.links = pending_links
+            .into_iter()
+            .filter(|link| graph.work_items.contains_key(&link.target))
+            .collect();
+        graph.missing.sort_unstable();
+        graph.truncated.sort_unstable();
+        Ok(graph)
+    }
+}
+
+impl std::future::IntoFuture for TraverseBuilder {
+    type Output = azure_core::Result<WorkItemGraph>;
+    type IntoFuture = BoxFuture<'static, azure_core::Result<WorkItemGraph>>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(self.traverse())
+    }
+}
+
+/// Builds a batch request that omits work items that can't be read, rather than failing.
+fn batch_request(ids: Vec<i32>) -> WorkItemBatchGetRequest {
+    WorkItemBatchGetRequest {
+        expand: Some(Expand::Relations),
+        error_policy: Some(ErrorPolicy::Omit),
+        ids,
+        ..Default::default()
+    }
+}
+
+/// Parses a batch response, skipping omitted work items.
+fn parse_batch(bytes: &[u8]) -> azure_core::Result<Vec<WorkItem>> {
+    // Omitted work items are returned as `null` entries, which the generated
+    // `WorkItemList` model can't represent.
+    #[derive(Deserialize)]
+    struct OptionalWorkItemList {
+        #[serde(default)]
+        value: Vec<Option<WorkItem>>,
+    }
+    let list: OptionalWorkItemList = serde_json::from_slice(bytes).map_err(|e| {
+        Error::full(
+            ErrorKind::DataConversion,
+            e,
+            format!(
+                "Failed to deserialize response:\n{}",
+                String::from_utf8_lossy(bytes)
+            ),
+        )
+    })?;
+    Ok(list.value.into_iter().flatten().collect())
+}
+
+/// Returns `(target_id, link_type, attributes)` for each relation of `work_item` matching `link_types`.
+fn work_item_links<'a>(
+    work_item: &'a WorkItem,
+    link_types: &'a [LinkType],
+) -> impl Iterator<Item = (i32, &'a LinkType, serde_json::Value)> + 'a {
+    work_item.relations.iter().filter_map(move |relation| {
+        let link = &relation.link;
+        let link_type = link_types
+            .iter()
+            .find(|link_type| link_type.reference_name == link.rel)?;
+        let target = work_item_id_from_url(&link.url)?;
+        Some((target, link_type, link.attributes.clone()))
+    })
+}
+
+/// Extracts the work item id from a work item REST URL
+/// (e.g. `https://dev.azure.com/{organization}/_apis/wit/workItems/{id}`).
+pub fn work_item_id_from_url(url: &str) -> Option<i32> {
+    let (prefix, id) = url.trim_end_matches('/').rsplit_once('/')?;
+    if !prefix.to_ascii_lowercase().ends_with("/workitems") {
+        return None;
+    }
+    id.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wit::models::{
+        Link, WorkItemRelation, WorkItemTrackingResource, WorkItemTrackingResourceReference,
+    };
+    use serde_json::json;
+
+    fn child_link_type() -> LinkType {
+        LinkType {
+            reference_name: HIERARCHY_FORWARD.to_string(),
+            name: Some("Child".to_string()),
+            attributes: None,
+        }
+    }
+
+    fn work_item(id: i32, children: &[i32]) -> WorkItem {
+        let url = format!("https://dev.azure.com/org/_apis/wit/workItems/{id}");
+        let mut work_item = WorkItem::new(
+            WorkItemTrackingResource::new(WorkItemTrackingResourceReference::new(url)),
+            json!({}),
+            id,
+        );
+        work_item.relations = children
+            .iter()
+            .map(|child| {
+                WorkItemRelation::new(Link::new(
+                    json!({}),
+                    HIERARCHY_FORWARD.to_string(),
+                    format!("https://dev.azure.com/org/_apis/wit/workItems/{child}"),
+                ))
+            })
+            .collect();
+        work_item
+    }
+
+    fn graph(items: &[(i32, &[i32])]) -> WorkItemGraph {
+        let link_types = [child_link_type()];
+        let mut graph = WorkItemGraph {
+            roots: vec![items[0].0],
+            ..Default::default()
+        };
+        for (id, children) in items {
+            let work_item = work_item(*id, children);
+            graph
+                .links
+                .extend(work_item_links(&work_item, &link_types).map(
+                    |(target, link_type, attributes)| WorkItemLink {
+                        source: *id,
+                        target,
+                        link_type: link_type.clone(),
+                        attributes,
+                    },
+                ));
+            graph.work_items.insert(*id, work_item);
+        }
+        graph
+    }
+
+    #[test]
+    fn test_work_item_id_from_url() {
+        assert_eq!(
+            work_item_id_from_url("https://dev.azure.com/org/_apis/wit/workItems/42"),
+            Some(42)
+        );
+        assert_eq!(
+            work_item_id_from_url("https://dev.azure.com/org/proj/_apis/wit/workitems/7/"),
+            Some(7)
+        );
+        assert_eq!(
+            work_item_id_from_url("https://dev.azure.com/org/_apis/wit/attachments/1234"),
+            None
+        );
+        assert_eq!(work_item_id_from_url("vstfs:///Git/Commit/abc"), None);
+    }
+
+    #[test]
+    fn test_tree_visits_each_work_item_once() {
+        // 1 -> 2, 3; 2 -> 4; 3 -> 4
+        let graph = graph(&[(1, &[2, 3]), (2, &[4]), (3, &[4]), (4, &[])]);
+        let tree = graph.tree(1).unwrap();
+        let child_ids: Vec<i32> = tree.children.iter().map(|c| c.work_item.id).collect();
+        assert_eq!(child_ids, vec![2, 3]);
+        assert_eq!(tree.children[0].children.len(), 1);
+        assert_eq!(tree.children[0].children[0].work_item.id, 4);
+        assert!(tree.children[1].children.is_empty());
+        assert_eq!(graph.find_cycle(), None);
+    }
+
+    #[test]
+    fn test_batch_omits_missing_work_items() {
+        let request = serde_json::to_value(batch_request(vec![1, 2, 3])).unwrap();
+        assert_eq!(request["errorPolicy"], "omit");
+        assert_eq!(request["ids"], json!([1, 2, 3]));
+
+        let response = json!({
+            "count": 3,
+            "value": [serde_json::to_value(work_item(1, &[])).unwrap(), null, null]
+        });
+        let fetched = parse_batch(&serde_json::to_vec(&response).unwrap()).unwrap();
+        assert_eq!(fetched.len(), 1);
+        let mut graph = WorkItemGraph::default();
+        graph.record_missing(&[1, 2, 3], &fetched);
+        assert_eq!(graph.missing(), [2, 3]);
+    }
+
+    #[test]
+    fn test_find_cycle() {
+        let graph = graph(&[(1, &[2]), (2, &[3]), (3, &[1])]);
+        assert_eq!(graph.find_cycle(), Some(vec![1, 2, 3, 1]));
+        let tree = graph.tree(1).unwrap();
+        assert_eq!(tree.children[0].children[0].children.len(), 0);
+    }
+}
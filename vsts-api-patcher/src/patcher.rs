@@ -99,6 +99,8 @@ impl Patcher {
         Patcher::patch_jobjects,
         Patcher::patch_identity_descriptors,
         Patcher::patch_security,
+        Patcher::patch_approvals,
+        Patcher::patch_variable_groups,
         Patcher::patch_response_schema,
         // This must be done after the other patches
         Patcher::patch_definition_required_fields,
@@ -442,6 +444,95 @@ impl Patcher {
         }
     }
 
+    fn patch_approvals(&mut self, key: &[&str], value: &JsonValue) -> Option<JsonValue> {
+        // Only applies to pipelines approval specs
+        if !self.spec_path.ends_with("pipelinesapproval.json") {
+            return None;
+        }
+        match key {
+            ["definitions", "Approval", "properties"] => {
+                println!("Add pipeline to Approval definition");
+                self.new_definitions.insert(
+                    "ApprovalPipelineReference".to_string(),
+                    json::object! {
+                        "description": "The pipeline whose run is waiting for an approval.",
+                        "type": "object",
+                        "properties": {
+                            "id": {
+                                "description": "ID of the pipeline definition.",
+                                "type": "string"
+                            },
+                            "name": {
+                                "description": "Name of the pipeline definition.",
+                                "type": "string"
+                            },
+                            "owner": {
+                                "$ref": "#/definitions/ApprovalPipelineOwner"
+                            }
+                        }
+                    },
+                );
+                self.new_definitions.insert(
+                    "ApprovalPipelineOwner".to_string(),
+                    json::object! {
+                        "description": "The pipeline run waiting for an approval.",
+                        "type": "object",
+                        "properties": {
+                            "id": {
+                                "description": "ID of the pipeline run.",
+                                "type": "integer",
+                                "format": "int32"
+                            },
+                            "name": {
+                                "description": "Name of the pipeline run.",
+                                "type": "string"
+                            }
+                        }
+                    },
+                );
+                let mut value = value.clone();
+                value["pipeline"] = json::object! {
+                    "$ref": "#/definitions/ApprovalPipelineReference"
+                };
+                Some(value)
+            }
+            ["definitions", "ApprovalUpdateParameters", "properties"] => {
+                println!("Add deferredTo to ApprovalUpdateParameters definition");
+                let mut value = value.clone();
+                value["deferredTo"] = json::object! {
+                    "description": "Defer the approval until this time.",
+                    "type": "string",
+                    "format": "date-time"
+                };
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Patch VariableGroup and VariableGroupParameters
+    ///
+    /// `providerData` is spec'd as `VariableGroupProviderData`, which has no properties, so the
+    /// provider data of a group (e.g. the Key Vault it is linked to) is lost when a group is
+    /// deserialized and sent back. Patch it to be an untyped JSON object.
+    fn patch_variable_groups(&mut self, key: &[&str], value: &JsonValue) -> Option<JsonValue> {
+        if !self.spec_path.ends_with("taskAgent.json") {
+            return None;
+        }
+        match key {
+            ["definitions", "VariableGroup" | "VariableGroupParameters", "properties"] => {
+                println!("Patch providerData of {} definition", key[1]);
+                let mut value = value.clone();
+                value["providerData"] = json::object! {
+                    "description": "Defines provider data of the variable group.",
+                    "type": "object"
+                };
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
     fn patch_pipelines_reference_links(
         &mut self,
         key: &[&str],
@@ -826,6 +917,10 @@ impl Patcher {
                           "autoCompleteSetBy": {
                             "description": "If set, auto-complete is enabled for this pull request and this is the identity that enabled it.",
                             "$ref": "#/definitions/IdentityRef"
+                          },
+                          "completionOptions": {
+                            "description": "Options which affect how the pull request will be merged when it is completed.",
+                            "$ref": "#/definitions/GitPullRequestCompletionOptions"
                           }
                         }
                     },
@@ -1037,13 +1132,13 @@ impl Patcher {
                 "ServiceEndpoint",
                 // Excluded
                 //   administratorsGroup
+                //   createdBy (not set when creating an endpoint)
+                //   id (not set when creating an endpoint)
                 //   operationStatus
                 r#"[
                     "authorization",
-                    "createdBy",
                     "data",
                     "description",
-                    "id",
                     "isReady",
                     "isShared",
                     "name",